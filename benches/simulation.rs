@@ -0,0 +1,121 @@
+//! Sampling, summary statistics, plot rendering and a whole `update` pass.
+//! Run with `cargo bench`; compare against a saved baseline with
+//! `cargo bench -- --save-baseline before` then `--baseline before`.
+
+use criterion::{ black_box, BatchSize, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput };
+use serde_json::json;
+
+use rusty_shiny::config::Config;
+use rusty_shiny::plot::{ get_dist, get_plot, get_plot_json, make_rng, sample, Distribution, PlotOptions, PlotType };
+use rusty_shiny::server::CustomServer;
+use rusty_shiny::stats::{ density, quantile, Bandwidth, Summary };
+use rusty_shiny::testing::Driver;
+
+const SIZES: [usize; 4] = [100, 10_000, 100_000, 1_000_000];
+
+fn normal() -> Distribution {
+    Distribution::from_inputs("normal", 0.0, 1.0)
+}
+
+fn values(n: usize) -> Vec<f64> {
+    get_dist(n, &normal(), &mut make_rng(Some(42))).unwrap()
+}
+
+fn sampling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sampling");
+    for n in SIZES {
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("get_dist", n), &n, |b, &n| {
+            let mut rng = make_rng(Some(42));
+            b.iter(|| get_dist(n, &normal(), &mut rng).unwrap());
+        });
+        // With the default threshold, the largest sizes are drawn on all
+        // cores.
+        group.bench_with_input(BenchmarkId::new("sample", n), &n, |b, &n| {
+            let threshold = Config::default().parallel_threshold;
+            b.iter(|| sample(n, &normal(), Some(42), threshold).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn statistics(c: &mut Criterion) {
+    let mut group = c.benchmark_group("statistics");
+    for n in SIZES {
+        let values = values(n);
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("summary", n), &values, |b, values| {
+            b.iter(|| Summary::of(values));
+        });
+        group.bench_with_input(BenchmarkId::new("quantile", n), &sorted, |b, sorted| {
+            b.iter(|| quantile(sorted, black_box(0.95)));
+        });
+        // Quadratic in practice, the largest samples would take minutes.
+        if n <= 10_000 {
+            group.bench_with_input(BenchmarkId::new("density", n), &values, |b, values| {
+                b.iter(|| density(values, 512, Bandwidth::default()));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn plots(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plots");
+    let options = PlotOptions::default();
+    for n in [100, 10_000] {
+        let (first, second) = (values(n), values(n));
+        let series: [(&[f64], &str); 2] = [(&first, "dist1"), (&second, "dist2")];
+        for (name, plot_type) in [("histogram", PlotType::Histogram), ("density", PlotType::Density)] {
+            group.bench_function(BenchmarkId::new(format!("get_plot/{}", name), n), |b| {
+                b.iter(|| get_plot(&series, plot_type, &options));
+            });
+            group.bench_function(BenchmarkId::new(format!("get_plot_json/{}", name), n), |b| {
+                b.iter(|| get_plot_json(&series, plot_type, &options));
+            });
+        }
+    }
+    group.finish();
+}
+
+/// A started session whose panels draw `n` values.
+fn driver(n: usize) -> Driver {
+    Driver::with_inputs(CustomServer::builder().build(), &[
+        ("current_tab", json!("Simulation")),
+        ("dist1-dist_type", json!("normal")),
+        ("dist2-dist_type", json!("gamma")),
+        ("dist1-n:shiny.number", json!(n)),
+        ("dist2-n:shiny.number", json!(n)),
+        ("dist1-mean:shiny.number", json!(0)),
+        ("dist1-sd:shiny.number", json!(1)),
+        ("dist2-mean:shiny.number", json!(2)),
+        ("dist2-sd:shiny.number", json!(1)),
+        ("plot_type", json!("histogram")),
+    ])
+}
+
+/// A change of sample size, going through validation, sampling, the plot
+/// and the summary table as it does when a user edits the input.
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for n in [100, 1000] {
+        // Every pass gets a new session, as a session's rate limit would
+        // soon drop the updates of a busy loop.
+        group.bench_function(BenchmarkId::new("sample_size", n), |b| {
+            b.iter_batched(
+                || driver(n),
+                |mut driver| {
+                    driver.set_number("dist1-n", (n + 1) as f64);
+                    driver
+                },
+                BatchSize::LargeInput
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sampling, statistics, plots, update);
+criterion_main!(benches);
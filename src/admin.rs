@@ -0,0 +1,119 @@
+//! The `/admin` page: every connected session, with a button to disconnect
+//! it. Only signed in admins may use it, so it's disabled when the app is
+//! open to anyone.
+
+use std::time::Duration;
+use actix::{ Message, MessageResponse };
+use actix_web::{ error, http::header, web, Error, HttpMessage, HttpRequest, HttpResponse };
+use chrono::{ DateTime, Utc };
+use futures_util::future::join_all;
+
+use super::auth::User;
+use super::registry::SessionRegistry;
+use super::roles::Role;
+use super::stats::escape_html;
+
+/// Asks a session what the admin page shows about it.
+#[derive(Message)]
+#[rtype(result = "SessionInfo")]
+pub struct GetSessionInfo;
+
+#[derive(MessageResponse)]
+pub struct SessionInfo {
+    pub id: String,
+    pub connected_at: DateTime<Utc>,
+    pub since_heartbeat: Duration,
+    pub input_events: u64,
+    /// Settings of every distribution panel, a line each.
+    pub parameters: Vec<String>,
+}
+
+/// Closes a session's websocket.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect;
+
+/// Fails unless `req` was made by a signed in admin. Requests reaching the
+/// page without a user only get through `auth::require_login` when the
+/// app is open to anyone.
+fn check(req: &HttpRequest) -> Result<(), Error> {
+    match req.extensions().get::<User>() {
+        Some(user) if user.role.allows(Role::Admin) => Ok(()),
+        Some(_) => Err(error::ErrorForbidden("Only admins may open this page")),
+        None => Err(error::ErrorNotFound("The admin page is disabled")),
+    }
+}
+
+fn session_row(info: &SessionInfo) -> String {
+    let parameters: Vec<String> = info.parameters.iter().map(|line| escape_html(line)).collect();
+    format!(
+        r#"<tr><td><code>{id}</code></td><td>{}</td><td>{:.1} s ago</td><td>{}</td><td>{}</td>
+<td><form method="post" action="admin/disconnect/{id}"><button type="submit">Disconnect</button></form></td></tr>"#,
+        info.connected_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        info.since_heartbeat.as_secs_f64(),
+        info.input_events,
+        parameters.join("<br>"),
+        id = escape_html(&info.id)
+    )
+}
+
+fn admin_page(sessions: &[SessionInfo]) -> String {
+    let rows: String = sessions.iter().map(session_row).collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Sessions</title>
+<style>
+body {{ margin: 2em; font-family: sans-serif; color: #222; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; vertical-align: top; }}
+</style>
+</head>
+<body>
+<h1>{} connected sessions</h1>
+<table>
+<thead><tr><th>Session</th><th>Connected</th><th>Last heartbeat</th><th>Input events</th><th>Parameters</th><th></th></tr></thead>
+<tbody>{}</tbody>
+</table>
+</body>
+</html>
+"#,
+        sessions.len(),
+        rows
+    )
+}
+
+pub async fn admin(
+    req: HttpRequest,
+    registry: web::Data<SessionRegistry>
+) -> Result<HttpResponse, Error> {
+    check(&req)?;
+    let sessions = registry.all();
+    // Sessions that stopped since they were listed are left out.
+    let mut sessions: Vec<SessionInfo> = join_all(sessions.iter().map(|addr| addr.send(GetSessionInfo)))
+        .await
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect();
+    sessions.sort_by_key(|info| info.connected_at);
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(admin_page(&sessions)))
+}
+
+pub async fn disconnect(
+    req: HttpRequest,
+    registry: web::Data<SessionRegistry>,
+    id: web::Path<String>
+) -> Result<HttpResponse, Error> {
+    check(&req)?;
+    let addr = registry
+        .get(&id)
+        .ok_or_else(|| error::ErrorNotFound("Unknown session"))?;
+    addr.do_send(Disconnect);
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "../../admin"))
+        .finish())
+}
@@ -0,0 +1,73 @@
+//! JSON API drawing samples with the same code as the app, e.g.
+//! `/api/v1/simulate?dist=gamma&n=100&mean=2&sd=1&seed=42`. `mean` and `sd`
+//! are the two parameters of the family, as in the distribution panels.
+
+use actix_web::{ error, web, Error, HttpResponse };
+use serde::Deserialize;
+use serde_json::json;
+
+use super::cache::SampleCache;
+use super::config::Config;
+use super::dist_module::draw;
+use super::metrics::AppMetrics;
+use super::plot::Distribution;
+use super::stats::Summary;
+
+/// Families `Distribution::from_inputs` knows, by input value.
+const DISTRIBUTIONS: [&str; 6] = ["normal", "uniform", "exponential", "gamma", "poisson", "lognormal"];
+
+fn default_dist() -> String {
+    String::from("normal")
+}
+
+fn default_sd() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+pub struct SimulateQuery {
+    #[serde(default = "default_dist")]
+    dist: String,
+    n: u64,
+    #[serde(default)]
+    mean: f64,
+    #[serde(default = "default_sd")]
+    sd: f64,
+    seed: Option<u64>,
+}
+
+pub async fn simulate(
+    query: web::Query<SimulateQuery>,
+    config: web::Data<Config>,
+    cache: web::Data<SampleCache>,
+    metrics: web::Data<AppMetrics>
+) -> Result<HttpResponse, Error> {
+    if !DISTRIBUTIONS.contains(&query.dist.as_str()) {
+        return Err(error::ErrorBadRequest(format!(
+            "Unknown distribution {:?}, expected one of {}",
+            query.dist,
+            DISTRIBUTIONS.join(", ")
+        )));
+    }
+    if query.n == 0 || query.n > config.max_sample_size {
+        return Err(error::ErrorBadRequest(format!(
+            "n must be between 1 and {}",
+            config.max_sample_size
+        )));
+    }
+    let dist = Distribution::from_inputs(&query.dist, query.mean, query.sd);
+    let key = (query.n, dist, query.seed);
+    let threshold = config.parallel_threshold;
+    let values = web::block(move || draw(&cache, &metrics, &key, threshold).map_err(|e| e.to_string()))
+        .await?
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(json!({
+        "distribution": query.dist,
+        "n": query.n,
+        "mean": query.mean,
+        "sd": query.sd,
+        "seed": query.seed,
+        "summary": Summary::of(&values),
+        "values": values
+    })))
+}
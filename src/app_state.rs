@@ -0,0 +1,89 @@
+//! State shared by every session. Sessions join `AppState` when they start
+//! and leave when they stop, so a message from one session can reach all
+//! of them.
+
+use std::collections::HashMap;
+use actix::{ Actor, Context, Handler, Message, Recipient };
+
+use super::chat::{ ChatHistory, ChatMessage };
+
+/// Shown as a notification in every session.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct Announcement(pub String);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Join {
+    pub id: String,
+    pub announcements: Recipient<Announcement>,
+    pub chat: Recipient<ChatMessage>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Leave(pub String);
+
+/// Sends the announcement to every session, including the sender.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Broadcast(pub Announcement);
+
+/// Adds a message to the chat, sending it to every session.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatPost(pub ChatMessage);
+
+struct Member {
+    announcements: Recipient<Announcement>,
+    chat: Recipient<ChatMessage>,
+}
+
+#[derive(Default)]
+pub struct AppState {
+    sessions: HashMap<String, Member>,
+    chat: ChatHistory,
+}
+
+impl Actor for AppState {
+    type Context = Context<Self>;
+}
+
+impl Handler<Join> for AppState {
+    type Result = ();
+    fn handle(&mut self, msg: Join, _ctx: &mut Self::Context) {
+        for message in self.chat.iter() {
+            msg.chat.do_send(message.clone());
+        }
+        self.sessions.insert(msg.id, Member {
+            announcements: msg.announcements,
+            chat: msg.chat,
+        });
+    }
+}
+
+impl Handler<Leave> for AppState {
+    type Result = ();
+    fn handle(&mut self, msg: Leave, _ctx: &mut Self::Context) {
+        self.sessions.remove(&msg.0);
+    }
+}
+
+impl Handler<Broadcast> for AppState {
+    type Result = ();
+    fn handle(&mut self, msg: Broadcast, _ctx: &mut Self::Context) {
+        for member in self.sessions.values() {
+            member.announcements.do_send(msg.0.clone());
+        }
+    }
+}
+
+impl Handler<ChatPost> for AppState {
+    type Result = ();
+    fn handle(&mut self, msg: ChatPost, _ctx: &mut Self::Context) {
+        for member in self.sessions.values() {
+            member.chat.do_send(msg.0.clone());
+        }
+        self.chat.push(msg.0);
+    }
+}
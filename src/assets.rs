@@ -0,0 +1,67 @@
+//! The pages and their scripts and styles, from `static/`. Release builds
+//! embed the directory, so the binary can be deployed on its own; debug
+//! builds read it from disk on every request, so edits show up on reload.
+//! Either way `static/lib` must have been rendered from `ui.R` first.
+//!
+//! Files under `/lib` have the version of their library in their path, so
+//! clients may cache them for good. The pages and `/www` are revalidated
+//! every time, with their hash as the ETag.
+
+use actix_web::http::header::{ CacheControl, CacheDirective, EntityTag, ETag, IfNoneMatch };
+use actix_web::{ web, HttpMessage, HttpRequest, HttpResponse };
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "static/"]
+#[exclude = "ui.R"]
+#[exclude = ".gitignore"]
+struct Assets;
+
+const YEAR: u32 = 365 * 24 * 60 * 60;
+
+fn serve(req: &HttpRequest, path: &str, immutable: bool) -> HttpResponse {
+    let file = match Assets::get(path) {
+        Some(file) => file,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let tag = EntityTag::new_strong(
+        file.metadata.sha256_hash().iter().map(|byte| format!("{:02x}", byte)).collect()
+    );
+    let cache = if immutable {
+        CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(YEAR),
+            CacheDirective::Extension(String::from("immutable"), None),
+        ])
+    } else {
+        CacheControl(vec![CacheDirective::NoCache])
+    };
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|other| other.weak_eq(&tag)),
+        None => false,
+    };
+    if unchanged {
+        return HttpResponse::NotModified().insert_header(ETag(tag)).insert_header(cache).finish();
+    }
+    HttpResponse::Ok()
+        .content_type(file.metadata.mimetype())
+        .insert_header(ETag(tag))
+        .insert_header(cache)
+        .body(file.data.into_owned())
+}
+
+/// The page rendered from `ui.R` into `file`.
+pub fn html(req: &HttpRequest, file: &str) -> HttpResponse {
+    serve(req, file, false)
+}
+
+/// A file of the libraries rendered from `ui.R`.
+pub async fn lib(req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    serve(&req, &format!("lib/{}", path), true)
+}
+
+/// A file of the app's own scripts and styles.
+pub async fn www(req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    serve(&req, &format!("www/{}", path), false)
+}
@@ -0,0 +1,270 @@
+//! Signing in. Users are listed in `config.toml` with the hash of their
+//! password, and the app is open to anyone when there are none. Signing in
+//! sets a cookie, which `require_login` checks on every route but the login
+//! page and the static files, so a websocket can only be opened by a
+//! signed in user. A sign in lasts `login_ttl`; the cookie is only sent
+//! over HTTPS when TLS is on.
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+use actix_web::body::BoxBody;
+use actix_web::cookie::{ time, Cookie, SameSite };
+use actix_web::dev::{ ServiceRequest, ServiceResponse };
+use actix_web::middleware::Next;
+use actix_web::{ http::header, web, Error, HttpMessage, HttpRequest, HttpResponse };
+use argon2::{ Argon2, PasswordHash, PasswordHasher, PasswordVerifier };
+use argon2::password_hash::{ rand_core::OsRng, SaltString };
+use serde::Deserialize;
+
+use super::clock::Clock;
+use super::config::Account;
+use super::roles::Role;
+use super::stats::escape_html;
+
+const COOKIE: &str = "rusty_shiny_auth";
+
+/// Routes reachable without signing in, at the root or in a page.
+const PUBLIC_PREFIXES: [&str; 3] = ["login", "lib/", "www/"];
+
+fn is_public(path: &str) -> bool {
+    let path = path.trim_start_matches('/');
+    let in_page = path.split_once('/').map_or("", |(_, rest)| rest);
+    PUBLIC_PREFIXES.iter().any(|prefix| path.starts_with(prefix) || in_page.starts_with(prefix))
+}
+
+/// The signed in user, added to the extensions of authenticated requests.
+#[derive(Clone)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+struct Login {
+    user: User,
+    at: Instant,
+}
+
+/// The accounts allowed to sign in, and who is signed in with which
+/// cookie.
+#[derive(Clone, Default)]
+pub struct Auth {
+    accounts: Arc<Vec<Account>>,
+    logins: Arc<Mutex<HashMap<String, Login>>>,
+    /// How long a sign in lasts.
+    ttl: Duration,
+    /// Whether the cookie is only sent over HTTPS.
+    secure: bool,
+    clock: Clock,
+}
+
+impl Auth {
+    /// Signs users in for `ttl`, with a cookie only sent over HTTPS if
+    /// `secure`.
+    pub fn new(accounts: Vec<Account>, ttl: Duration, secure: bool) -> Self {
+        Auth { accounts: Arc::new(accounts), logins: Arc::default(), ttl, secure, clock: Clock::default() }
+    }
+
+    /// Whether anyone may use the app without signing in.
+    pub fn is_open(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// The account of `name`, if `password` is theirs.
+    fn verify(&self, name: &str, password: &str) -> Option<&Account> {
+        let account = self.accounts.iter().find(|account| account.name == name)?;
+        let hash = PasswordHash::new(&account.password_hash).ok()?;
+        Argon2::default().verify_password(password.as_bytes(), &hash).ok()?;
+        Some(account)
+    }
+
+    /// Signs `account` in, returning the token of their cookie. Sign ins
+    /// that ran out are dropped.
+    fn sign_in(&self, account: &Account) -> String {
+        let token = format!("{:032x}", rand::random::<u128>());
+        let user = User { name: account.name.clone(), role: account.role };
+        let mut logins = self.logins.lock().unwrap();
+        logins.retain(|_, login| self.clock.since(login.at) < self.ttl);
+        logins.insert(token.clone(), Login { user, at: self.clock.now() });
+        token
+    }
+
+    /// The user signed in with `token`, if it hasn't run out.
+    fn signed_in(&self, token: &str) -> Option<User> {
+        let mut logins = self.logins.lock().unwrap();
+        match logins.get(token) {
+            Some(login) if self.clock.since(login.at) < self.ttl => Some(login.user.clone()),
+            Some(_) => {
+                logins.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn user(&self, req: &ServiceRequest) -> Option<User> {
+        self.signed_in(req.cookie(COOKIE)?.value())
+    }
+
+    fn cookie(&self, token: String) -> Cookie<'static> {
+        let max_age = time::Duration::seconds(self.ttl.as_secs().try_into().unwrap_or(i64::MAX));
+        Cookie::build(COOKIE, token)
+            .path("/")
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(SameSite::Lax)
+            .max_age(max_age)
+            .finish()
+    }
+}
+
+/// Hash of `password` to list in the `users` setting.
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+/// Lets signed in users through, adding their `User` to the request. Others
+/// are sent to the login page, or refused if they weren't asking for a page.
+pub async fn require_login(
+    req: ServiceRequest,
+    next: Next<BoxBody>
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let auth = req.app_data::<web::Data<Auth>>().map(|auth| auth.get_ref().clone());
+    let auth = match auth {
+        Some(auth) if !auth.is_open() => auth,
+        _ => return next.call(req).await,
+    };
+    if is_public(req.path()) {
+        return next.call(req).await;
+    }
+    if let Some(user) = auth.user(&req) {
+        req.extensions_mut().insert(user);
+        return next.call(req).await;
+    }
+    // Pages end with a slash; the login page is at the root.
+    let response = if req.path().ends_with('/') {
+        let depth = req.path().matches('/').count() - 1;
+        let location = format!("{}login", "../".repeat(depth));
+        HttpResponse::SeeOther().insert_header((header::LOCATION, location)).finish()
+    } else {
+        HttpResponse::Unauthorized().body("Sign in first")
+    };
+    Ok(req.into_response(response))
+}
+
+fn login_page(error: Option<&str>) -> String {
+    let error = error.map_or(String::new(), |error| {
+        format!("<p class=\"error\">{}</p>", escape_html(error))
+    });
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Sign in</title>
+<style>
+body {{ max-width: 20em; margin: 4em auto; font-family: sans-serif; color: #222; }}
+label, input, button {{ display: block; width: 100%; margin-bottom: 0.75em; }}
+.error {{ color: #b00; }}
+</style>
+</head>
+<body>
+<h1>Sign in</h1>
+{}
+<form method="post" action="login">
+<label>Username <input name="username" autocomplete="username" required autofocus></label>
+<label>Password <input name="password" type="password" autocomplete="current-password" required></label>
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#,
+        error
+    )
+}
+
+pub async fn login_form() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(login_page(None))
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+pub async fn login(auth: web::Data<Auth>, form: web::Form<Credentials>) -> HttpResponse {
+    let account = match auth.verify(&form.username, &form.password) {
+        Some(account) => account,
+        None => {
+            tracing::warn!(user = %form.username, "failed sign in");
+            return HttpResponse::Unauthorized()
+                .content_type("text/html; charset=utf-8")
+                .body(login_page(Some("Wrong username or password")));
+        }
+    };
+    tracing::info!(user = %account.name, role = ?account.role, "signed in");
+    let cookie = auth.cookie(auth.sign_in(account));
+    HttpResponse::SeeOther()
+        .cookie(cookie)
+        .insert_header((header::LOCATION, "./"))
+        .finish()
+}
+
+pub async fn logout(req: HttpRequest, auth: web::Data<Auth>) -> HttpResponse {
+    if let Some(cookie) = req.cookie(COOKIE) {
+        auth.logins.lock().unwrap().remove(cookie.value());
+    }
+    let mut cookie = Cookie::build(COOKIE, "").path("/").secure(auth.secure).finish();
+    cookie.make_removal();
+    HttpResponse::SeeOther()
+        .cookie(cookie)
+        .insert_header((header::LOCATION, "login"))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> Account {
+        Account { name: String::from("ana"), password_hash: String::new(), role: Role::Editor }
+    }
+
+    #[test]
+    fn sign_ins_run_out() {
+        let auth = Auth::new(vec![account()], Duration::from_secs(3600), false);
+        auth.clock.freeze();
+        let token = auth.sign_in(&account());
+        auth.clock.advance(Duration::from_secs(3599));
+        assert_eq!(auth.signed_in(&token).map(|user| user.name), Some(String::from("ana")));
+        auth.clock.advance(Duration::from_secs(1));
+        assert!(auth.signed_in(&token).is_none());
+        assert!(auth.logins.lock().unwrap().is_empty(), "removed once run out");
+    }
+
+    #[test]
+    fn sign_ins_prune_those_run_out() {
+        let auth = Auth::new(vec![account()], Duration::from_secs(3600), false);
+        auth.clock.freeze();
+        auth.sign_in(&account());
+        auth.clock.advance(Duration::from_secs(3600));
+        let token = auth.sign_in(&account());
+        let logins = auth.logins.lock().unwrap();
+        assert_eq!(logins.keys().collect::<Vec<_>>(), [&token]);
+    }
+
+    #[test]
+    fn cookies_are_secure_with_tls() {
+        let ttl = Duration::from_secs(3600);
+        let cookie = Auth::new(vec![], ttl, true).cookie(String::from("token"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.max_age(), Some(time::Duration::hours(1)));
+        let cookie = Auth::new(vec![], ttl, false).cookie(String::from("token"));
+        assert_ne!(cookie.secure(), Some(true));
+    }
+}
@@ -0,0 +1,100 @@
+//! Bookmarks: snapshots of a session's inputs, restored when a page is
+//! opened with `?bookmark=<id>`. Snapshots are kept in memory, so they
+//! don't survive a restart.
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use serde_json::{ json, Map, Value };
+use shiny_rs::ui::*;
+
+use super::messages::{ Session, update_text_input };
+use super::dist_module::SEED_KEY;
+use super::server::CustomServer;
+use super::validation::input_id;
+
+const FLAGS: [&str; 1] = ["dark_mode"];
+
+/// The user who saved a bookmark, and its snapshot.
+type Bookmark = (Option<String>, Value);
+
+/// Bookmarks by id, with the user who saved them. A bookmark can only be
+/// restored by the user who saved it, or by anyone if it was saved without
+/// signing in.
+#[derive(Clone, Default)]
+pub struct BookmarkStore {
+    bookmarks: Arc<Mutex<HashMap<String, Bookmark>>>,
+}
+
+impl BookmarkStore {
+    /// Stores `state` for `user`, returning the id to restore it with.
+    pub fn save(&self, user: Option<&str>, state: Value) -> String {
+        let id = generate_id();
+        self.bookmarks.lock().unwrap().insert(id.clone(), (user.map(String::from), state));
+        id
+    }
+
+    pub fn get(&self, user: Option<&str>, id: &str) -> Option<Value> {
+        let bookmarks = self.bookmarks.lock().unwrap();
+        let (owner, state) = bookmarks.get(id)?;
+        (owner.as_deref() == user).then(|| state.clone())
+    }
+}
+
+/// Keys of the bookmarked inputs, split into text and numeric ones.
+/// Checkboxes, sending booleans, are listed in `FLAGS`.
+fn bookmarked_keys(shiny: &CustomServer) -> (Vec<String>, Vec<String>) {
+    let mut text = vec![String::from("text1"), String::from("text2"), String::from("markdown")];
+    let mut numbers = vec![String::from(SEED_KEY)];
+    for panel in &shiny.panels {
+        text.push(panel.ns("dist_type"));
+        for id in ["n", "mean", "sd"] {
+            numbers.push(format!("{}:shiny.number", panel.ns(id)));
+        }
+    }
+    (text, numbers)
+}
+
+/// The current value of every bookmarked input, by key.
+pub fn snapshot(shiny: &CustomServer) -> Value {
+    let (text, numbers) = bookmarked_keys(shiny);
+    let mut state = Map::new();
+    for key in text {
+        if let Some(value) = shiny.input.get_string(&key) {
+            state.insert(key, json!(value));
+        }
+    }
+    for key in FLAGS {
+        if let Some(value) = shiny.input.get(key).and_then(|value| value.as_bool()) {
+            state.insert(key.to_string(), json!(value));
+        }
+    }
+    for key in numbers {
+        let value = shiny.input
+            .get_u64(&key)
+            .map(|value| json!(value))
+            .or_else(|| shiny.input.get_f64(&key).map(|value| json!(value)));
+        if let Some(value) = value {
+            state.insert(key, value);
+        }
+    }
+    Value::Object(state)
+}
+
+/// Sets the inputs of a snapshot, both in the input pool and on the client.
+pub fn restore(shiny: &mut CustomServer, session: &mut dyn Session, state: &Value) {
+    if let Some(state) = state.as_object() {
+        for (key, value) in state {
+            shiny.input.insert(key, value.clone());
+            update_text_input(session, input_id(key), json!({ "value": value }));
+        }
+    }
+}
+
+/// The bookmark id in a query string such as `?bookmark=abc&x=1`.
+pub fn bookmark_id(search: &str) -> Option<&str> {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("bookmark="))
+        .filter(|id| !id.is_empty())
+}
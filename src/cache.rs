@@ -0,0 +1,65 @@
+//! Least recently used caches of samples and rendered plots, so going back
+//! to earlier settings doesn't draw or render everything again.
+
+use std::collections::VecDeque;
+use std::sync::{ Arc, Mutex };
+
+use super::dist_module::SampleKey;
+
+/// Samples kept by the shared cache.
+const SAMPLE_CACHE_SIZE: usize = 64;
+
+/// Keeps the `capacity` most recently used values. Lookups compare every
+/// key, which is fine for the few dozen entries these caches hold, and
+/// lets keys contain floats.
+pub struct Lru<K, V> {
+    entries: VecDeque<(K, V)>,
+    capacity: usize,
+}
+
+impl<K: PartialEq, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Lru { entries: VecDeque::new(), capacity }
+    }
+
+    /// The value of `key`, marking it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        self.entries.push_front(entry);
+        self.entries.front().map(|(_, value)| value)
+    }
+
+    /// Inserts or replaces the value of `key`, evicting the least recently
+    /// used value if the cache is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(index) = self.entries.iter().position(|(k, _)| *k == key) {
+            self.entries.remove(index);
+        }
+        self.entries.push_front((key, value));
+        self.entries.truncate(self.capacity);
+    }
+}
+
+/// Samples drawn with a seed, shared by every session. Unseeded samples
+/// are never the same twice, so they aren't cached.
+#[derive(Clone)]
+pub struct SampleCache {
+    samples: Arc<Mutex<Lru<SampleKey, Arc<Vec<f64>>>>>,
+}
+
+impl Default for SampleCache {
+    fn default() -> Self {
+        SampleCache { samples: Arc::new(Mutex::new(Lru::new(SAMPLE_CACHE_SIZE))) }
+    }
+}
+
+impl SampleCache {
+    pub fn get(&self, key: &SampleKey) -> Option<Arc<Vec<f64>>> {
+        self.samples.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: SampleKey, sample: Arc<Vec<f64>>) {
+        self.samples.lock().unwrap().insert(key, sample);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+/// Last accepted value of the numeric inputs checked with `changed_by!`.
+#[derive(Default)]
+pub struct LastValues {
+    values: HashMap<String, f64>,
+}
+
+impl LastValues {
+    pub fn set(&mut self, key: &str, value: f64) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    /// Returns `true` if `predicate(old, new)` considers the change
+    /// meaningful, in which case `new` becomes the value future changes
+    /// are compared against. Inputs without a previous value always count
+    /// as changed.
+    pub fn changed_by(
+        &mut self,
+        key: &str,
+        new: Option<f64>,
+        predicate: impl Fn(f64, f64) -> bool
+    ) -> bool {
+        let new = match new {
+            Some(new) => new,
+            None => return false,
+        };
+        let changed = match self.values.get(key) {
+            Some(&old) => predicate(old, new),
+            None => true,
+        };
+        if changed {
+            self.set(key, new);
+        }
+        changed
+    }
+}
+
+/// Like `changed!`, but for a single numeric input and with a predicate
+/// deciding whether the difference between the old and new value matters.
+/// Only the input's own events count, others leave its last value alone.
+///
+/// ```ignore
+/// if changed_by!(shiny, "sd-1:shiny.number", |old, new| (old - new).abs() > 1e-6) {
+///     ...
+/// }
+/// ```
+macro_rules! changed_by {
+    ($shiny:ident, $key:expr, $predicate:expr) => {{
+        let key: &str = $key;
+        $shiny.event == key && $shiny.last_values.changed_by(key, $shiny.input.get_f64(key), $predicate)
+    }};
+}
+
+pub(crate) use changed_by;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::server::CustomServer;
+
+    fn moved(old: f64, new: f64) -> bool {
+        (old - new).abs() > 1e-6
+    }
+
+    #[test]
+    fn only_changes_past_the_predicate_count() {
+        let mut shiny = CustomServer::builder().build();
+        let key = "sd-1:shiny.number";
+        shiny.event = key.to_string();
+        shiny.input.insert(key, json!(1.0));
+        assert!(changed_by!(shiny, key, moved), "first value always counts");
+        shiny.input.insert(key, json!(1.0 + 1e-9));
+        assert!(!changed_by!(shiny, key, moved));
+        shiny.input.insert(key, json!(1.5));
+        assert!(changed_by!(shiny, key, moved));
+        assert!(!changed_by!(shiny, key, moved), "compared with the accepted value");
+    }
+
+    #[test]
+    fn other_inputs_leave_the_value_alone() {
+        let mut shiny = CustomServer::builder().build();
+        let key = "sd-1:shiny.number";
+        shiny.event = key.to_string();
+        shiny.input.insert(key, json!(1.0));
+        assert!(changed_by!(shiny, key, moved));
+        shiny.input.insert(key, json!(2.0));
+        shiny.event = String::from("n-1:shiny.number");
+        assert!(!changed_by!(shiny, key, moved), "not the input's event");
+        shiny.event = key.to_string();
+        shiny.input.insert(key, json!(1.0));
+        assert!(!changed_by!(shiny, key, moved), "1.0 is still the accepted value");
+    }
+
+    #[test]
+    fn small_changes_add_up_against_the_accepted_value() {
+        let mut values = super::LastValues::default();
+        assert!(values.changed_by("x", Some(1.0), moved));
+        assert!(!values.changed_by("x", Some(1.0 + 6e-7), moved));
+        assert!(values.changed_by("x", Some(1.0 + 12e-7), moved));
+        assert!(!values.changed_by("x", None, moved));
+    }
+}
@@ -0,0 +1,71 @@
+//! A chat shared by every session. Messages are rendered from markdown by
+//! the sender and relayed to every session by `AppState`, which keeps the
+//! latest ones for sessions that join later.
+
+use std::collections::VecDeque;
+use actix::Message;
+use serde_json::json;
+
+use super::app_state::ChatPost;
+use super::messages::{ Session, insert_ui, update_text_input };
+use super::server::CustomServer;
+
+pub const CHAT_HISTORY: usize = 50;
+
+/// A rendered chat message, appended to `#chat_log`.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct ChatMessage(pub String);
+
+/// The latest `capacity` messages, oldest first.
+pub struct ChatHistory {
+    messages: VecDeque<ChatMessage>,
+    capacity: usize,
+}
+
+impl ChatHistory {
+    pub fn new(capacity: usize) -> Self {
+        ChatHistory {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, message: ChatMessage) {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.messages.iter()
+    }
+}
+
+impl Default for ChatHistory {
+    fn default() -> Self {
+        ChatHistory::new(CHAT_HISTORY)
+    }
+}
+
+/// Posts the content of `chat_message` and clears the input.
+pub fn send(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let text = shiny.input.get_string("chat_message").unwrap_or_default();
+    if shiny.validators.validate(session, "chat_message", &json!(text)).is_err() {
+        return;
+    }
+    let html = format!(
+        r#"<div class="chat-message"><strong>{}</strong>{}</div>"#,
+        shiny.id.chars().take(6).collect::<String>(),
+        shiny.markdown.render(&text)
+    );
+    if let Some(app_state) = &shiny.app_state {
+        app_state.do_send(ChatPost(ChatMessage(html)));
+    }
+    update_text_input(session, "chat_message", json!({ "value": "" }));
+}
+
+pub fn show(session: &mut dyn Session, message: &ChatMessage) {
+    insert_ui(session, "#chat_log", "beforeEnd", &message.0);
+}
@@ -0,0 +1,72 @@
+use clap::{ Parser, ValueEnum };
+
+use super::config::Config;
+
+/// Command line options. Those given override `config.toml`.
+#[derive(Parser, Debug)]
+#[command(version, about = "Rust backend of the Shiny example app")]
+pub struct Cli {
+    /// Path of the configuration file
+    #[arg(long, default_value = "config.toml")]
+    pub config: String,
+    /// Address to bind to
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Number of HTTP worker threads
+    #[arg(long)]
+    pub workers: Option<usize>,
+    /// PEM certificate chain to serve HTTPS with, along with `--tls-key`
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<String>,
+    /// PEM private key of `--tls-cert`
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<String>,
+    /// Log filter, e.g. `info` or `rusty_shiny=debug`. Defaults to `RUST_LOG`,
+    /// or `info` if unset.
+    #[arg(long)]
+    pub log_level: Option<String>,
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+    /// Print the hash of the password read from stdin, for the `users`
+    /// setting, and exit
+    #[arg(long)]
+    pub hash_password: bool,
+    /// Replay a session recorded in `record_dir`, printing what the server
+    /// sent for each input as JSON lines, and exit
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<String>,
+    /// Watch `static/`, rendering `ui.R` again when it changes and
+    /// reloading the open pages when they or their scripts do
+    #[arg(long)]
+    pub dev: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable, multi-line
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl Cli {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(host) = &self.host {
+            config.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(workers) = self.workers {
+            config.workers = workers;
+        }
+        if let Some(tls_cert) = &self.tls_cert {
+            config.tls_cert = Some(tls_cert.clone());
+        }
+        if let Some(tls_key) = &self.tls_key {
+            config.tls_key = Some(tls_key.clone());
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! The time debounces, rate limits and sign ins go by. It follows the
+//! system clock until frozen; `testing::Driver` freezes it and moves it
+//! forward by hand, so tests of timed behaviour don't sleep.
+
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+
+/// Shared by everything a session times, clones see the same time.
+#[derive(Clone, Default)]
+pub struct Clock {
+    frozen: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Clock {
+    pub fn now(&self) -> Instant {
+        self.frozen.lock().unwrap().unwrap_or_else(Instant::now)
+    }
+
+    /// Stops the clock at the current time, after which only `advance`
+    /// moves it.
+    pub fn freeze(&self) {
+        self.frozen.lock().unwrap().get_or_insert_with(Instant::now);
+    }
+
+    /// Moves the clock `by` forward, freezing it first if it isn't.
+    pub fn advance(&self, by: Duration) {
+        let mut frozen = self.frozen.lock().unwrap();
+        *frozen = Some(frozen.unwrap_or_else(Instant::now) + by);
+    }
+
+    /// Time passed since `at`.
+    pub fn since(&self, at: Instant) -> Duration {
+        self.now().saturating_duration_since(at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen_clocks_only_move_when_advanced() {
+        let clock = Clock::default();
+        clock.freeze();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        clock.clone().advance(Duration::from_secs(2));
+        assert_eq!(clock.since(start), Duration::from_secs(2), "clones share the time");
+    }
+}
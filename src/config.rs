@@ -0,0 +1,359 @@
+//! Server settings, read from `config.toml` and overridden by
+//! `RUSTY_SHINY_*` environment variables. Missing settings keep their
+//! defaults.
+
+use std::error::Error;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+use serde::Deserialize;
+
+use super::format::{ NumberFormat, NumberStyle };
+use super::roles::Role;
+
+/// How plots reach the client.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotBackend {
+    /// HTML with an inline script drawing the plot.
+    Html,
+    /// A Plotly figure spec, drawn by `www/app.js`.
+    Json,
+}
+
+impl std::str::FromStr for PlotBackend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(PlotBackend::Html),
+            "json" => Ok(PlotBackend::Json),
+            _ => Err(format!("unknown plot backend {:?}, expected html or json", s)),
+        }
+    }
+}
+
+/// Compression of the binary frames large messages are sent in.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Gzip,
+    /// zlib-wrapped deflate, as HTTP's `deflate` content encoding.
+    Deflate,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Deflate => "deflate",
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "deflate" => Ok(Compression::Deflate),
+            _ => Err(format!("unknown compression {:?}, expected none, gzip or deflate", s)),
+        }
+    }
+}
+
+/// A user allowed to sign in.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// Argon2 hash of the password, as printed by `--hash-password`.
+    pub password_hash: String,
+    /// Users are viewers unless given another role.
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    /// Number of HTTP worker threads.
+    pub workers: usize,
+    /// Seconds between heartbeats.
+    pub hb_interval: u64,
+    /// Seconds without a heartbeat answer before the session is dropped.
+    pub client_timeout: u64,
+    /// Seconds the workers get to finish once sessions were told about a
+    /// shutdown.
+    pub shutdown_timeout: u64,
+    pub max_markdown_length: usize,
+    /// Largest number of observations a panel may draw.
+    pub max_sample_size: u64,
+    /// Input messages a client may send in a burst, and per second after.
+    pub message_burst: f64,
+    pub messages_per_sec: f64,
+    /// Largest input value accepted from a client, in bytes of JSON.
+    pub max_message_size: usize,
+    /// Messages rejected for their rate or size within a minute after which
+    /// the client is disconnected, 0 to never disconnect it.
+    pub max_violations: u32,
+    pub plot_backend: PlotBackend,
+    /// TrueType font the downloaded plots' text is drawn with.
+    pub plot_font: String,
+    /// Format of the numbers in the summary table.
+    pub summary_format: NumberFormat,
+    /// Format of the values in the Data tab's sample table.
+    pub datatable_format: NumberFormat,
+    /// Format of the labels of the plots' value axes. Plotly picks one
+    /// when unset.
+    pub axis_format: Option<NumberFormat>,
+    /// Samples of at least this many observations are drawn on all cores.
+    pub parallel_threshold: usize,
+    /// Threads the large simulations of all sessions run on, and how many
+    /// may wait for one before more are refused.
+    pub simulation_workers: usize,
+    pub simulation_queue: usize,
+    /// Messages of at least this many bytes of JSON are sent as binary
+    /// frames to clients that negotiated an encoding.
+    pub binary_threshold: usize,
+    /// Compression of those frames, for clients that can inflate them.
+    pub compression: Compression,
+    /// SQLite database the simulation runs and presets are kept in.
+    /// Without one they are only kept in memory, per session.
+    pub database: Option<String>,
+    /// PEM certificate chain and private key. With both, the server only
+    /// accepts HTTPS and WSS.
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    /// With TLS, port answering plain HTTP with redirects to HTTPS.
+    pub http_redirect_port: Option<u16>,
+    /// Directory each session's inputs are recorded in, for `--replay`.
+    pub record_dir: Option<String>,
+    /// Directory the sessions' scratch directories are made in, a
+    /// `rusty-shiny` directory in the system's temporary one when unset.
+    pub files_dir: Option<String>,
+    /// Bytes all sessions' scratch files may take up together.
+    pub files_quota: u64,
+    /// Seconds a dropped session's state is kept for the page to reconnect,
+    /// 0 to start over on every connection.
+    pub reconnect_grace: u64,
+    /// Seconds without input after which a session is closed, 0 to keep
+    /// idle sessions open.
+    pub idle_timeout: u64,
+    /// Seconds before closing an idle session that a warning is shown.
+    pub idle_warning: u64,
+    /// Shown to every session every `notice_interval` seconds.
+    pub notice: Option<String>,
+    pub notice_interval: u64,
+    /// Seconds between reloads of the History tab, 0 to never reload it.
+    pub runs_refresh_interval: u64,
+    /// Users allowed to sign in. Anyone may use the app when there are none.
+    pub users: Vec<Account>,
+    /// Seconds a sign in lasts.
+    pub login_ttl: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: String::from("0.0.0.0"),
+            port: 8080,
+            workers: 2,
+            hb_interval: 5,
+            client_timeout: 10,
+            shutdown_timeout: 10,
+            max_markdown_length: 5000,
+            max_sample_size: 10000,
+            message_burst: 50.0,
+            messages_per_sec: 20.0,
+            max_message_size: 64 * 1024,
+            max_violations: 200,
+            plot_backend: PlotBackend::Html,
+            plot_font: String::from("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"),
+            summary_format: NumberFormat::new(NumberStyle::Grouped, 3),
+            datatable_format: NumberFormat::new(NumberStyle::Grouped, 4),
+            axis_format: None,
+            parallel_threshold: 100_000,
+            simulation_workers: 2,
+            simulation_queue: 16,
+            binary_threshold: 32 * 1024,
+            compression: Compression::None,
+            database: None,
+            tls_cert: None,
+            tls_key: None,
+            http_redirect_port: None,
+            record_dir: None,
+            files_dir: None,
+            files_quota: 256 * 1024 * 1024,
+            reconnect_grace: 30,
+            idle_timeout: 30 * 60,
+            idle_warning: 60,
+            notice: None,
+            notice_interval: 3600,
+            runs_refresh_interval: 60,
+            users: Vec::new(),
+            login_ttl: 12 * 3600,
+        }
+    }
+}
+
+/// Parses the environment variable `name`, if set.
+fn env_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => Ok(Some(value.parse().map_err(|e| format!("{}: {}", name, e))?)),
+        Err(_) => Ok(None),
+    }
+}
+
+impl Config {
+    /// Reads `path` if it exists, then applies the environment overrides.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, Box<dyn Error>> {
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(host) = env_var("RUSTY_SHINY_HOST")? {
+            config.host = host;
+        }
+        if let Some(port) = env_var("RUSTY_SHINY_PORT")? {
+            config.port = port;
+        }
+        if let Some(workers) = env_var("RUSTY_SHINY_WORKERS")? {
+            config.workers = workers;
+        }
+        if let Some(hb_interval) = env_var("RUSTY_SHINY_HB_INTERVAL")? {
+            config.hb_interval = hb_interval;
+        }
+        if let Some(client_timeout) = env_var("RUSTY_SHINY_CLIENT_TIMEOUT")? {
+            config.client_timeout = client_timeout;
+        }
+        if let Some(shutdown_timeout) = env_var("RUSTY_SHINY_SHUTDOWN_TIMEOUT")? {
+            config.shutdown_timeout = shutdown_timeout;
+        }
+        if let Some(max_markdown_length) = env_var("RUSTY_SHINY_MAX_MARKDOWN_LENGTH")? {
+            config.max_markdown_length = max_markdown_length;
+        }
+        if let Some(max_sample_size) = env_var("RUSTY_SHINY_MAX_SAMPLE_SIZE")? {
+            config.max_sample_size = max_sample_size;
+        }
+        if let Some(message_burst) = env_var("RUSTY_SHINY_MESSAGE_BURST")? {
+            config.message_burst = message_burst;
+        }
+        if let Some(messages_per_sec) = env_var("RUSTY_SHINY_MESSAGES_PER_SEC")? {
+            config.messages_per_sec = messages_per_sec;
+        }
+        if let Some(max_message_size) = env_var("RUSTY_SHINY_MAX_MESSAGE_SIZE")? {
+            config.max_message_size = max_message_size;
+        }
+        if let Some(max_violations) = env_var("RUSTY_SHINY_MAX_VIOLATIONS")? {
+            config.max_violations = max_violations;
+        }
+        if let Some(plot_backend) = env_var("RUSTY_SHINY_PLOT_BACKEND")? {
+            config.plot_backend = plot_backend;
+        }
+        if let Some(plot_font) = env_var("RUSTY_SHINY_PLOT_FONT")? {
+            config.plot_font = plot_font;
+        }
+        if let Some(summary_format) = env_var("RUSTY_SHINY_SUMMARY_FORMAT")? {
+            config.summary_format = summary_format;
+        }
+        if let Some(datatable_format) = env_var("RUSTY_SHINY_DATATABLE_FORMAT")? {
+            config.datatable_format = datatable_format;
+        }
+        if let Some(axis_format) = env_var("RUSTY_SHINY_AXIS_FORMAT")? {
+            config.axis_format = Some(axis_format);
+        }
+        if let Some(parallel_threshold) = env_var("RUSTY_SHINY_PARALLEL_THRESHOLD")? {
+            config.parallel_threshold = parallel_threshold;
+        }
+        if let Some(simulation_workers) = env_var("RUSTY_SHINY_SIMULATION_WORKERS")? {
+            config.simulation_workers = simulation_workers;
+        }
+        if let Some(simulation_queue) = env_var("RUSTY_SHINY_SIMULATION_QUEUE")? {
+            config.simulation_queue = simulation_queue;
+        }
+        if let Some(binary_threshold) = env_var("RUSTY_SHINY_BINARY_THRESHOLD")? {
+            config.binary_threshold = binary_threshold;
+        }
+        if let Some(compression) = env_var("RUSTY_SHINY_COMPRESSION")? {
+            config.compression = compression;
+        }
+        if let Some(database) = env_var("RUSTY_SHINY_DATABASE")? {
+            config.database = Some(database);
+        }
+        if let Some(tls_cert) = env_var("RUSTY_SHINY_TLS_CERT")? {
+            config.tls_cert = Some(tls_cert);
+        }
+        if let Some(tls_key) = env_var("RUSTY_SHINY_TLS_KEY")? {
+            config.tls_key = Some(tls_key);
+        }
+        if let Some(http_redirect_port) = env_var("RUSTY_SHINY_HTTP_REDIRECT_PORT")? {
+            config.http_redirect_port = Some(http_redirect_port);
+        }
+        if let Some(record_dir) = env_var("RUSTY_SHINY_RECORD_DIR")? {
+            config.record_dir = Some(record_dir);
+        }
+        if let Some(files_dir) = env_var("RUSTY_SHINY_FILES_DIR")? {
+            config.files_dir = Some(files_dir);
+        }
+        if let Some(files_quota) = env_var("RUSTY_SHINY_FILES_QUOTA")? {
+            config.files_quota = files_quota;
+        }
+        if let Some(reconnect_grace) = env_var("RUSTY_SHINY_RECONNECT_GRACE")? {
+            config.reconnect_grace = reconnect_grace;
+        }
+        if let Some(idle_timeout) = env_var("RUSTY_SHINY_IDLE_TIMEOUT")? {
+            config.idle_timeout = idle_timeout;
+        }
+        if let Some(idle_warning) = env_var("RUSTY_SHINY_IDLE_WARNING")? {
+            config.idle_warning = idle_warning;
+        }
+        if let Some(notice) = env_var("RUSTY_SHINY_NOTICE")? {
+            config.notice = Some(notice);
+        }
+        if let Some(notice_interval) = env_var("RUSTY_SHINY_NOTICE_INTERVAL")? {
+            config.notice_interval = notice_interval;
+        }
+        if let Some(runs_refresh_interval) = env_var("RUSTY_SHINY_RUNS_REFRESH_INTERVAL")? {
+            config.runs_refresh_interval = runs_refresh_interval;
+        }
+        if let Some(login_ttl) = env_var("RUSTY_SHINY_LOGIN_TTL")? {
+            config.login_ttl = login_ttl;
+        }
+        Ok(config)
+    }
+
+    pub fn hb_interval(&self) -> Duration {
+        Duration::from_secs(self.hb_interval)
+    }
+
+    pub fn client_timeout(&self) -> Duration {
+        Duration::from_secs(self.client_timeout)
+    }
+
+    pub fn reconnect_grace(&self) -> Duration {
+        Duration::from_secs(self.reconnect_grace)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout)
+    }
+
+    pub fn idle_warning(&self) -> Duration {
+        Duration::from_secs(self.idle_warning)
+    }
+
+    pub fn login_ttl(&self) -> Duration {
+        Duration::from_secs(self.login_ttl)
+    }
+
+    pub fn files_dir(&self) -> PathBuf {
+        self.files_dir.as_ref().map_or_else(|| std::env::temp_dir().join("rusty-shiny"), PathBuf::from)
+    }
+}
@@ -0,0 +1,153 @@
+use super::format::NumberFormat;
+use super::i18n::Language;
+use super::stats::escape_html;
+
+/// Rows sent per page.
+pub const PAGE_SIZE: usize = 25;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortColumn {
+    Series,
+    Index,
+    Value,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 3] = [SortColumn::Series, SortColumn::Index, SortColumn::Value];
+
+    pub fn from_input(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|column| column.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortColumn::Series => "series",
+            SortColumn::Index => "index",
+            SortColumn::Value => "value",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            SortColumn::Series => "Series",
+            SortColumn::Index => "#",
+            SortColumn::Value => "Value",
+        }
+    }
+}
+
+/// Page and sort order of the sample table. Only the rows of the current
+/// page are rendered; www/app.js reports clicks on the headers and the pager
+/// as the `datatable_sort` and `datatable_page` inputs.
+pub struct DataTable {
+    page: usize,
+    sort: SortColumn,
+    descending: bool,
+}
+
+impl Default for DataTable {
+    fn default() -> Self {
+        DataTable { page: 0, sort: SortColumn::Series, descending: false }
+    }
+}
+
+impl DataTable {
+    /// Sorts by `column`, or reverses the order if it's sorted by it already.
+    /// Goes back to the first page.
+    pub fn sort_by(&mut self, column: SortColumn) {
+        if self.sort == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            self.descending = false;
+        }
+        self.page = 0;
+    }
+
+    /// Pages past the end are clamped when rendering.
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page;
+    }
+
+    /// A row per value of every `(values, label)` series, numbered from 1
+    /// within its series, with the values written with `format` in
+    /// `language`.
+    pub fn render(&mut self, series: &[(&[f64], &str)], format: NumberFormat, language: Language) -> String {
+        let mut rows: Vec<(usize, usize, f64)> = series
+            .iter()
+            .enumerate()
+            .flat_map(|(s, (values, _))| values.iter().enumerate().map(move |(i, x)| (s, i, *x)))
+            .collect();
+        let pages = rows.len().div_ceil(PAGE_SIZE).max(1);
+        self.page = self.page.min(pages - 1);
+        let sort = self.sort;
+        rows.sort_by(|a, b| {
+            let order = match sort {
+                SortColumn::Series => a.0.cmp(&b.0).then(a.1.cmp(&b.1)),
+                SortColumn::Index => a.1.cmp(&b.1).then(a.0.cmp(&b.0)),
+                SortColumn::Value => a.2.total_cmp(&b.2),
+            };
+            if self.descending { order.reverse() } else { order }
+        });
+        let body: String = rows
+            .iter()
+            .skip(self.page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .map(|(s, i, x)| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(series[*s].1),
+                    i + 1,
+                    format.format(*x, language)
+                )
+            })
+            .collect();
+        format!(
+            "<table class=\"table table-sm\"><thead><tr>{}</tr></thead><tbody>{}</tbody></table>{}",
+            self.header(),
+            body,
+            self.pager(pages, rows.len())
+        )
+    }
+
+    fn header(&self) -> String {
+        SortColumn::ALL
+            .iter()
+            .map(|column| {
+                let arrow = match (self.sort == *column, self.descending) {
+                    (false, _) => "",
+                    (true, false) => " ▲",
+                    (true, true) => " ▼",
+                };
+                format!(
+                    "<th><a href=\"#\" data-table-sort=\"{}\">{}{}</a></th>",
+                    column.name(),
+                    column.title(),
+                    arrow
+                )
+            })
+            .collect()
+    }
+
+    fn pager(&self, pages: usize, rows: usize) -> String {
+        let button = |label: &str, page: Option<usize>| match page {
+            Some(page) => format!(
+                "<button type=\"button\" class=\"btn btn-default btn-sm\" data-table-page=\"{}\">{}</button>",
+                page, label
+            ),
+            None => format!(
+                "<button type=\"button\" class=\"btn btn-default btn-sm\" disabled>{}</button>",
+                label
+            ),
+        };
+        let next = Some(self.page + 1).filter(|page| *page < pages);
+        format!(
+            "<div>{} Page {} of {} ({} rows) {}</div>",
+            button("Previous", self.page.checked_sub(1)),
+            self.page + 1,
+            pages,
+            rows,
+            button("Next", next)
+        )
+    }
+}
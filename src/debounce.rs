@@ -0,0 +1,80 @@
+//! Rate limits for handlers of noisy inputs. Both wrappers only record
+//! changes of their input; the handler runs when `ready` says so, checked
+//! in `update`, in a timer armed for `wait()`, and on every heartbeat.
+
+use std::time::{ Duration, Instant };
+
+use super::clock::Clock;
+
+/// Fires once the input stopped changing for `delay`.
+pub struct Debounced {
+    key: &'static str,
+    delay: Duration,
+    changed_at: Option<Instant>,
+    clock: Clock,
+}
+
+impl Debounced {
+    pub fn new(key: &'static str, delay: Duration, clock: Clock) -> Self {
+        Debounced { key, delay, changed_at: None, clock }
+    }
+
+    /// Records a change if `event` is the input's. Returns whether it was.
+    pub fn observe(&mut self, event: &str) -> bool {
+        let matches = event == self.key;
+        if matches {
+            self.changed_at = Some(self.clock.now());
+        }
+        matches
+    }
+
+    /// Time left before a pending change is due.
+    pub fn wait(&self) -> Duration {
+        self.changed_at.map_or(Duration::ZERO, |at| self.delay.saturating_sub(self.clock.since(at)))
+    }
+
+    /// Whether a change is pending and due, clearing it if so.
+    pub fn ready(&mut self) -> bool {
+        let due = self.changed_at.is_some_and(|at| self.clock.since(at) >= self.delay);
+        if due {
+            self.changed_at = None;
+        }
+        due
+    }
+}
+
+/// Fires at most once per `interval`: right away after a quiet period,
+/// then once more at the end of the interval if the input changed again.
+pub struct Throttled {
+    key: &'static str,
+    interval: Duration,
+    fired_at: Option<Instant>,
+    pending: bool,
+    clock: Clock,
+}
+
+impl Throttled {
+    pub fn new(key: &'static str, interval: Duration, clock: Clock) -> Self {
+        Throttled { key, interval, fired_at: None, pending: false, clock }
+    }
+
+    /// Records a change if `event` is the input's. Returns whether it was.
+    pub fn observe(&mut self, event: &str) -> bool {
+        let matches = event == self.key;
+        self.pending |= matches;
+        matches
+    }
+
+    pub fn wait(&self) -> Duration {
+        self.fired_at.map_or(Duration::ZERO, |at| self.interval.saturating_sub(self.clock.since(at)))
+    }
+
+    pub fn ready(&mut self) -> bool {
+        let due = self.pending && self.fired_at.is_none_or(|at| self.clock.since(at) >= self.interval);
+        if due {
+            self.pending = false;
+            self.fired_at = Some(self.clock.now());
+        }
+        due
+    }
+}
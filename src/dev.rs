@@ -0,0 +1,127 @@
+//! Development mode, with `--dev`. The files of `static/` are polled for
+//! changes: a change to `ui.R` renders the pages again with `Rscript`, and
+//! a change to a page or to `www/` reloads every open page. A reloaded
+//! page keeps its reconnect token, so its new session takes over the state
+//! of the old one as after a dropped connection, within
+//! `reconnect_grace`. Debug builds serve `static/` from disk; release
+//! builds embed it and keep serving what they were built with.
+//!
+//! Polling instead of file system events is deliberate. `static/` holds a
+//! few dozen files, so scanning their mtimes twice a second costs next to
+//! nothing. A scan also sees the several writes and renames of one save as
+//! a single change, which events would need a debouncer for. Events also
+//! don't arrive through Docker bind mounts on macOS and Windows, nor on
+//! network file systems, where polling works the same. And a development
+//! only feature doesn't take a watcher dependency in every build.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::process::Command;
+use std::time::{ Duration, SystemTime };
+use actix_web::rt::{ self, time };
+use tracing::{ error, info, warn };
+
+use super::registry::SessionRegistry;
+use super::scheduler::Scheduled;
+
+/// Time between two looks at the files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The pages' definition, rendered by `Rscript`.
+const UI_DEFINITION: &str = "ui.R";
+
+/// Rendered libraries, only ever changed along with the pages.
+const LIBRARIES: &str = "lib";
+
+type Modified = HashMap<PathBuf, SystemTime>;
+
+/// When each file under `dir` was last modified, `lib/` left out.
+fn scan(root: &Path, dir: &Path, modified: &mut Modified) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path == root.join(LIBRARIES) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            scan(root, &path, modified)?;
+        } else {
+            modified.insert(path, metadata.modified()?);
+        }
+    }
+    Ok(())
+}
+
+fn modified(root: &Path) -> Modified {
+    let mut modified = Modified::new();
+    if let Err(e) = scan(root, root, &mut modified) {
+        warn!(error = %e, dir = %root.display(), "files could not be listed");
+    }
+    modified
+}
+
+/// Files added, changed or removed between two scans.
+fn changes(before: &Modified, after: &Modified) -> Vec<PathBuf> {
+    let changed = after
+        .iter()
+        .filter(|(path, time)| before.get(*path) != Some(*time))
+        .map(|(path, _)| path.clone());
+    let removed = before.keys().filter(|path| !after.contains_key(*path)).cloned();
+    changed.chain(removed).collect()
+}
+
+/// Renders the pages from `ui.R`, as the README has it done by hand.
+fn render(root: &Path) {
+    info!("rendering the pages");
+    match Command::new("Rscript").arg(root.join(UI_DEFINITION)).output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            error!(status = %output.status, stderr = %String::from_utf8_lossy(&output.stderr), "ui.R failed");
+        }
+        Err(e) => error!(error = %e, "Rscript could not be run"),
+    }
+}
+
+/// Polls `root` until the server stops, rendering the pages when `ui.R`
+/// changes and reloading the sessions' pages when they or `www/` do.
+pub fn watch(root: impl Into<PathBuf>, registry: SessionRegistry) {
+    let root = root.into();
+    if !cfg!(debug_assertions) {
+        warn!("release builds serve the files they were built with, edits only show after a rebuild");
+    }
+    info!(dir = %root.display(), "watching for changes");
+    rt::spawn(async move {
+        let mut interval = time::interval(POLL_INTERVAL);
+        let scanned = root.clone();
+        let mut before = match rt::task::spawn_blocking(move || modified(&scanned)).await {
+            Ok(before) => before,
+            Err(_) => return,
+        };
+        loop {
+            interval.tick().await;
+            let scanned = root.clone();
+            let after = match rt::task::spawn_blocking(move || modified(&scanned)).await {
+                Ok(after) => after,
+                Err(_) => continue,
+            };
+            let changed = changes(&before, &after);
+            before = after;
+            if changed.is_empty() {
+                continue;
+            }
+            // The pages rendered anew are picked up by the next scan.
+            if changed.contains(&root.join(UI_DEFINITION)) {
+                let rendered = root.clone();
+                let _ = rt::task::spawn_blocking(move || render(&rendered)).await;
+                continue;
+            }
+            let sessions = registry.all();
+            info!(files = changed.len(), sessions = sessions.len(), "reloading the pages");
+            for session in sessions {
+                session.do_send(Scheduled::Reload);
+            }
+        }
+    });
+}
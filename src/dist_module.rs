@@ -0,0 +1,615 @@
+//! The distribution panel: a family selector, the number of observations
+//! and the family's parameters. Each instance lives under its own
+//! namespace, like a Shiny module, so the panel can be used any number of
+//! times. The page starts with `dist1` and `dist2`; the user can add more
+//! with the "Add comparison" button, each with a button removing it.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use serde_json::{ json, Map, Value };
+use shiny_rs::changed;
+use shiny_rs::session::input_pool::InputPool;
+use tracing::warn;
+
+use super::changes::changed_by;
+use super::i18n::{ t, translate, Language };
+use super::messages::{ Session, insert_ui, remove_ui, update_text_input, OutputErrorKind };
+use super::notifications::NotificationMode;
+use super::plot::{ sample, Distribution, PALETTE };
+use super::reactive::Reactive;
+use super::server::{ CustomServer, build_plot, plot_error, render_runs };
+use super::validation::{ Validators, Validator };
+use super::cache::SampleCache;
+use super::metrics::AppMetrics;
+use super::updates::{ update_numeric_input, update_select, NumericUpdate, SelectUpdate };
+
+/// Number of observations, distribution and seed a sample was drawn with.
+pub type SampleKey = (u64, Distribution, Option<u64>);
+
+pub const SEED_KEY: &str = "seed:shiny.number";
+
+/// Panels in the page from the start, which can't be removed.
+pub const BASE_PANELS: usize = 2;
+
+/// Most panels a session can have, added ones included.
+pub const MAX_PANELS: usize = 8;
+
+/// Values and labels of the distribution selector, the first selected.
+const DISTRIBUTIONS: [(&str, &str); 6] = [
+    ("normal", "Normal"),
+    ("uniform", "Uniform"),
+    ("exponential", "Exponential"),
+    ("gamma", "Gamma"),
+    ("poisson", "Poisson"),
+    ("lognormal", "Log-normal"),
+];
+
+/// Initial number of observations, µ and σ of a panel.
+const DEFAULTS: (f64, f64, f64) = (500.0, 0.0, 0.1);
+
+
+/// Identifies a sample in the plot cache: by the settings it was drawn with
+/// if it was drawn with a seed, since those always give the same values,
+/// else by its version.
+#[derive(Clone, PartialEq)]
+pub enum SampleId {
+    Seeded(SampleKey),
+    Drawn(u64),
+}
+
+pub struct DistPanel {
+    number: u64,
+    namespace: String,
+    pub sample: Reactive<SampleKey, Vec<f64>>,
+    /// Name of the pasted column replacing the sample, until the panel's
+    /// settings change.
+    pub imported: Option<String>,
+}
+
+impl DistPanel {
+    /// Panel `dist<number>`.
+    pub fn new(number: u64) -> Self {
+        DistPanel {
+            number,
+            namespace: format!("dist{}", number),
+            sample: Reactive::new(),
+            imported: None,
+        }
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Namespaced id of one of the panel's elements, as `NS(namespace)(id)`
+    /// would build it in R.
+    pub fn ns(&self, id: &str) -> String {
+        format!("{}-{}", self.namespace, id)
+    }
+
+    fn number_key(&self, id: &str) -> String {
+        format!("{}:shiny.number", self.ns(id))
+    }
+
+    /// The selected distribution with its current parameters.
+    pub fn distribution(&self, input: &InputPool) -> Distribution {
+        Distribution::from_inputs(
+            &input.get_string(&self.ns("dist_type")).unwrap_or_default(),
+            input.get_f64(&self.number_key("mean")).unwrap_or(0.0),
+            input.get_f64(&self.number_key("sd")).unwrap_or(0.1)
+        )
+    }
+
+    /// Panels draw from their own generator, seeded with the `seed` input
+    /// plus their number, so that panels with the same settings still
+    /// differ, and a panel keeps its draws when others are removed.
+    fn sample_key(&self, input: &InputPool) -> SampleKey {
+        (
+            input.get_u64(&self.number_key("n")).unwrap_or(0),
+            self.distribution(input),
+            input.get_u64(SEED_KEY).map(|seed| seed.wrapping_add(self.number - 1))
+        )
+    }
+
+    pub fn sample_id(&self) -> SampleId {
+        match self.sample.key() {
+            Some(key) if key.2.is_some() => SampleId::Seeded(key.clone()),
+            _ => SampleId::Drawn(self.sample.version()),
+        }
+    }
+
+    /// Color picked for the panel's series, as `#rrggbb`.
+    pub fn color(&self, input: &InputPool) -> Option<String> {
+        input.get_string(&self.ns("color"))
+    }
+
+    /// The panel's settings on a line, e.g. `dist1: Normal, n = 100, µ = 0, σ = 1`.
+    /// Always in English, since runs are stored with it.
+    pub fn describe(&self, input: &InputPool) -> String {
+        let (first, second) = self.distribution(input).param_labels();
+        let value = |id| {
+            input.get_f64(&self.number_key(id)).map_or(String::from("?"), |value| value.to_string())
+        };
+        let label = |key| translate(Language::En, key, &[]);
+        let mut text = format!("{}, n = {}, {} = {}", self.label(input), value("n"), label(first), value("mean"));
+        if let Some(second) = second {
+            text += &format!(", {} = {}", label(second), value("sd"));
+        }
+        text
+    }
+
+    /// The panel's inputs and the seed, by key, as `bookmark::restore`
+    /// sets them.
+    pub fn parameters(&self, input: &InputPool) -> Value {
+        let mut params = Map::new();
+        let keys = [
+            self.ns("dist_type"),
+            self.number_key("n"),
+            self.number_key("mean"),
+            self.number_key("sd"),
+            SEED_KEY.to_string(),
+        ];
+        for key in keys {
+            if let Some(value) = input.get(&key) {
+                params.insert(key, value.clone());
+            }
+        }
+        Value::Object(params)
+    }
+
+    /// Legend entry of the panel's sample.
+    pub fn label(&self, input: &InputPool) -> String {
+        match &self.imported {
+            Some(column) => format!("{}: {} (pasted)", self.namespace, column),
+            None => format!("{}: {}", self.namespace, self.distribution(input).name()),
+        }
+    }
+
+    pub fn add_validators(&self, validators: Validators, max_sample_size: u64) -> Validators {
+        validators
+            .add(&self.number_key("n"), Validator::Required)
+            .add(
+                &self.number_key("n"),
+                Validator::Range { min: 1.0, max: max_sample_size as f64 }
+            )
+            .add(&self.number_key("n"), Validator::Step(1.0))
+    }
+
+    /// Sets the validators of the second parameter for the selected
+    /// family, whose meaning it depends on.
+    fn set_param_validators(&self, validators: &mut Validators, input: &InputPool) {
+        validators.set(&self.number_key("sd"), param_validators(&self.distribution(input)));
+    }
+
+    fn remove_validators(&self, validators: &mut Validators) {
+        validators.remove(&self.number_key("n"));
+        validators.remove(&self.number_key("sd"));
+    }
+}
+
+/// Validators of the second parameter of `distribution`: a spread or a rate
+/// must be positive, the upper bound of a uniform distribution may be any
+/// number, and single-parameter families don't use it.
+fn param_validators(distribution: &Distribution) -> Vec<Validator> {
+    match distribution {
+        Distribution::Normal(_) | Distribution::Gamma(_) | Distribution::LogNormal(_) => vec![
+            Validator::Required,
+            Validator::Predicate(|value| value > 0.0, "validation.positive"),
+        ],
+        Distribution::Uniform(_) => vec![Validator::Required],
+        Distribution::Exponential(_) | Distribution::Poisson(_) => Vec::new(),
+    }
+}
+
+fn number_input(id: &str, label: &str, value: f64, attrs: &str) -> String {
+    format!(
+        r#"<div class="form-group shiny-input-container">
+  <label class="control-label" id="{id}-label" for="{id}">{label}</label>
+  <input id="{id}" type="number" class="form-control" value="{value}"{attrs}/>
+</div>"#,
+        id = id,
+        label = label,
+        value = value,
+        attrs = attrs
+    )
+}
+
+/// HTML of a panel living under `namespace`, plotted in `color` until
+/// another one is picked, taking up to `max_sample_size` observations,
+/// labelled in `language`.
+pub fn ui(namespace: &str, color: &str, max_sample_size: u64, language: Language) -> String {
+    let ns = |id| format!("{}-{}", namespace, id);
+    let label = |key| translate(language, key, &[]);
+    let (n, mean, sd) = DEFAULTS;
+    let options = DISTRIBUTIONS
+        .iter()
+        .enumerate()
+        .map(|(i, (value, label))| {
+            let selected = if i == 0 { " selected" } else { "" };
+            format!(r#"<option value="{}"{}>{}</option>"#, value, selected, label)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    [
+        format!(
+            r#"<div class="form-group shiny-input-container">
+  <label class="control-label" id="{id}-label" for="{id}">{label}</label>
+  <div>
+    <select id="{id}" class="form-control">{options}</select>
+  </div>
+</div>"#,
+            id = ns("dist_type"),
+            label = label("panel.distribution"),
+            options = options
+        ),
+        number_input(&ns("n"), &label("panel.n"), n, &format!(r#" min="1" max="{}""#, max_sample_size)),
+        number_input(&ns("mean"), &label("param.mean"), mean, r#" step="0.1""#),
+        number_input(&ns("sd"), &label("param.sd"), sd, r#" min="0" step="0.1""#),
+        format!(
+            r#"<div class="form-group shiny-input-container">
+  <label class="control-label" id="{id}-label" for="{id}">{label}</label>
+  <input id="{id}" type="color" class="form-control form-control-color native-input" value="{color}"/>
+</div>"#,
+            id = ns("color"),
+            label = label("panel.color"),
+            color = color
+        ),
+    ].join("\n")
+}
+
+fn sd_changed(old: f64, new: f64) -> bool {
+    (old - new).abs() > 1e-6
+}
+
+/// Draws the sample of `key`. Seeded samples are looked up in the shared
+/// cache first, and added to it once drawn.
+pub(crate) fn draw(
+    cache: &SampleCache,
+    metrics: &AppMetrics,
+    key: &SampleKey,
+    parallel_threshold: usize
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let (n, dist, seed) = key;
+    if seed.is_some() {
+        if let Some(cached) = cache.get(key) {
+            metrics.sample_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(cached.to_vec());
+        }
+        metrics.sample_cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+    let values = sample(*n as usize, dist, *seed, parallel_threshold)?;
+    metrics.add_samples(values.len() as u64);
+    if seed.is_some() {
+        cache.insert(key.clone(), Arc::new(values.clone()));
+    }
+    Ok(values)
+}
+
+/// Draws the first sample of panel `index`.
+pub fn initialize(shiny: &mut CustomServer, index: usize) {
+    let sd_key = shiny.panels[index].number_key("sd");
+    let sd = shiny.input.get_f64(&sd_key).unwrap_or(0.1);
+    shiny.last_values.set(&sd_key, sd);
+    let key = shiny.panels[index].sample_key(&shiny.input);
+    let threshold = shiny.config.parallel_threshold;
+    let (cache, metrics) = (&shiny.sample_cache, &shiny.metrics);
+    let panel = &mut shiny.panels[index];
+    panel.sample.track(key);
+    panel.sample.refresh(|key| draw(cache, metrics, key, threshold).unwrap_or_default());
+}
+
+/// Sets the most observations panel `index` takes to the configured
+/// maximum, for the panels of the page, which are written with a fixed
+/// one.
+pub fn render_limits(shiny: &CustomServer, session: &mut dyn Session, index: usize) {
+    update_numeric_input(session, &shiny.panels[index].ns("n"), NumericUpdate {
+        max: Some(shiny.config.max_sample_size as f64),
+        ..Default::default()
+    });
+}
+
+/// Records the sample panel `index` just drew as a run.
+fn record_run(shiny: &CustomServer, session: &mut dyn Session, index: usize) {
+    let panel = &shiny.panels[index];
+    let result = shiny.storage.record(
+        shiny.user.as_deref(),
+        panel.namespace(),
+        &panel.describe(&shiny.input),
+        &panel.parameters(&shiny.input),
+        shiny.input.get_u64(SEED_KEY),
+        panel.sample.value().map_or(&[][..], |values| &values[..])
+    );
+    match result {
+        Ok(()) => render_runs(shiny, session),
+        Err(e) => warn!(error = %e, "run could not be recorded"),
+    }
+}
+
+/// Validates the panel's inputs, showing a validation message in place of
+/// the plot if any of them is invalid.
+fn validate(shiny: &mut CustomServer, session: &mut dyn Session, index: usize) -> bool {
+    shiny.panels[index].set_param_validators(&mut shiny.validators, &shiny.input);
+    let mut valid = true;
+    for id in ["n", "sd"] {
+        let key = shiny.panels[index].number_key(id);
+        let value = json!(shiny.input.get_f64(&key));
+        valid &= shiny.validators.validate(session, &key, &value).is_ok();
+    }
+    if !valid {
+        let message = t!(shiny, "plot.invalid_inputs");
+        plot_error(shiny, session, &message, OutputErrorKind::Validation);
+    }
+    valid
+}
+
+/// Reacts to changes of the inputs of panel `index`, resampling and
+/// rebuilding the plot when its distribution changed.
+pub fn server(shiny: &mut CustomServer, session: &mut dyn Session, index: usize) {
+    let dist_type = shiny.panels[index].ns("dist_type");
+    let n_key = shiny.panels[index].number_key("n");
+    let mean_key = shiny.panels[index].number_key("mean");
+    let sd_key = shiny.panels[index].number_key("sd");
+    let sd_changed = changed_by!(shiny, &sd_key, sd_changed);
+    if changed!(shiny, (shiny.panels[index].ns("color").as_str())) {
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, (dist_type.as_str())) {
+        render_labels(shiny, session, index);
+    }
+    if sd_changed || changed!(shiny, (n_key.as_str(), mean_key.as_str(), dist_type.as_str(), SEED_KEY)) {
+        for key in [&n_key, &mean_key, &sd_key] {
+            if let Some(value) = shiny.input.get_f64(key) {
+                shiny.input_events.publish(key, json!(value));
+            }
+        }
+        resample(shiny, session, index);
+    }
+}
+
+/// Labels the inputs of panel `index` in the session's language, the
+/// parameters for its distribution.
+pub fn render_labels(shiny: &CustomServer, session: &mut dyn Session, index: usize) {
+    let panel = &shiny.panels[index];
+    update_select(session, &panel.ns("dist_type"), SelectUpdate {
+        label: Some(t!(shiny, "panel.distribution")),
+        ..Default::default()
+    });
+    update_numeric_input(session, &panel.ns("n"), NumericUpdate {
+        label: Some(t!(shiny, "panel.n")),
+        ..Default::default()
+    });
+    update_text_input(session, &panel.ns("color"), json!({ "label": t!(shiny, "panel.color") }));
+    let (first, second) = panel.distribution(&shiny.input).param_labels();
+    update_numeric_input(session, &panel.ns("mean"), NumericUpdate {
+        label: Some(t!(shiny, first)),
+        ..Default::default()
+    });
+    update_numeric_input(session, &panel.ns("sd"), NumericUpdate {
+        label: Some(t!(shiny, second.unwrap_or("param.unused"))),
+        ..Default::default()
+    });
+}
+
+/// Sets the inputs of panel `index` back to a normal distribution of the
+/// initial size, its options and limits included. The client reports the
+/// values that changed, which resamples the panel.
+pub fn reset(shiny: &CustomServer, session: &mut dyn Session, index: usize) {
+    let panel = &shiny.panels[index];
+    let (n, mean, sd) = DEFAULTS;
+    update_select(session, &panel.ns("dist_type"), SelectUpdate {
+        choices: Some(
+            DISTRIBUTIONS
+                .iter()
+                .map(|(value, label)| (value.to_string(), label.to_string()))
+                .collect()
+        ),
+        selected: Some(DISTRIBUTIONS[0].0.to_string()),
+        ..Default::default()
+    });
+    update_numeric_input(session, &panel.ns("n"), NumericUpdate {
+        value: Some(Some(n)),
+        min: Some(1.0),
+        max: Some(shiny.config.max_sample_size as f64),
+        ..Default::default()
+    });
+    update_numeric_input(session, &panel.ns("mean"), NumericUpdate {
+        value: Some(Some(mean)),
+        ..Default::default()
+    });
+    update_numeric_input(session, &panel.ns("sd"), NumericUpdate {
+        value: Some(Some(sd)),
+        ..Default::default()
+    });
+}
+
+/// Draws a new sample for panel `index` if its inputs are valid and changed
+/// since the last one, and rebuilds the plot.
+pub fn resample(shiny: &mut CustomServer, session: &mut dyn Session, index: usize) {
+    if !validate(shiny, session, index) {
+        return;
+    }
+    let key = shiny.panels[index].sample_key(&shiny.input);
+    let threshold = shiny.config.parallel_threshold;
+    let (cache, metrics) = (&shiny.sample_cache, &shiny.metrics);
+    let panel = &mut shiny.panels[index];
+    panel.sample.track(key);
+    match panel.sample.try_refresh(|key| draw(cache, metrics, key, threshold)) {
+        Ok(drawn) => {
+            if drawn {
+                shiny.panels[index].imported = None;
+                record_run(shiny, session, index);
+            }
+            build_plot(shiny, session)
+        }
+        Err(e) => plot_error(shiny, session, &e.to_string(), OutputErrorKind::Error),
+    }
+}
+
+/// Replaces the sample of panel `index` with the pasted column `name`,
+/// until the panel's settings change.
+pub fn import(shiny: &mut CustomServer, session: &mut dyn Session, index: usize, name: String, values: Vec<f64>) {
+    let panel = &mut shiny.panels[index];
+    panel.sample.replace(values);
+    panel.imported = Some(name);
+    build_plot(shiny, session);
+}
+
+/// Starts tracking panel `number`, whose inputs are in the page already or
+/// about to be, and returns its index.
+pub fn track(shiny: &mut CustomServer, number: u64) -> usize {
+    let panel = DistPanel::new(number);
+    let validators = std::mem::take(&mut shiny.validators);
+    shiny.validators = panel.add_validators(validators, shiny.config.max_sample_size);
+    shiny.next_panel = shiny.next_panel.max(number + 1);
+    shiny.panels.push(panel);
+    shiny.panels.len() - 1
+}
+
+/// Adds a panel with the default settings below the others, with a button
+/// removing it, and plots its first sample.
+pub fn add(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if shiny.panels.len() == MAX_PANELS {
+        shiny.notifications.show(session, NotificationMode::Immediate, json!({
+            "html": t!(shiny, "panels.max", max = MAX_PANELS),
+            "action": "",
+            "deps": [],
+            "closeButton": true,
+            "id": "max_panels_warning",
+            "type": "warning"
+        }));
+        return;
+    }
+    let number = shiny.next_panel;
+    let index = track(shiny, number);
+    let panel = &shiny.panels[index];
+    // Added panels start with the next of Plotly's colors in turn.
+    let color = PALETTE[(number as usize - 1) % PALETTE.len()];
+    // The values the inputs are created with, reported by the client once
+    // it bound them.
+    let (n, mean, sd) = DEFAULTS;
+    let defaults = [
+        (panel.ns("dist_type"), json!(DISTRIBUTIONS[0].0)),
+        (panel.number_key("n"), json!(n as u64)),
+        (panel.number_key("mean"), json!(mean)),
+        (panel.number_key("sd"), json!(sd)),
+        (panel.ns("color"), json!(color)),
+    ];
+    let html = format!(
+        r#"<div id="{ns}-panel" class="col-sm-6">
+{ui}
+<button id="{ns}-remove" type="button" class="btn btn-default btn-sm action-button">{remove}</button>
+</div>"#,
+        ns = panel.namespace(),
+        ui = ui(panel.namespace(), color, shiny.config.max_sample_size, shiny.language),
+        remove = t!(shiny, "panel.remove")
+    );
+    insert_ui(session, "#comparisons", "beforeEnd", &html);
+    for (key, value) in defaults {
+        shiny.input.insert(&key, value);
+    }
+    initialize(shiny, index);
+    build_plot(shiny, session);
+}
+
+/// Index of the panel whose remove button was just clicked, if any.
+pub fn remove_clicked(shiny: &CustomServer) -> Option<usize> {
+    let namespace = shiny.event.strip_suffix("-remove:shiny.action")?;
+    if shiny.input.get_u64(&shiny.event).unwrap_or(0) == 0 {
+        return None;
+    }
+    shiny.panels
+        .iter()
+        .position(|panel| panel.namespace() == namespace)
+        .filter(|index| *index >= BASE_PANELS)
+}
+
+/// Removes panel `index` from the page and the session.
+pub fn remove(shiny: &mut CustomServer, session: &mut dyn Session, index: usize) {
+    let panel = shiny.panels.remove(index);
+    panel.remove_validators(&mut shiny.validators);
+    remove_ui(session, &format!("#{}-panel", panel.namespace()));
+    build_plot(shiny, session);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::Driver;
+
+    fn driver(dist_type: &str, first: f64, second: f64) -> Driver {
+        Driver::with_inputs(CustomServer::builder().build(), &[
+            ("dist1-dist_type", json!(dist_type)),
+            ("dist1-n:shiny.number", json!(50)),
+            ("dist1-mean:shiny.number", json!(first)),
+            ("dist1-sd:shiny.number", json!(second)),
+        ])
+    }
+
+    fn resampled(driver: &mut Driver) -> bool {
+        let version = driver.shiny.panels[0].sample.version();
+        let n = driver.shiny.input.get_f64("dist1-n:shiny.number").unwrap_or(0.0);
+        driver.set_number("dist1-n", n + 1.0);
+        driver.shiny.panels[0].sample.version() != version
+    }
+
+    #[test]
+    fn uniform_bounds_may_be_negative() {
+        let mut driver = driver("uniform", -3.0, -1.0);
+        assert!(resampled(&mut driver));
+        assert!(driver.session.inserted("#dist1-sd").is_empty());
+    }
+
+    #[test]
+    fn unused_parameters_are_not_checked() {
+        for dist_type in ["exponential", "poisson"] {
+            let mut driver = driver(dist_type, 2.0, -1.0);
+            assert!(resampled(&mut driver), "{}", dist_type);
+            assert!(driver.session.inserted("#dist1-sd").is_empty(), "{}", dist_type);
+        }
+    }
+
+    #[test]
+    fn spreads_must_be_positive() {
+        for dist_type in ["normal", "gamma", "lognormal"] {
+            let mut driver = driver(dist_type, 1.0, 0.0);
+            assert!(!resampled(&mut driver), "{}", dist_type);
+            assert_eq!(driver.session.inserted("#dist1-sd").len(), 1, "{}", dist_type);
+        }
+    }
+
+    #[test]
+    fn errors_go_when_the_family_no_longer_uses_the_parameter() {
+        let mut driver = driver("normal", 1.0, -1.0);
+        assert!(!resampled(&mut driver));
+        driver.set("dist1-dist_type", json!("exponential"));
+        assert_eq!(driver.session.removed().last(), Some(&"#dist1-sd-error"));
+        assert!(resampled(&mut driver));
+    }
+
+    #[test]
+    fn sample_size_limit_comes_from_the_config() {
+        let config = Config { max_sample_size: 2000, ..Config::default() };
+        let mut driver = Driver::new(CustomServer::builder().config(config).build());
+        assert_eq!(driver.session.input_messages("dist1-n")[0]["max"], json!(2000.0));
+        driver.click("add_panel");
+        let html = driver.session.inserted("#comparisons")[0];
+        assert!(html.contains(r#"id="dist3-n" type="number" class="form-control" value="500" min="1" max="2000""#), "{}", html);
+        assert_eq!(driver.shiny.input.get_u64("dist3-n:shiny.number"), Some(DEFAULTS.0 as u64));
+    }
+
+    #[test]
+    fn panels_are_labelled_in_the_session_language() {
+        let mut driver = driver("normal", 0.0, 1.0);
+        driver.set("language", json!("es"));
+        let labels = driver.session.input_messages("dist1-n");
+        assert_eq!(labels.last().map(|message| &message["label"]), Some(&json!("Número de observaciones")));
+        driver.click("add_panel");
+        let added = driver.session.inserted("#comparisons");
+        assert!(added.last().is_some_and(|html| html.contains(">Distribución</label>")), "{:?}", added);
+    }
+}
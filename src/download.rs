@@ -0,0 +1,178 @@
+use actix_web::{ error, web, Error, HttpResponse };
+use serde::Deserialize;
+
+use super::auth::User;
+use super::config::Config;
+use super::plot_export::{ self, ImageFormat, ImageSize };
+use super::registry::SessionRegistry;
+use super::server::{ GetDistributions, GetFigure, GetMarkdown };
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    session: String,
+}
+
+/// Writes one column per distribution, leaving the cells of the shorter
+/// ones empty once they run out of values.
+pub fn distributions_csv(dists: &[(String, Vec<f64>)]) -> String {
+    let header: Vec<&str> = dists.iter().map(|(name, _)| name.as_str()).collect();
+    let mut csv = header.join(",") + "\n";
+    let rows = dists.iter().map(|(_, values)| values.len()).max().unwrap_or(0);
+    for i in 0..rows {
+        let row: Vec<String> = dists
+            .iter()
+            .map(|(_, values)| values.get(i).map(f64::to_string).unwrap_or_default())
+            .collect();
+        csv.push_str(&(row.join(",") + "\n"));
+    }
+    csv
+}
+
+pub async fn download_dist(
+    registry: web::Data<SessionRegistry>,
+    query: web::Query<DownloadQuery>,
+    user: Option<web::ReqData<User>>
+) -> Result<HttpResponse, Error> {
+    let addr = registry
+        .get_owned(&query.session, user.as_ref().map(|user| user.name.as_str()))
+        .ok_or_else(|| error::ErrorNotFound("Unknown session"))?;
+    let dists = addr
+        .send(GetDistributions)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(("Content-Disposition", "attachment; filename=\"dist.csv\""))
+        .body(distributions_csv(&dists.0)))
+}
+
+/// A standalone page around the rendered markdown, styled to read well
+/// without the app's stylesheets.
+pub fn markdown_page(body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Markdown export</title>
+<style>
+body {{ max-width: 48em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.5; color: #222; }}
+pre {{ padding: 0.75em; overflow-x: auto; border-radius: 4px; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25em 0.5em; }}
+</style>
+</head>
+<body>
+{}
+</body>
+</html>
+"#,
+        body
+    )
+}
+
+pub async fn download_markdown(
+    registry: web::Data<SessionRegistry>,
+    query: web::Query<DownloadQuery>,
+    user: Option<web::ReqData<User>>
+) -> Result<HttpResponse, Error> {
+    let addr = registry
+        .get_owned(&query.session, user.as_ref().map(|user| user.name.as_str()))
+        .ok_or_else(|| error::ErrorNotFound("Unknown session"))?;
+    let markdown = addr
+        .send(GetMarkdown)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(("Content-Disposition", "attachment; filename=\"markdown.html\""))
+        .body(markdown_page(&markdown.0)))
+}
+
+#[derive(Deserialize)]
+pub struct PlotQuery {
+    session: String,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    dpi: u32,
+}
+
+/// The session's plot as an image, drawn on the blocking thread pool.
+pub async fn download_plot(
+    registry: web::Data<SessionRegistry>,
+    config: web::Data<Config>,
+    query: web::Query<PlotQuery>,
+    user: Option<web::ReqData<User>>
+) -> Result<HttpResponse, Error> {
+    let size = ImageSize { width: query.width, height: query.height, dpi: query.dpi };
+    size.validate().map_err(error::ErrorBadRequest)?;
+    let addr = registry
+        .get_owned(&query.session, user.as_ref().map(|user| user.name.as_str()))
+        .ok_or_else(|| error::ErrorNotFound("Unknown session"))?;
+    let figure = addr
+        .send(GetFigure)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    let format = query.format;
+    let font = config.plot_font.clone();
+    let image = web::block(move || {
+        plot_export::render(&figure, format, size, &font).map_err(|e| e.to_string())
+    })
+        .await
+        .map_err(error::ErrorInternalServerError)?
+        .map_err(|e| {
+            tracing::error!(error = %e, "plot could not be drawn");
+            error::ErrorInternalServerError(e)
+        })?;
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"plot.{}\"", format.extension())
+        ))
+        .body(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{ test, App, HttpMessage };
+    use actix_web::http::StatusCode;
+    use actix_web_actors::ws::WebsocketContext;
+    use futures_util::StreamExt;
+    use super::*;
+    use crate::roles::Role;
+    use crate::server::CustomServer;
+
+    /// Starts a session of `user`, as its websocket would, returning its id.
+    async fn start_session(registry: &SessionRegistry, user: &str) -> String {
+        let user = User { name: user.to_string(), role: Role::Viewer };
+        let shiny = CustomServer::builder().registry(registry.clone()).user(Some(user)).build();
+        let id = shiny.id.clone();
+        let input = futures_util::stream::pending::<Result<web::Bytes, actix_web::error::PayloadError>>();
+        let (_, output) = WebsocketContext::create_with_addr(shiny, input);
+        actix_web::rt::spawn(output.for_each(|_| async {}));
+        while registry.get(&id).is_none() {
+            actix_web::rt::task::yield_now().await;
+        }
+        id
+    }
+
+    #[actix_web::test]
+    async fn sessions_only_download_for_their_user() {
+        let registry = SessionRegistry::default();
+        let id = start_session(&registry, "ana").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(registry.clone()))
+                .route("/download/dist.csv", web::get().to(download_dist))
+        ).await;
+        for (user, status) in [(Some("ana"), StatusCode::OK), (Some("bob"), StatusCode::NOT_FOUND), (None, StatusCode::NOT_FOUND)] {
+            let req = test::TestRequest::get().uri(&format!("/download/dist.csv?session={}", id)).to_request();
+            if let Some(user) = user {
+                req.extensions_mut().insert(User { name: user.to_string(), role: Role::Viewer });
+            }
+            assert_eq!(test::call_service(&app, req).await.status(), status, "{:?}", user);
+        }
+    }
+}
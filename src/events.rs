@@ -0,0 +1,98 @@
+use futures_util::stream::{ self, Stream, StreamExt };
+use serde_json::Value;
+use tokio::sync::broadcast::{ self, error::RecvError };
+
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Publishes the input changes observed by `update` so background tasks
+/// can react to them without going through the handler.
+pub struct InputEvents {
+    sender: broadcast::Sender<(String, Value)>,
+}
+
+impl InputEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        InputEvents { sender }
+    }
+
+    pub fn publish(&self, key: &str, value: Value) {
+        // Nobody listening is not an error.
+        let _ = self.sender.send((key.to_string(), value));
+    }
+
+    /// Stream of `(key, value)` changes published after the call, in the
+    /// order they were published. Subscribers that fall more than
+    /// `CHANNEL_CAPACITY` events behind skip the events they missed.
+    pub fn subscribe(&self) -> impl Stream<Item = (String, Value)> {
+        stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+impl Default for InputEvents {
+    fn default() -> Self {
+        InputEvents::new()
+    }
+}
+
+pub async fn log_input_changes(events: impl Stream<Item = (String, Value)>) {
+    futures_util::pin_mut!(events);
+    while let Some((key, value)) = events.next().await {
+        tracing::debug!(%key, %value, "input published");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::server::CustomServer;
+    use crate::testing::Driver;
+
+    #[actix_web::test]
+    async fn changes_arrive_in_order() {
+        let events = InputEvents::new();
+        let stream = events.subscribe();
+        for n in 0..3 {
+            events.publish("n", json!(n));
+        }
+        drop(events);
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(received, (0..3).map(|n| ("n".to_string(), json!(n))).collect::<Vec<_>>());
+    }
+
+    #[actix_web::test]
+    async fn lagging_subscribers_skip_what_they_missed() {
+        let events = InputEvents::new();
+        let stream = events.subscribe();
+        for n in 0..CHANNEL_CAPACITY + 10 {
+            events.publish("n", json!(n));
+        }
+        drop(events);
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(received.len(), CHANNEL_CAPACITY);
+        assert_eq!(received[0].1, json!(10));
+        assert_eq!(received.last().unwrap().1, json!(CHANNEL_CAPACITY + 9));
+    }
+
+    #[actix_web::test]
+    async fn panel_changes_are_published() {
+        let mut driver = Driver::new(CustomServer::builder().build());
+        let stream = driver.shiny.input_events.subscribe();
+        driver.set_number("dist1-n", 20.0);
+        driver.set_number("dist1-mean", 2.0);
+        drop(driver);
+        let received: Vec<_> = stream.collect().await;
+        let n = received.iter().position(|(key, value)| key == "dist1-n:shiny.number" && *value == json!(20.0));
+        let mean = received.iter().position(|(key, value)| key == "dist1-mean:shiny.number" && *value == json!(2.0));
+        assert!(n.is_some() && mean.is_some() && n < mean, "{:?}", received);
+    }
+}
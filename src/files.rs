@@ -0,0 +1,173 @@
+//! Scratch space of the sessions. Every session gets a directory of its
+//! own under `files_dir`, named after its id, for the files it works with,
+//! such as uploads. The directory is removed with everything in it when
+//! the session ends, whether the page closed, the heartbeat timed out or
+//! the session was dropped as idle. What all sessions keep on disk at once
+//! is limited to `files_quota` bytes: a write past it fails and leaves the
+//! session's files as they were.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::{ Arc, Mutex };
+use tracing::warn;
+
+use super::config::Config;
+
+/// Shared by every session: where their directories go, and what they
+/// hold together.
+#[derive(Clone)]
+pub struct FileStore {
+    root: PathBuf,
+    quota: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl Default for FileStore {
+    fn default() -> Self {
+        let config = Config::default();
+        FileStore::new(config.files_dir(), config.files_quota)
+    }
+}
+
+impl FileStore {
+    pub fn new(root: impl Into<PathBuf>, quota: u64) -> Self {
+        FileStore { root: root.into(), quota, used: Arc::default() }
+    }
+
+    /// Bytes held by the sessions' files.
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn quota(&self) -> u64 {
+        self.quota
+    }
+
+    /// Removes the directories a previous run left behind, as when it
+    /// crashed, so they don't count against nor outlive the quota.
+    pub fn sweep(&self) -> io::Result<()> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the directory of session `id`.
+    pub fn open(&self, id: &str) -> io::Result<SessionFiles> {
+        let path = self.root.join(sanitize(id));
+        std::fs::create_dir_all(&path)?;
+        Ok(SessionFiles(Arc::new(SessionDir {
+            path,
+            store: self.clone(),
+            sizes: Mutex::new(HashMap::new()),
+        })))
+    }
+}
+
+/// The directory of a session. Clones share it, so a request can keep
+/// using it while the session ends; it is removed once the last is
+/// dropped.
+#[derive(Clone)]
+pub struct SessionFiles(Arc<SessionDir>);
+
+struct SessionDir {
+    path: PathBuf,
+    store: FileStore,
+    /// Bytes of each file written, by name.
+    sizes: Mutex<HashMap<String, u64>>,
+}
+
+/// `name` as a file name: its last component, with anything but letters,
+/// digits, dots, dashes and underscores replaced.
+fn sanitize(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => String::from("file"),
+        _ => name,
+    }
+}
+
+impl SessionFiles {
+    pub fn dir(&self) -> &Path {
+        &self.0.path
+    }
+
+    /// Where file `name` goes in the session's directory. Names are made
+    /// safe first, so `../x` is `x`.
+    pub fn path_for(&self, name: &str) -> PathBuf {
+        self.0.path.join(sanitize(name))
+    }
+
+    /// Bytes held by the session's files.
+    pub fn used(&self) -> u64 {
+        self.0.sizes.lock().unwrap().values().sum()
+    }
+
+    /// Writes `bytes` to file `name`, replacing it. Fails with
+    /// `StorageFull` when the files of all sessions would pass the quota.
+    pub fn write(&self, name: &str, bytes: &[u8]) -> io::Result<PathBuf> {
+        let name = sanitize(name);
+        let path = self.0.path.join(&name);
+        let mut sizes = self.0.sizes.lock().unwrap();
+        let previous = sizes.get(&name).copied().unwrap_or(0);
+        let size = bytes.len() as u64;
+        let store = &self.0.store;
+        store.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                let used = used - previous + size;
+                (used <= store.quota).then_some(used)
+            })
+            .map_err(|_| io::Error::new(io::ErrorKind::StorageFull, "the quota of session files is used up"))?;
+        if let Err(e) = std::fs::write(&path, bytes) {
+            // The file may be gone or cut short: count it as removed.
+            store.used.fetch_sub(size, Ordering::Relaxed);
+            sizes.remove(&name);
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+        sizes.insert(name, size);
+        Ok(path)
+    }
+
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.path_for(name))
+    }
+
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        let name = sanitize(name);
+        let mut sizes = self.0.sizes.lock().unwrap();
+        if let Some(size) = sizes.remove(&name) {
+            self.0.store.used.fetch_sub(size, Ordering::Relaxed);
+        }
+        match std::fs::remove_file(self.0.path.join(&name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for SessionDir {
+    fn drop(&mut self) {
+        let size: u64 = self.sizes.get_mut().map_or(0, |sizes| sizes.values().sum());
+        self.store.used.fetch_sub(size, Ordering::Relaxed);
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!(error = %e, dir = %self.path.display(), "session files could not be removed");
+            }
+        }
+    }
+}
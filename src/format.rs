@@ -0,0 +1,150 @@
+//! Formatting numbers for display. Each output showing numbers has a
+//! `NumberFormat` set in the configuration, e.g. `grouped:3` for three
+//! decimals with thousands separators, and the separators follow the
+//! session's language: `1,234.5` in English, `1.234,5` in Spanish. Plotly
+//! formats axis labels on the client, so formats also convert to its
+//! d3-format specifiers.
+
+use serde::Deserialize;
+
+use super::i18n::Language;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberStyle {
+    /// `1234.57`
+    Fixed,
+    /// `1,234.57`
+    Grouped,
+    /// `12.5%` for 0.125, with thousands separators.
+    Percent,
+    /// `1.23e+3`
+    Scientific,
+}
+
+/// A style and its number of decimals, written `<style>:<decimals>` in the
+/// configuration. The decimals default to 2.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct NumberFormat {
+    pub style: NumberStyle,
+    pub decimals: usize,
+}
+
+/// Most decimals a format may have.
+const MAX_DECIMALS: usize = 15;
+
+impl std::str::FromStr for NumberFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (style, decimals) = s.split_once(':').unwrap_or((s, "2"));
+        let style = match style {
+            "fixed" => NumberStyle::Fixed,
+            "grouped" => NumberStyle::Grouped,
+            "percent" => NumberStyle::Percent,
+            "scientific" => NumberStyle::Scientific,
+            _ => {
+                return Err(format!(
+                    "unknown number format {:?}, expected fixed, grouped, percent or scientific",
+                    s
+                ))
+            }
+        };
+        let decimals = decimals
+            .parse()
+            .ok()
+            .filter(|decimals| *decimals <= MAX_DECIMALS)
+            .ok_or_else(|| format!("number format {:?} must have 0 to {} decimals", s, MAX_DECIMALS))?;
+        Ok(NumberFormat { style, decimals })
+    }
+}
+
+impl TryFrom<String> for NumberFormat {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl NumberFormat {
+    pub const fn new(style: NumberStyle, decimals: usize) -> Self {
+        NumberFormat { style, decimals }
+    }
+
+    /// `x` with the separators of `language`. Infinities and NaN are
+    /// written as Rust writes them.
+    pub fn format(&self, x: f64, language: Language) -> String {
+        if !x.is_finite() {
+            return x.to_string();
+        }
+        let (decimal, thousands) = separators(language);
+        let decimals = self.decimals;
+        match self.style {
+            NumberStyle::Fixed => localize(&format!("{:.*}", decimals, x), decimal, None),
+            NumberStyle::Grouped => localize(&format!("{:.*}", decimals, x), decimal, Some(thousands)),
+            NumberStyle::Percent => {
+                localize(&format!("{:.*}", decimals, x * 100.0), decimal, Some(thousands)) + "%"
+            }
+            NumberStyle::Scientific => {
+                let written = format!("{:.*e}", decimals, x);
+                let (mantissa, exponent) = written.split_once('e').unwrap_or((&written, "0"));
+                let exponent: i32 = exponent.parse().unwrap_or(0);
+                format!(
+                    "{}e{}{}",
+                    localize(mantissa, decimal, None),
+                    if exponent < 0 { '-' } else { '+' },
+                    exponent.abs()
+                )
+            }
+        }
+    }
+
+    /// The same format as a d3-format specifier, for Plotly's `tickformat`.
+    /// Plotly takes the separators from the layout's `separators`.
+    pub fn d3(&self) -> String {
+        match self.style {
+            NumberStyle::Fixed => format!(".{}f", self.decimals),
+            NumberStyle::Grouped => format!(",.{}f", self.decimals),
+            NumberStyle::Percent => format!(",.{}%", self.decimals),
+            NumberStyle::Scientific => format!(".{}e", self.decimals),
+        }
+    }
+}
+
+/// Decimal and thousands separators of `language`.
+fn separators(language: Language) -> (char, char) {
+    match language {
+        Language::En => ('.', ','),
+        Language::Es => (',', '.'),
+    }
+}
+
+/// The separators of `language` as Plotly's layout takes them.
+pub fn plotly_separators(language: Language) -> String {
+    let (decimal, thousands) = separators(language);
+    format!("{}{}", decimal, thousands)
+}
+
+/// Replaces the decimal point of `number`, as Rust writes it, with
+/// `decimal`, grouping the integer digits by three with `thousands`.
+fn localize(number: &str, decimal: char, thousands: Option<char>) -> String {
+    let (sign, digits) = match number.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", number),
+    };
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+    let mut localized = String::from(sign);
+    for (i, digit) in integer.chars().enumerate() {
+        if let (Some(thousands), true) = (thousands, i > 0 && (integer.len() - i) % 3 == 0) {
+            localized.push(thousands);
+        }
+        localized.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        localized.push(decimal);
+        localized.push_str(fraction);
+    }
+    localized
+}
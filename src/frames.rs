@@ -0,0 +1,151 @@
+//! Binary websocket frames for large messages.
+//!
+//! Shiny's client accepts binary frames made of a byte giving the length of
+//! a message type, the type, and a payload, which it hands to the custom
+//! message handler of that type. Once connected, clients say which
+//! encodings they decode with the `binary_encoding` input and which
+//! compressions they inflate with the `compression` input. Frames are typed
+//! `<encoding>` or `<encoding>+<compression>`, e.g. `msgpack+gzip`, and
+//! www/app.js unpacks them and dispatches them like any JSON message.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::io::Write;
+use flate2::write::{ GzEncoder, ZlibEncoder };
+use serde_json::Value;
+use tracing::warn;
+
+use super::config::Compression;
+use super::messages::Session;
+use super::server::CustomSession;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    /// The encoding named by the `binary_encoding` input. Anything the
+    /// server doesn't know keeps the messages in JSON.
+    pub fn from_input(name: &str) -> Self {
+        match name {
+            "msgpack" => Encoding::MessagePack,
+            _ => Encoding::Json,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::MessagePack => "msgpack",
+        }
+    }
+}
+
+/// Sends messages of at least `threshold` bytes of JSON as binary frames,
+/// once the client negotiated an encoding or the configured compression.
+#[derive(Clone, Copy)]
+pub struct FrameEncoder {
+    pub encoding: Encoding,
+    /// The configured compression, if the client can inflate it.
+    pub compression: Compression,
+    configured: Compression,
+    threshold: usize,
+}
+
+impl FrameEncoder {
+    pub fn new(threshold: usize, compression: Compression) -> Self {
+        FrameEncoder {
+            encoding: Encoding::Json,
+            compression: Compression::None,
+            configured: compression,
+            threshold,
+        }
+    }
+
+    /// Compresses with the configured compression if it's one of
+    /// `supported`, as listed by the `compression` input.
+    pub fn negotiate_compression(&mut self, supported: &[Value]) {
+        let name = self.configured.name();
+        self.compression = if supported.iter().any(|value| value == name) {
+            self.configured
+        } else {
+            Compression::None
+        };
+    }
+
+    pub fn wrap(self, session: &mut dyn Session) -> EncodedSession<'_> {
+        EncodedSession { session, encoder: self }
+    }
+
+    fn is_binary(&self) -> bool {
+        self.encoding != Encoding::Json || self.compression != Compression::None
+    }
+
+    /// Type and payload of the frame carrying `message`, whose JSON is `text`.
+    fn encode(&self, message: &Value, text: &str) -> Result<(String, Vec<u8>), Box<dyn Error>> {
+        let payload: Cow<[u8]> = match self.encoding {
+            Encoding::Json => Cow::Borrowed(text.as_bytes()),
+            Encoding::MessagePack => Cow::Owned(rmp_serde::to_vec(message)?),
+        };
+        let payload = match self.compression {
+            Compression::None => {
+                return Ok((self.encoding.name().to_string(), payload.into_owned()));
+            }
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(&payload)?;
+                encoder.finish()?
+            }
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(&payload)?;
+                encoder.finish()?
+            }
+        };
+        Ok((format!("{}+{}", self.encoding.name(), self.compression.name()), payload))
+    }
+}
+
+/// Binary frame of type `kind` carrying `payload`.
+fn frame(kind: &str, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(1 + kind.len() + payload.len());
+    frame.push(kind.len() as u8);
+    frame.extend_from_slice(kind.as_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Writes large messages to the websocket as binary frames. Outside a live
+/// websocket, such as in `testing::MockSession`, every message stays JSON.
+pub struct EncodedSession<'a> {
+    session: &'a mut dyn Session,
+    encoder: FrameEncoder,
+}
+
+impl<'a> Session for EncodedSession<'a> {
+    fn send(&mut self, message: Value) {
+        let encoder = self.encoder;
+        let context = match (encoder.is_binary(), self.session.actor_context()) {
+            (true, Some(context)) => context,
+            _ => return self.session.send(message),
+        };
+        let text = message.to_string();
+        if text.len() < encoder.threshold {
+            return context.text(text);
+        }
+        match encoder.encode(&message, &text) {
+            Ok((kind, payload)) => context.binary(frame(&kind, &payload)),
+            Err(e) => {
+                warn!(error = %e, "message could not be encoded, sending it as JSON");
+                context.text(text);
+            }
+        }
+    }
+
+    fn actor_context(&mut self) -> Option<&mut CustomSession> {
+        self.session.actor_context()
+    }
+}
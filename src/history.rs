@@ -0,0 +1,73 @@
+//! Undo and redo of input changes, as snapshots taken with
+//! `bookmark::snapshot`.
+
+use std::collections::VecDeque;
+use serde_json::{ Map, Value };
+
+pub struct History {
+    past: VecDeque<Value>,
+    future: Vec<Value>,
+    current: Value,
+    limit: usize,
+}
+
+impl History {
+    /// Keeps up to `limit` states to undo.
+    pub fn new(limit: usize) -> Self {
+        History { past: VecDeque::new(), future: Vec::new(), current: Value::Null, limit }
+    }
+
+    /// Starts over from `state`, forgetting everything recorded so far.
+    pub fn reset(&mut self, state: Value) {
+        self.past.clear();
+        self.future.clear();
+        self.current = state;
+    }
+
+    /// Records `state` if it differs from the current one. A new state
+    /// can't be redone past, so it clears the redo stack.
+    pub fn record(&mut self, state: Value) {
+        if state == self.current {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.current, state);
+        self.past.push_back(previous);
+        if self.past.len() > self.limit {
+            self.past.pop_front();
+        }
+        self.future.clear();
+    }
+
+    /// Goes back a state, returning the inputs to change to get there.
+    pub fn undo(&mut self) -> Option<Value> {
+        let state = self.past.pop_back()?;
+        let changes = changes(&self.current, &state);
+        self.future.push(std::mem::replace(&mut self.current, state));
+        Some(changes)
+    }
+
+    /// Goes forward a state, returning the inputs to change to get there.
+    pub fn redo(&mut self) -> Option<Value> {
+        let state = self.future.pop()?;
+        let changes = changes(&self.current, &state);
+        self.past.push_back(std::mem::replace(&mut self.current, state));
+        Some(changes)
+    }
+}
+
+/// The keys whose value differs between two snapshots, with their value in
+/// `to`. Keys missing from `to` are cleared with `null`.
+fn changes(from: &Value, to: &Value) -> Value {
+    let empty = Map::new();
+    let from = from.as_object().unwrap_or(&empty);
+    let to = to.as_object().unwrap_or(&empty);
+    let mut changes: Map<String, Value> = to
+        .iter()
+        .filter(|(key, value)| from.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    for key in from.keys().filter(|key| !to.contains_key(*key)) {
+        changes.insert(key.clone(), Value::Null);
+    }
+    Value::Object(changes)
+}
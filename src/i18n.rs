@@ -0,0 +1,263 @@
+//! Translations of the text the server renders: notifications, modals,
+//! validation errors and the labels it pushes to inputs. Each language has
+//! a catalog of messages by key, with `{name}` placeholders filled in by
+//! `t!`. A key missing from a catalog falls back to English, and to the
+//! key itself if English lacks it too. The session's language follows the
+//! `language` select and can change at any time.
+
+use super::server::CustomServer;
+
+pub const LANGUAGE_KEY: &str = "language";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Language {
+    #[default]
+    En,
+    Es,
+}
+
+impl Language {
+    /// The language with ISO 639-1 code `code`, as the `language` select
+    /// sends it.
+    pub fn from_code(code: &str) -> Option<Language> {
+        match code {
+            "en" => Some(Language::En),
+            "es" => Some(Language::Es),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Es => "es",
+        }
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::En => EN,
+            Language::Es => ES,
+        }
+    }
+}
+
+const EN: &[(&str, &str)] = &[
+    ("account.greeting", "Hello, {user}"),
+    ("account.sign_out", "Sign out"),
+    ("bookmark.saved", "Bookmarked: the page URL now restores the current inputs"),
+    ("dialog.cancel", "Cancel"),
+    ("dialog.remove", "Remove"),
+    ("editor.only", "Only editors can use these controls."),
+    ("error.report", "Something went wrong. If it keeps happening, report error {id}."),
+    ("flash.title", "Hello from Rust!"),
+    ("idle.closed_body", "The session was closed after {minutes} minutes without activity. Reload the page to start again."),
+    ("idle.closed_title", "Session closed"),
+    ("idle.keep_alive", "Keep alive"),
+    ("idle.warning_body", r#"The session will be closed in <strong class="idle-countdown"></strong> seconds."#),
+    ("idle.warning_title", "Are you still there?"),
+    ("import.applied", "{column} replaced the sample of {panel}"),
+    ("import.columns", "{columns} numeric columns, {rows} rows ({format})"),
+    ("import.format_delimited", "separated by {delimiter}"),
+    ("import.format_json", "JSON"),
+    ("import.format_tab", "separated by tabs"),
+    ("import.format_whitespace", "separated by spaces"),
+    ("import.json", "Not valid JSON: {error}"),
+    ("import.no_numbers", "No numbers found"),
+    ("import.not_number", "Line {line}: \"{cell}\" in {column} is not a number"),
+    ("import.ragged", "Line {line} has {found} cells, the first has {expected}"),
+    ("input.invalid", "Input {key} is invalid: {message}"),
+    ("input.missing", "Input {key} has no value"),
+    ("insert.button", "Insert"),
+    ("insert.count", "{count} plots inserted"),
+    ("insert.max", "Only the last {max} plots are kept"),
+    ("insert.remove_body", "All {count} inserted plots will be removed."),
+    ("insert.remove_button", "Remove"),
+    ("insert.remove_title", "Remove plots?"),
+    ("latency.summary", "RTT {rtt} · {rate} messages/s · last update {update}"),
+    ("markdown.count", "{count} / {max} characters"),
+    ("markdown.exceeded", "Exceeded {max} characters!"),
+    ("message.disconnected", "Disconnected after too many ignored updates. Reload the page to start again."),
+    ("message.too_large", "An update larger than {max} bytes was ignored"),
+    ("panel.color", "Color"),
+    ("panel.distribution", "Distribution"),
+    ("panel.n", "Number of observations"),
+    ("panel.remove", "Remove"),
+    ("panels.max", "At most {max} distributions can be compared"),
+    ("outliers.count", "{count} outliers"),
+    ("outliers.more", "and {count} more"),
+    ("param.lambda", "λ"),
+    ("param.log_mean", "µ (log scale)"),
+    ("param.log_sd", "σ (log scale)"),
+    ("param.max", "Maximum"),
+    ("param.mean", "µ"),
+    ("param.min", "Minimum"),
+    ("param.rate", "Rate"),
+    ("param.sd", "σ"),
+    ("param.shape", "Shape"),
+    ("param.unused", "(unused)"),
+    ("percentiles.invalid", "percentiles must be between 0 and 100, lower first"),
+    ("plot.click", "Clicked at x = {x}, y = {y}"),
+    ("plot.invalid_inputs", "Fix the invalid inputs to update the plot"),
+    ("preset.saved", "Saved preset {name}"),
+    ("progress.cancel", "Cancel"),
+    ("progress.failed", "The work failed"),
+    ("rate_limit.warning", "Too many updates, some changes were ignored"),
+    ("runs.empty", "No runs yet. Change the settings of a panel to record one."),
+    ("runs.failed", "The runs could not be loaded."),
+    ("seed.random", "Seed: random"),
+    ("seed.value", "Seed: {seed}"),
+    ("server.restarting", "Server restarting, reconnecting shortly..."),
+    ("simulation.cancelled", "Simulation cancelled"),
+    ("simulation.progress", "{done} of {total} samples"),
+    ("simulation.result", "Mean {mean}, SD {sd} over {n} samples"),
+    ("stream.rate", "Milliseconds between points"),
+    ("stream.series", "random walk"),
+    ("stream.start", "Start streaming"),
+    ("stream.stop", "Stop streaming"),
+    ("upload.missing", "No file was uploaded"),
+    ("upload.no_numbers", "The file contains no numbers"),
+    ("upload.no_room", "The server has no room for more files"),
+    ("upload.not_number", "Line {line}: \"{cell}\" is not a number"),
+    ("upload.not_text", "The file is not text"),
+    ("upload.series", "uploaded: {name}"),
+    ("upload.too_large", "Files are limited to 1 MB"),
+    ("validation.max_length", "Must be at most {max} characters"),
+    ("validation.no_markup", "Can't contain < or >"),
+    ("validation.number", "Must be a number"),
+    ("validation.positive", "Must be greater than 0"),
+    ("validation.range", "Must be between {min} and {max}"),
+    ("validation.required", "Required"),
+    ("validation.step", "Must be a multiple of {step}"),
+    ("validation.text", "Must be text"),
+    ("workers.busy", "The server is busy with other simulations, try again shortly"),
+    ("workers.queued", "Waiting for a free worker, number {position} in line"),
+];
+
+/// Symbols such as µ and σ are left to the English catalog.
+const ES: &[(&str, &str)] = &[
+    ("account.greeting", "Hola, {user}"),
+    ("account.sign_out", "Cerrar sesión"),
+    ("bookmark.saved", "Guardado: la URL de la página ahora restaura los valores actuales"),
+    ("dialog.cancel", "Cancelar"),
+    ("dialog.remove", "Quitar"),
+    ("editor.only", "Solo los editores pueden usar estos controles."),
+    ("error.report", "Algo salió mal. Si vuelve a ocurrir, informe del error {id}."),
+    ("flash.title", "¡Hola desde Rust!"),
+    ("idle.closed_body", "La sesión se cerró tras {minutes} minutos sin actividad. Recargue la página para empezar de nuevo."),
+    ("idle.closed_title", "Sesión cerrada"),
+    ("idle.keep_alive", "Mantener abierta"),
+    ("idle.warning_body", r#"La sesión se cerrará en <strong class="idle-countdown"></strong> segundos."#),
+    ("idle.warning_title", "¿Sigue ahí?"),
+    ("import.applied", "{column} reemplazó la muestra de {panel}"),
+    ("import.columns", "{columns} columnas numéricas, {rows} filas ({format})"),
+    ("import.format_delimited", "separado por {delimiter}"),
+    ("import.format_tab", "separado por tabuladores"),
+    ("import.format_whitespace", "separado por espacios"),
+    ("import.json", "JSON no válido: {error}"),
+    ("import.no_numbers", "No se encontraron números"),
+    ("import.not_number", "Línea {line}: \"{cell}\" en {column} no es un número"),
+    ("import.ragged", "La línea {line} tiene {found} celdas, la primera tiene {expected}"),
+    ("input.invalid", "El valor de {key} no es válido: {message}"),
+    ("input.missing", "{key} no tiene valor"),
+    ("insert.button", "Insertar"),
+    ("insert.count", "{count} gráficos insertados"),
+    ("insert.max", "Solo se conservan los últimos {max} gráficos"),
+    ("insert.remove_body", "Se quitarán los {count} gráficos insertados."),
+    ("insert.remove_button", "Quitar"),
+    ("insert.remove_title", "¿Quitar los gráficos?"),
+    ("latency.summary", "RTT {rtt} · {rate} mensajes/s · última actualización {update}"),
+    ("markdown.count", "{count} / {max} caracteres"),
+    ("markdown.exceeded", "¡Se superaron los {max} caracteres!"),
+    ("message.disconnected", "Desconectado tras demasiadas actualizaciones ignoradas. Recargue la página para empezar de nuevo."),
+    ("message.too_large", "Se ignoró una actualización de más de {max} bytes"),
+    ("panel.color", "Color"),
+    ("panel.distribution", "Distribución"),
+    ("panel.n", "Número de observaciones"),
+    ("panel.remove", "Quitar"),
+    ("panels.max", "Se pueden comparar como máximo {max} distribuciones"),
+    ("outliers.count", "{count} valores atípicos"),
+    ("outliers.more", "y {count} más"),
+    ("param.log_mean", "µ (escala logarítmica)"),
+    ("param.log_sd", "σ (escala logarítmica)"),
+    ("param.max", "Máximo"),
+    ("param.min", "Mínimo"),
+    ("param.rate", "Tasa"),
+    ("param.shape", "Forma"),
+    ("param.unused", "(sin uso)"),
+    ("percentiles.invalid", "los percentiles deben estar entre 0 y 100, el menor primero"),
+    ("plot.click", "Clic en x = {x}, y = {y}"),
+    ("plot.invalid_inputs", "Corrija los valores no válidos para actualizar el gráfico"),
+    ("preset.saved", "Se guardó el ajuste {name}"),
+    ("progress.cancel", "Cancelar"),
+    ("progress.failed", "El trabajo falló"),
+    ("rate_limit.warning", "Demasiadas actualizaciones, se ignoraron algunos cambios"),
+    ("runs.empty", "Todavía no hay ejecuciones. Cambie los ajustes de un panel para registrar una."),
+    ("runs.failed", "No se pudieron cargar las ejecuciones."),
+    ("seed.random", "Semilla: aleatoria"),
+    ("seed.value", "Semilla: {seed}"),
+    ("server.restarting", "El servidor se está reiniciando, se reconectará en breve..."),
+    ("simulation.cancelled", "Simulación cancelada"),
+    ("simulation.progress", "{done} de {total} muestras"),
+    ("simulation.result", "Media {mean}, DE {sd} en {n} muestras"),
+    ("stream.rate", "Milisegundos entre puntos"),
+    ("stream.series", "paseo aleatorio"),
+    ("stream.start", "Iniciar transmisión"),
+    ("stream.stop", "Detener transmisión"),
+    ("upload.missing", "No se subió ningún archivo"),
+    ("upload.no_numbers", "El archivo no contiene números"),
+    ("upload.no_room", "El servidor no tiene espacio para más archivos"),
+    ("upload.not_number", "Línea {line}: \"{cell}\" no es un número"),
+    ("upload.not_text", "El archivo no es de texto"),
+    ("upload.series", "subido: {name}"),
+    ("upload.too_large", "Los archivos están limitados a 1 MB"),
+    ("validation.max_length", "Debe tener como máximo {max} caracteres"),
+    ("validation.no_markup", "No puede contener < ni >"),
+    ("validation.number", "Debe ser un número"),
+    ("validation.positive", "Debe ser mayor que 0"),
+    ("validation.range", "Debe estar entre {min} y {max}"),
+    ("validation.required", "Obligatorio"),
+    ("validation.step", "Debe ser múltiplo de {step}"),
+    ("validation.text", "Debe ser texto"),
+    ("workers.busy", "El servidor está ocupado con otras simulaciones, inténtelo de nuevo en breve"),
+    ("workers.queued", "Esperando un trabajador libre, número {position} en la fila"),
+];
+
+fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    language.catalog().iter().find(|(k, _)| *k == key).map(|(_, message)| *message)
+}
+
+/// The message `key` in `language`, with each `{name}` of `args` replaced
+/// by its value.
+pub fn translate(language: Language, key: &str, args: &[(&str, String)]) -> String {
+    let message = lookup(language, key).or_else(|| lookup(Language::En, key)).unwrap_or(key);
+    args.iter().fold(message.to_string(), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// The message `key` in the session's language, with named arguments
+/// filling its placeholders. Values are inserted as they are, so text
+/// from users must be escaped first.
+///
+/// ```ignore
+/// render_text(session, "active_seed", &t!(shiny, "seed.value", seed = 42));
+/// ```
+macro_rules! t {
+    ($shiny:expr, $key:expr) => {
+        $crate::i18n::translate($shiny.language, $key, &[])
+    };
+    ($shiny:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($shiny.language, $key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+pub(crate) use t;
+
+/// Sets the session's language to the one picked in the `language` select,
+/// English until the client reported it.
+pub fn set_language(shiny: &mut CustomServer) {
+    let code = shiny.input.get_string(LANGUAGE_KEY).unwrap_or_default();
+    shiny.language = Language::from_code(&code).unwrap_or_default();
+    shiny.validators.set_language(shiny.language);
+}
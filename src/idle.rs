@@ -0,0 +1,101 @@
+//! Closing idle sessions. Heartbeats keep a session alive as long as its
+//! page is open, so user activity is tracked separately: a session that
+//! received no input for `idle_timeout` seconds is closed, after a modal
+//! counting down the last `idle_warning` seconds with a button keeping it
+//! open. The heartbeat's `tick` does the checking.
+
+use std::time::{ Duration, Instant };
+use actix::ActorContext;
+use actix_web_actors::ws::{ CloseCode, CloseReason };
+use serde_json::json;
+use tracing::info;
+
+use super::latency;
+use super::i18n::t;
+use super::messages::{ Session, allow_reconnect, modal_dialog, remove_modal, send_custom_message, show_modal };
+use super::server::CustomServer;
+
+pub struct IdleTimer {
+    /// Zero never closes the session.
+    timeout: Duration,
+    warning: Duration,
+    last_activity: Instant,
+    warned: bool,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: Duration, warning: Duration) -> Self {
+        IdleTimer {
+            timeout,
+            warning: warning.min(timeout),
+            last_activity: Instant::now(),
+            warned: false,
+        }
+    }
+
+    /// Records user activity, returning whether the warning was showing.
+    pub fn activity(&mut self) -> bool {
+        self.last_activity = Instant::now();
+        std::mem::take(&mut self.warned)
+    }
+
+    /// Time left before the session is closed, `None` when it never is.
+    fn remaining(&self) -> Option<Duration> {
+        if self.timeout.is_zero() {
+            return None;
+        }
+        Some(self.timeout.saturating_sub(self.last_activity.elapsed()))
+    }
+}
+
+/// Counts as activity, hiding the warning if it was shown. Inputs Shiny
+/// reports by itself, such as the window size or buttons reporting 0 when
+/// bound, don't count, nor do the page's answers to latency pings.
+pub fn record_activity(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let bound = shiny.event.ends_with(":shiny.action") && shiny.input.get_u64(&shiny.event) == Some(0);
+    if shiny.event.starts_with(".clientdata") || shiny.event == latency::PONG_KEY || bound {
+        return;
+    }
+    if shiny.idle.activity() {
+        remove_modal(session);
+    }
+}
+
+/// Shows the warning once the session is about to be closed, and closes it
+/// once its time is up.
+pub fn check(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let remaining = match shiny.idle.remaining() {
+        Some(remaining) => remaining,
+        None => return,
+    };
+    if remaining.is_zero() {
+        let minutes = shiny.idle.timeout.as_secs() / 60;
+        info!(minutes, "closing idle session");
+        // Closed sessions are not worth reconnecting to.
+        shiny.reconnect_token = None;
+        allow_reconnect(session, json!(false));
+        show_modal(session, &modal_dialog(
+            &t!(shiny, "idle.closed_title"),
+            &t!(shiny, "idle.closed_body", minutes = minutes),
+            ""
+        ));
+        if let Some(session) = session.actor_context() {
+            session.close(Some(CloseReason {
+                code: CloseCode::Away,
+                description: Some(String::from("Idle")),
+            }));
+            session.stop();
+        }
+    } else if remaining <= shiny.idle.warning && !shiny.idle.warned {
+        shiny.idle.warned = true;
+        show_modal(session, &modal_dialog(
+            &t!(shiny, "idle.warning_title"),
+            &t!(shiny, "idle.warning_body"),
+            &format!(
+                r#"<button id="keep_alive" type="button" class="btn btn-primary action-button">{}</button>"#,
+                t!(shiny, "idle.keep_alive")
+            )
+        ));
+        send_custom_message(session, "idle-countdown", json!({ "seconds": remaining.as_secs() }));
+    }
+}
@@ -0,0 +1,246 @@
+//! Data pasted into the Data tab's `paste_data` box. The text is either
+//! JSON, an array of numbers, of objects or an object of arrays, or
+//! delimited rows whose delimiter is sniffed from the first lines. Columns
+//! whose cells are all text, like ids, are left out; the others must only
+//! hold numbers, with a decimal comma allowed when the delimiter isn't a
+//! comma. The first row is a header if it names the columns: if it has
+//! text over a column of numbers, or only text.
+
+use serde_json::Value;
+
+use super::i18n::{ translate, Language };
+use super::stats::escape_html;
+
+/// Delimiters tried, preferred in this order when several fit.
+const DELIMITERS: [char; 4] = ['\t', ';', '|', ','];
+
+/// Lines the delimiter is sniffed from.
+const SNIFFED_LINES: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Delimited(char),
+    /// Cells separated by spaces.
+    Whitespace,
+}
+
+impl Format {
+    pub fn describe(&self, language: Language) -> String {
+        match self {
+            Format::Json => translate(language, "import.format_json", &[]),
+            Format::Delimited('\t') => translate(language, "import.format_tab", &[]),
+            Format::Delimited(delimiter) => {
+                translate(language, "import.format_delimited", &[("delimiter", format!("\"{}\"", delimiter))])
+            }
+            Format::Whitespace => translate(language, "import.format_whitespace", &[]),
+        }
+    }
+}
+
+pub struct Column {
+    pub name: String,
+    /// The column's numbers, without its empty cells.
+    pub values: Vec<f64>,
+}
+
+pub struct Table {
+    pub format: Format,
+    pub columns: Vec<Column>,
+    pub rows: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    Json(String),
+    /// Line `line` has `found` cells where the first line has `expected`.
+    Ragged { line: usize, expected: usize, found: usize },
+    NotNumber { line: usize, column: String, cell: String },
+    NoNumbers,
+}
+
+impl ImportError {
+    /// What went wrong, as HTML: the pasted text quoted is escaped.
+    pub fn message(&self, language: Language) -> String {
+        let t = |key, args: &[(&str, String)]| translate(language, key, args);
+        match self {
+            ImportError::Json(error) => t("import.json", &[("error", error.clone())]),
+            ImportError::Ragged { line, expected, found } => t("import.ragged", &[
+                ("line", line.to_string()),
+                ("expected", expected.to_string()),
+                ("found", found.to_string()),
+            ]),
+            ImportError::NotNumber { line, column, cell } => t("import.not_number", &[
+                ("line", line.to_string()),
+                ("column", escape_html(column)),
+                ("cell", escape_html(cell)),
+            ]),
+            ImportError::NoNumbers => t("import.no_numbers", &[]),
+        }
+    }
+}
+
+/// The delimiter of the first line splitting the most of the first lines
+/// into as many cells as it does. Lines it splits differently are then
+/// reported as ragged.
+fn sniff(text: &str) -> Format {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).take(SNIFFED_LINES).collect();
+    let first = match lines.first() {
+        Some(first) => first,
+        None => return Format::Whitespace,
+    };
+    let mut best: Option<(char, usize)> = None;
+    for delimiter in DELIMITERS {
+        let cells = first.matches(delimiter).count();
+        if cells == 0 {
+            continue;
+        }
+        let fitting = lines.iter().filter(|line| line.matches(delimiter).count() == cells).count();
+        if best.is_none_or(|(_, most)| fitting > most) {
+            best = Some((delimiter, fitting));
+        }
+    }
+    best.map_or(Format::Whitespace, |(delimiter, _)| Format::Delimited(delimiter))
+}
+
+fn split(line: &str, format: Format) -> Vec<&str> {
+    let cells: Vec<&str> = match format {
+        Format::Delimited(delimiter) => line.split(delimiter).collect(),
+        _ => line.split_whitespace().collect(),
+    };
+    cells.into_iter().map(|cell| cell.trim().trim_matches('"')).collect()
+}
+
+/// `cell` as a number, `1,5` included unless commas delimit the cells.
+fn number(cell: &str, format: Format) -> Option<f64> {
+    cell.parse().ok().or_else(|| match format {
+        Format::Delimited(',') => None,
+        _ => cell.replacen(',', ".", 1).parse().ok(),
+    })
+}
+
+/// A column's cells, each with its line number, into its numbers. `None`
+/// when no cell is a number, for text columns.
+fn numeric(name: &str, cells: Vec<(usize, &str)>, format: Format) -> Result<Option<Column>, ImportError> {
+    let cells: Vec<(usize, &str)> = cells.into_iter().filter(|(_, cell)| !cell.is_empty()).collect();
+    if !cells.iter().any(|(_, cell)| number(cell, format).is_some()) {
+        return Ok(None);
+    }
+    let values = cells
+        .iter()
+        .map(|(line, cell)| {
+            number(cell, format).ok_or_else(|| ImportError::NotNumber {
+                line: *line,
+                column: name.to_string(),
+                cell: cell.to_string(),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Some(Column { name: name.to_string(), values }))
+}
+
+fn delimited(text: &str, format: Format) -> Result<Vec<Column>, ImportError> {
+    let rows: Vec<(usize, Vec<&str>)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, split(line, format)))
+        .collect();
+    let width = rows.first().map_or(0, |(_, cells)| cells.len());
+    if let Some((line, cells)) = rows.iter().find(|(_, cells)| cells.len() != width) {
+        return Err(ImportError::Ragged { line: *line, expected: width, found: cells.len() });
+    }
+    // A text cell over a column of numbers is a name, a text cell over
+    // text may be an id.
+    let header = rows.first().is_some_and(|(_, first)| {
+        let below = |index: usize| rows[1..].iter().any(|(_, cells)| number(cells[index], format).is_some());
+        let text = |index: &usize| number(first[*index], format).is_none();
+        (0..width).filter(text).any(below) || (rows.len() > 1 && (0..width).all(|index| text(&index)))
+    });
+    let names: Vec<String> = match (header, rows.first()) {
+        (true, Some((_, cells))) => cells.iter().map(|cell| cell.to_string()).collect(),
+        _ => (1..=width).map(|i| format!("column {}", i)).collect(),
+    };
+    let data = &rows[usize::from(header).min(rows.len())..];
+    let mut columns = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let cells = data.iter().map(|(line, cells)| (*line, cells[index])).collect();
+        columns.extend(numeric(name, cells, format)?);
+    }
+    Ok(columns)
+}
+
+/// Cells of a JSON column: numbers as they are, strings as their text and
+/// nulls as empty cells. Lines are counted as the array's items.
+fn json_cells(name: &str, values: &[Value]) -> Result<Option<Column>, ImportError> {
+    let cells: Vec<(usize, String)> = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let cell = match value {
+                Value::Null => String::new(),
+                Value::String(text) => text.trim().to_string(),
+                other => other.to_string(),
+            };
+            (index + 1, cell)
+        })
+        .collect();
+    numeric(name, cells.iter().map(|(line, cell)| (*line, cell.as_str())).collect(), Format::Json)
+}
+
+fn json(text: &str) -> Result<Vec<Column>, ImportError> {
+    let value: Value = serde_json::from_str(text).map_err(|e| ImportError::Json(e.to_string()))?;
+    let mut columns = Vec::new();
+    match value {
+        Value::Array(items) if items.iter().all(Value::is_object) && !items.is_empty() => {
+            let mut names: Vec<&String> = Vec::new();
+            for item in &items {
+                for name in item.as_object().into_iter().flat_map(|item| item.keys()) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            for name in names {
+                let values: Vec<Value> = items.iter().map(|item| item.get(name).cloned().unwrap_or(Value::Null)).collect();
+                columns.extend(json_cells(name, &values)?);
+            }
+        }
+        Value::Array(items) => columns.extend(json_cells("value", &items)?),
+        Value::Object(object) => {
+            for (name, values) in &object {
+                match values {
+                    Value::Array(values) => columns.extend(json_cells(name, values)?),
+                    other => columns.extend(json_cells(name, std::slice::from_ref(other))?),
+                }
+            }
+        }
+        other => columns.extend(json_cells("value", &[other])?),
+    }
+    Ok(columns)
+}
+
+/// The numeric columns of `text`.
+pub fn parse(text: &str) -> Result<Table, ImportError> {
+    let trimmed = text.trim();
+    let (format, columns) = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        (Format::Json, json(trimmed)?)
+    } else {
+        let format = sniff(text);
+        (format, delimited(text, format)?)
+    };
+    if columns.iter().all(|column| column.values.is_empty()) {
+        return Err(ImportError::NoNumbers);
+    }
+    let rows = columns.iter().map(|column| column.values.len()).max().unwrap_or(0);
+    Ok(Table { format, columns, rows })
+}
+
+/// Validates the pasted text, for `Validator::Parse`. An empty box is
+/// valid, it imports nothing.
+pub fn check(text: &str, language: Language) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+    parse(text).map(|_| ()).map_err(|e| e.message(language))
+}
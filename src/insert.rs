@@ -0,0 +1,147 @@
+use std::collections::{ HashMap, VecDeque };
+use serde_json::json;
+use shiny_rs::ui::*;
+
+use super::messages::{ Session, insert_ui, remove_ui, send_custom_message };
+use super::server::CustomServer;
+
+pub type RemovedCallback = fn(&mut CustomServer, &mut dyn Session);
+
+/// Keeps track of the elements added through `insert_ui` for each
+/// selector, removing the oldest one once a selector holds more than
+/// `max_per_selector` elements.
+pub struct InsertedUi {
+    max_per_selector: usize,
+    /// Ids and HTML of the elements, oldest first.
+    inserted: HashMap<String, VecDeque<(String, String)>>,
+}
+
+impl InsertedUi {
+    pub fn new(max_per_selector: usize) -> Self {
+        InsertedUi {
+            max_per_selector,
+            inserted: HashMap::new(),
+        }
+    }
+
+    /// Inserts `html` wrapped in a container with a generated id and
+    /// returns that id.
+    pub fn insert(
+        &mut self,
+        session: &mut dyn Session,
+        selector: &str,
+        position: &str,
+        html: &str
+    ) -> String {
+        let id = generate_id();
+        insert_ui(
+            session,
+            selector,
+            position,
+            &format!("<div id=\"{}\">{}</div>", id, html)
+        );
+        let elements = self.inserted.entry(selector.to_string()).or_default();
+        elements.push_back((id.clone(), html.to_string()));
+        while elements.len() > self.max_per_selector {
+            if let Some((oldest, _)) = elements.pop_front() {
+                remove_ui(session, &format!("#{}", oldest));
+            }
+        }
+        id
+    }
+
+    /// Ids and HTML of the elements tracked for `selector`, oldest first.
+    pub fn elements(&self, selector: &str) -> Vec<(String, String)> {
+        self.inserted.get(selector).map_or(Vec::new(), |elements| elements.iter().cloned().collect())
+    }
+
+    /// Replaces the elements of `selector`, on the client too, with
+    /// `elements` as returned by `elements`. Each is inserted with
+    /// `position`, so `afterBegin` puts the newest first again.
+    pub fn restore(
+        &mut self,
+        session: &mut dyn Session,
+        selector: &str,
+        position: &str,
+        elements: Vec<(String, String)>
+    ) {
+        remove_ui(session, &format!("{} > div", selector));
+        for (id, html) in &elements {
+            insert_ui(session, selector, position, &format!("<div id=\"{}\">{}</div>", id, html));
+        }
+        self.inserted.insert(selector.to_string(), elements.into());
+    }
+
+    /// Forgets every element tracked for `selector`. Call this after
+    /// removing them from the client.
+    pub fn clear(&mut self, selector: &str) {
+        self.inserted.remove(selector);
+    }
+
+    pub fn count(&self, selector: &str) -> usize {
+        self.inserted.get(selector).map_or(0, |ids| ids.len())
+    }
+}
+
+/// Removals waiting for the client to confirm they happened. The client
+/// answers through the `remove_ui_done` input once the elements are gone.
+#[derive(Default)]
+pub struct PendingRemovals {
+    callbacks: HashMap<String, RemovedCallback>,
+}
+
+impl PendingRemovals {
+    /// Removes the elements matching `selector` and calls `on_removed`
+    /// once the client acknowledges the removal.
+    pub fn remove(
+        &mut self,
+        session: &mut dyn Session,
+        selector: &str,
+        on_removed: RemovedCallback
+    ) {
+        let id = generate_id();
+        remove_ui(session, selector);
+        send_custom_message(session, "remove-ui-done", json!({ "id": id }));
+        self.callbacks.insert(id, on_removed);
+    }
+
+    pub fn acknowledge(&mut self, id: &str) -> Option<RemovedCallback> {
+        self.callbacks.remove(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ Driver, MockSession };
+
+    #[test]
+    fn inserting_past_the_cap_removes_the_oldest() {
+        let mut inserted = InsertedUi::new(2);
+        let mut session = MockSession::default();
+        let ids: Vec<String> = (1..=3)
+            .map(|n| inserted.insert(&mut session, "#insert_section", "afterBegin", &format!("<p>{}</p>", n)))
+            .collect();
+        assert_eq!(session.removed(), vec![format!("#{}", ids[0])]);
+        assert_eq!(inserted.count("#insert_section"), 2);
+        let kept: Vec<String> = inserted.elements("#insert_section").into_iter().map(|(id, _)| id).collect();
+        assert_eq!(kept, ids[1..]);
+    }
+
+    #[test]
+    fn removal_callback_runs_once_the_client_acknowledges() {
+        let mut driver = Driver::new(CustomServer::builder().build());
+        driver.click("insert_ui");
+        driver.click("insert_ui");
+        driver.click("confirm_remove");
+        assert_eq!(driver.session.removed().last(), Some(&"#insert_section div"));
+        assert_eq!(driver.shiny.inserted_ui.count("#insert_section"), 2, "kept until acknowledged");
+        let id = driver.session.custom_messages("remove-ui-done")[0]["id"].clone();
+        driver.set("remove_ui_done", id.clone());
+        assert_eq!(driver.shiny.inserted_ui.count("#insert_section"), 0);
+        assert_eq!(driver.session.output("inserted_count"), Some("0 plots inserted"));
+        driver.session.clear();
+        driver.set("remove_ui_done", id);
+        assert_eq!(driver.session.output("inserted_count"), None, "called once");
+    }
+}
@@ -0,0 +1,131 @@
+//! Live figures of a session's responsiveness, shown next to the
+//! `latency` checkbox while it is checked: the round trip time to the page,
+//! the input messages handled per second and how long the last one took. The websocket's own
+//! pings are answered by the browser, out of the page's reach, so the round
+//! trip is timed with pings of the app's: a custom message the page echoes
+//! back as the `latency_pong` input, sent from the heartbeat's `tick`.
+
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+use serde_json::json;
+
+use super::i18n::t;
+use super::messages::{ Session, hide_element, render_ui, send_custom_message, show_element };
+use super::server::CustomServer;
+
+pub const PONG_KEY: &str = "latency_pong";
+
+/// Time between pings.
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Messages are counted over this last stretch of time.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+pub struct Latency {
+    enabled: bool,
+    /// Number and time of the last ping sent.
+    ping: Option<(u64, Instant)>,
+    rtt: Option<Duration>,
+    /// When the messages of the last `RATE_WINDOW` were handled.
+    handled: VecDeque<Instant>,
+    last_update: Option<Duration>,
+}
+
+impl Latency {
+    /// Records an input message that took `elapsed` to handle. Pongs are
+    /// left out, they'd only measure themselves.
+    pub fn record(&mut self, key: &str, elapsed: Duration) {
+        if key == PONG_KEY {
+            return;
+        }
+        let now = Instant::now();
+        self.handled.push_back(now);
+        self.forget(now);
+        self.last_update = Some(elapsed);
+    }
+
+    fn forget(&mut self, now: Instant) {
+        while self.handled.front().is_some_and(|at| now.duration_since(*at) > RATE_WINDOW) {
+            self.handled.pop_front();
+        }
+    }
+
+    pub fn messages_per_sec(&mut self) -> f64 {
+        self.forget(Instant::now());
+        self.handled.len() as f64 / RATE_WINDOW.as_secs_f64()
+    }
+
+    /// Takes the echo of ping `id`. Late echoes of earlier pings are
+    /// ignored.
+    fn pong(&mut self, id: u64) {
+        if let Some((_, sent)) = self.ping.filter(|(ping, _)| *ping == id) {
+            self.rtt = Some(sent.elapsed());
+        }
+    }
+
+    /// The number of the next ping, when one is due.
+    fn next_ping(&mut self) -> Option<u64> {
+        let id = match self.ping {
+            Some((_, sent)) if sent.elapsed() < PING_INTERVAL => return None,
+            Some((id, _)) => id + 1,
+            None => 1,
+        };
+        self.ping = Some((id, Instant::now()));
+        Some(id)
+    }
+}
+
+fn millis(duration: Option<Duration>) -> String {
+    duration.map_or_else(|| String::from("–"), |duration| format!("{:.1} ms", duration.as_secs_f64() * 1000.0))
+}
+
+fn render(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let rate = format!("{:.1}", shiny.latency.messages_per_sec());
+    let html = format!(
+        r#"<small class="font-monospace">{}</small>"#,
+        t!(
+            shiny,
+            "latency.summary",
+            rtt = millis(shiny.latency.rtt),
+            rate = rate,
+            update = millis(shiny.latency.last_update)
+        )
+    );
+    render_ui(session, "latency_info", &html);
+}
+
+/// Follows the `latency` checkbox, starting with a fresh round trip.
+pub fn set_enabled(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let enabled = shiny.inputs().get_bool("latency").unwrap_or(false);
+    shiny.latency.enabled = enabled;
+    shiny.latency.ping = None;
+    shiny.latency.rtt = None;
+    if enabled {
+        show_element(session, "#latency_info");
+        tick(shiny, session);
+    } else {
+        hide_element(session, "#latency_info");
+    }
+}
+
+/// Handles the `latency_pong` input.
+pub fn pong(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if let Some(id) = shiny.input.get_u64(PONG_KEY) {
+        shiny.latency.pong(id);
+    }
+    if shiny.latency.enabled {
+        render(shiny, session);
+    }
+}
+
+/// Pings the page when a ping is due and shows the figures, while enabled.
+pub fn tick(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if !shiny.latency.enabled {
+        return;
+    }
+    if let Some(id) = shiny.latency.next_ping() {
+        send_custom_message(session, "latency-ping", json!({ "id": id }));
+    }
+    render(shiny, session);
+}
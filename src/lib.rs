@@ -0,0 +1,63 @@
+pub mod server;
+pub mod plot;
+pub mod insert;
+pub mod messages;
+pub mod changes;
+pub mod tasks;
+pub mod markdown;
+pub mod scratch;
+pub mod events;
+pub mod notifications;
+pub mod validation;
+pub mod rate_limit;
+pub mod testing;
+pub mod registry;
+pub mod download;
+pub mod reactive;
+pub mod dist_module;
+pub mod upload;
+pub mod progress;
+pub mod simulate;
+pub mod stats;
+pub mod bookmark;
+pub mod app_state;
+pub mod chat;
+pub mod config;
+pub mod cli;
+pub mod metrics;
+pub mod shutdown;
+pub mod stream;
+pub mod debounce;
+pub mod typed_inputs;
+pub mod datatable;
+pub mod history;
+pub mod tests;
+pub mod cache;
+pub mod frames;
+pub mod admin;
+pub mod auth;
+pub mod roles;
+pub mod storage;
+pub mod presets;
+pub mod api;
+pub mod scheduler;
+pub mod reconnect;
+pub mod idle;
+pub mod panics;
+pub mod logic;
+pub mod recording;
+pub mod assets;
+pub mod tls;
+pub mod pages;
+pub mod plot_export;
+pub mod i18n;
+pub mod format;
+pub mod links;
+pub mod updates;
+pub mod import;
+pub mod files;
+pub mod latency;
+pub mod workers;
+pub mod snapshot;
+pub mod dev;
+pub mod clock;
@@ -0,0 +1,93 @@
+//! Inputs kept in step with each other. A link updates its target whenever
+//! its source changes, with the update message a transform makes of the
+//! source's value. Links can go both ways without bouncing: a value a link
+//! sets is remembered until the client echoes it back, and the echo isn't
+//! followed back to the source. Targets already holding the value aren't
+//! updated at all, so the client has nothing to echo.
+//!
+//! ```ignore
+//! let links = InputLinks::default()
+//!     .both("n:shiny.number", "n_slider", as_value, as_value);
+//! ```
+
+use std::collections::HashMap;
+use serde_json::Value;
+
+use super::messages::{ Session, update_text_input };
+use super::validation::input_id;
+
+/// Makes the message updating a link's target out of the source's value,
+/// `None` to leave the target as it is.
+pub type Transform = fn(&Value) -> Option<Value>;
+
+pub struct InputLink {
+    /// Input keys, as in the pool: `n:shiny.number` for a numeric input.
+    from: &'static str,
+    to: &'static str,
+    transform: Transform,
+}
+
+#[derive(Default)]
+pub struct InputLinks {
+    links: Vec<InputLink>,
+    /// Values the links set, by input, until the client echoes them.
+    pending: HashMap<&'static str, Value>,
+}
+
+impl InputLinks {
+    /// Updates `to` with `transform` of the value of `from` when `from`
+    /// changes.
+    pub fn link(mut self, from: &'static str, to: &'static str, transform: Transform) -> Self {
+        self.links.push(InputLink { from, to, transform });
+        self
+    }
+
+    /// Links `a` to `b` and `b` back to `a`.
+    pub fn both(self, a: &'static str, b: &'static str, a_to_b: Transform, b_to_a: Transform) -> Self {
+        self.link(a, b, a_to_b).link(b, a, b_to_a)
+    }
+
+    /// Whether `key` is the source of a link.
+    pub fn is_source(&self, key: &str) -> bool {
+        self.links.iter().any(|link| link.from == key)
+    }
+
+    /// Updates the targets of the links from `key`, whose value is now
+    /// `value`. `current` gives the value of a target. Returns false,
+    /// updating nothing, when the change is the echo of a value a link set.
+    pub fn follow(
+        &mut self,
+        session: &mut dyn Session,
+        key: &str,
+        value: &Value,
+        current: impl Fn(&str) -> Option<Value>
+    ) -> bool {
+        if let Some(pending) = self.pending.remove(key) {
+            if same(&pending, value) {
+                return false;
+            }
+        }
+        for link in self.links.iter().filter(|link| link.from == key) {
+            let message = match (link.transform)(value) {
+                Some(message) => message,
+                None => continue,
+            };
+            if let Some(target) = message.get("value") {
+                if current(link.to).is_some_and(|current| same(&current, target)) {
+                    continue;
+                }
+                self.pending.insert(link.to, target.clone());
+            }
+            update_text_input(session, input_id(link.to), message);
+        }
+        true
+    }
+}
+
+/// Whether two values are the same, `1` and `1.0` included.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
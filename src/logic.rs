@@ -0,0 +1,52 @@
+//! What a session does. `CustomServer` hands its start, every input change
+//! and every tick to a `ServerLogic`, which is `AppLogic`, the app's own,
+//! unless the builder is given another. One logic serves every session, so
+//! the state of a session lives in its `CustomServer`: its typed inputs,
+//! its `scratch` values and its fields.
+//!
+//! ```ignore
+//! struct Echo;
+//!
+//! impl ServerLogic for Echo {
+//!     fn initialize(&self, _shiny: &mut CustomServer, _session: &mut dyn Session) {}
+//!
+//!     fn update(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+//!         let text = shiny.inputs().get_string("text1").unwrap_or_default();
+//!         render_text(session, "echo", &text);
+//!     }
+//! }
+//!
+//! let server = CustomServer::builder().logic(Echo).build();
+//! ```
+
+use super::messages::Session;
+use super::server::{ self, CustomServer };
+
+pub trait ServerLogic: Send + Sync {
+    /// Called once the client sent its initial inputs.
+    fn initialize(&self, shiny: &mut CustomServer, session: &mut dyn Session);
+
+    /// Called for every input change, `shiny.event` naming the input.
+    fn update(&self, shiny: &mut CustomServer, session: &mut dyn Session);
+
+    /// Called by the heartbeat and the session's timers.
+    fn tick(&self, _shiny: &mut CustomServer, _session: &mut dyn Session) {}
+}
+
+/// The simulations, plots and everything else the app's UI has outputs
+/// for.
+pub struct AppLogic;
+
+impl ServerLogic for AppLogic {
+    fn initialize(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        server::initialize(shiny, session);
+    }
+
+    fn update(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        server::update(shiny, session);
+    }
+
+    fn tick(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        server::tick(shiny, session);
+    }
+}
@@ -1,29 +1,214 @@
-use actix_files::NamedFile;
+use actix::Actor;
+use clap::Parser;
 use actix_web::{
-    web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+    middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer,
 };
-mod server;
-mod plot;
-use server::create_server;
+use rusty_shiny::server::{ CustomServer, CustomServerBuilder };
+use rusty_shiny::config::Config;
+use rusty_shiny::cli::{ Cli, LogFormat };
+use rusty_shiny::metrics::{ metrics, AppMetrics };
+use rusty_shiny::shutdown::{ shutdown_signal, Shutdown, ShutdownCoordinator };
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+use rusty_shiny::registry::SessionRegistry;
+use rusty_shiny::bookmark::BookmarkStore;
+use rusty_shiny::app_state::AppState;
+use rusty_shiny::download::{ download_dist, download_markdown, download_plot };
+use rusty_shiny::upload::upload;
+use rusty_shiny::cache::SampleCache;
+use rusty_shiny::storage::Storage;
+use rusty_shiny::files::FileStore;
+use rusty_shiny::workers::WorkerPool;
+use rusty_shiny::dev;
+use rusty_shiny::api::simulate;
+use rusty_shiny::reconnect::ReconnectStore;
+use rusty_shiny::recording::replay;
+use rusty_shiny::assets;
+use rusty_shiny::pages::{ self, Page };
+use rusty_shiny::tls::{ self, redirect_to_https, HttpsPort };
+use rusty_shiny::scheduler::{ Job, Scheduled, Scheduler };
+use rusty_shiny::admin::{ admin, disconnect };
+use rusty_shiny::auth::{ self, Auth, User, require_login, login, login_form, logout };
 
-async fn index() -> impl Responder {
-    NamedFile::open_async("./static/index.html").await.unwrap()
+/// Starts a session with the shared state of `builder`.
+async fn server1(
+    req: HttpRequest,
+    stream: web::Payload,
+    builder: web::Data<CustomServerBuilder>,
+    user: Option<web::ReqData<User>>
+) -> Result<HttpResponse, Error> {
+    let user = user.map(|user| user.into_inner());
+    let server = builder.get_ref().clone().user(user).build();
+    let frame_size = server.max_frame_size();
+    actix_web_actors::ws::WsResponseBuilder::new(server, &req, stream)
+        .frame_size(frame_size)
+        .start()
 }
 
-async fn server1(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
-    actix_web_actors::ws::start(create_server(), &req, stream)
+/// `page` under its path, its sessions built by `builder`.
+fn page_scope(page: Page, builder: &CustomServerBuilder) -> actix_web::Scope {
+    web::scope(&format!("/{}", page.path()))
+        .app_data(web::Data::new(page))
+        .app_data(web::Data::new(page.builder(builder)))
+        .service(web::resource("").to(pages::add_slash))
+        .service(web::resource("/").route(web::get().to(pages::page)))
+        .service(web::resource("/lib/{path:.*}").route(web::get().to(assets::lib)))
+        .service(web::resource("/www/{path:.*}").route(web::get().to(assets::www)))
+        .service(web::resource("/websocket/").route(web::get().to(server1)))
+        .service(web::resource("/download/dist.csv").route(web::get().to(download_dist)))
+        .service(web::resource("/download/markdown.html").route(web::get().to(download_markdown)))
+        .service(web::resource("/download/plot").route(web::get().to(download_plot)))
+        .service(web::resource("/upload").route(web::post().to(upload)))
 }
 
-#[tokio::main(flavor = "current_thread")]
+#[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new()
-            .service(web::resource("/").to(index))
-            .service(actix_files::Files::new("/lib", "./static/lib").show_files_listing())
-            .service(web::resource("/websocket/").route(web::get().to(server1)))
+    let cli = Cli::parse();
+    if cli.hash_password {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        let hash = auth::hash_password(password.trim_end_matches(['\r', '\n']))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        println!("{}", hash);
+        return Ok(());
+    }
+    rusty_shiny::panics::install_hook();
+    let filter = match &cli.log_level {
+        Some(level) => EnvFilter::new(level),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let logger = tracing_subscriber::fmt().with_env_filter(filter);
+    match cli.log_format {
+        LogFormat::Pretty => logger.pretty().init(),
+        LogFormat::Json => logger.json().init(),
+    }
+    let mut config = Config::load(&cli.config)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    cli.apply(&mut config);
+    if let Some(path) = &cli.replay {
+        config.record_dir = None;
+        return replay(path, config, std::io::stdout().lock());
+    }
+    let workers = config.workers;
+    let address = (config.host.clone(), config.port);
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::server_config(cert, key)?),
+        (None, None) => None,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "tls_cert and tls_key must be set together"
+            ))
+        }
+    };
+    let redirect_address = config.http_redirect_port
+        .filter(|_| tls.is_some())
+        .map(|port| (config.host.clone(), port));
+    let shutdown_timeout = config.shutdown_timeout;
+    let registry = SessionRegistry::default();
+    let app_metrics = Arc::new(AppMetrics::default());
+    let coordinator = ShutdownCoordinator::new(registry.clone()).start();
+    let auth = web::Data::new(Auth::new(config.users.clone(), config.login_ttl(), tls.is_some()));
+    let storage = match &config.database {
+        Some(path) => Storage::open(path).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, e))
+        })?,
+        None => Storage::default(),
+    };
+    let mut scheduler = Scheduler::new(registry.clone());
+    if let Some(notice) = config.notice.clone().filter(|_| config.notice_interval > 0) {
+        let every = Duration::from_secs(config.notice_interval);
+        scheduler = scheduler.job(Job::new("notice", every, move || Some(Scheduled::Notice(notice.clone()))));
+    }
+    if config.runs_refresh_interval > 0 {
+        let every = Duration::from_secs(config.runs_refresh_interval);
+        scheduler = scheduler.job(Job::new("runs_refresh", every, || Some(Scheduled::RefreshRuns)));
+    }
+    let reconnect = ReconnectStore::new(config.reconnect_grace());
+    if config.reconnect_grace > 0 {
+        let store = reconnect.clone();
+        scheduler = scheduler.job(Job::new("reconnect_sweep", config.reconnect_grace(), move || {
+            store.sweep();
+            None
+        }));
+    }
+    let files = FileStore::new(config.files_dir(), config.files_quota);
+    if let Err(e) = files.sweep() {
+        tracing::warn!(error = %e, "files of a previous run could not be removed");
+    }
+    if cli.dev {
+        dev::watch("static", registry.clone());
+    }
+    let sample_cache = SampleCache::default();
+    let builder = CustomServer::builder()
+        .config(config.clone())
+        .registry(registry.clone())
+        .bookmarks(BookmarkStore::default())
+        .reconnect(reconnect)
+        .app_state(AppState::default().start())
+        .worker_pool(WorkerPool::start(config.simulation_workers, config.simulation_queue))
+        .metrics(app_metrics.clone())
+        .sample_cache(sample_cache.clone())
+        .storage(storage)
+        .files(files);
+    let config = web::Data::new(config);
+    let sample_cache = web::Data::new(sample_cache);
+    let server = HttpServer::new(move || {
+        let app = App::new()
+            .app_data(config.clone())
+            .app_data(sample_cache.clone())
+            .app_data(auth.clone())
+            .wrap(middleware::from_fn(require_login))
+            .app_data(web::Data::new(registry.clone()))
+            .app_data(web::Data::from(app_metrics.clone()))
+            .service(web::resource("/").route(web::get().to(pages::landing)))
+            .service(web::resource("/login").route(web::get().to(login_form)).route(web::post().to(login)))
+            .service(web::resource("/logout").route(web::post().to(logout)))
+            .service(web::resource("/metrics").route(web::get().to(metrics)))
+            .service(web::resource("/api/v1/simulate").route(web::get().to(simulate)))
+            .service(web::resource("/admin").route(web::get().to(admin)))
+            .service(web::resource("/admin/disconnect/{id}").route(web::post().to(disconnect)));
+        Page::ALL.into_iter().fold(app, |app, page| app.service(page_scope(page, &builder)))
     })
-    .workers(2)
-    .bind(("0.0.0.0", 8080))?
-    .run()
-    .await
+    .workers(workers)
+    .shutdown_timeout(shutdown_timeout)
+    .disable_signals();
+    let https_port = HttpsPort(address.1);
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23(address, tls)?,
+        None => server.bind(address)?,
+    }
+    .run();
+    let redirect = match redirect_address {
+        Some(address) => Some(
+            HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(https_port))
+                    .default_service(web::to(redirect_to_https))
+            })
+            .workers(1)
+            .disable_signals()
+            .bind(address)?
+            .run()
+        ),
+        None => None,
+    };
+    scheduler.start();
+    let handle = server.handle();
+    let redirect_handle = redirect.as_ref().map(|redirect| redirect.handle());
+    if let Some(redirect) = redirect {
+        actix_web::rt::spawn(redirect);
+    }
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        let _ = coordinator.send(Shutdown).await;
+        // Give the close frames a moment to reach the clients.
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+        if let Some(redirect_handle) = redirect_handle {
+            redirect_handle.stop(true).await;
+        }
+        handle.stop(true).await;
+    });
+    server.await
 }
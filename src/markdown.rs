@@ -0,0 +1,124 @@
+use std::collections::{ HashMap, VecDeque };
+use std::sync::OnceLock;
+use comrak::plugins::syntect::SyntectAdapter;
+use comrak::{ markdown_to_html_with_plugins, ComrakOptions, ComrakPlugins };
+
+const CACHE_SIZE: usize = 32;
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+/// Loading the syntaxes and themes takes a while, so every session shares
+/// one highlighter.
+fn highlighter() -> &'static SyntectAdapter<'static> {
+    static HIGHLIGHTER: OnceLock<SyntectAdapter<'static>> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| SyntectAdapter::new(HIGHLIGHT_THEME))
+}
+
+/// Allows what comrak and the highlighter emit on top of ammonia's
+/// defaults: task list checkboxes, footnote anchors and inline colors.
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        builder
+            .add_tags(&["input"])
+            .add_tag_attributes("input", &["type", "checked", "disabled"])
+            .add_tag_attributes("span", &["style"])
+            .add_tag_attributes("pre", &["style", "lang"])
+            .add_generic_attributes(&["id", "class"]);
+        builder
+    })
+}
+
+/// The markdown extensions users can turn on.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct MarkdownOptions {
+    pub tables: bool,
+    pub strikethrough: bool,
+    pub footnotes: bool,
+    pub tasklist: bool,
+}
+
+/// Renders markdown to sanitized HTML, highlighting fenced code blocks and
+/// reusing the output of recently rendered sources instead of parsing them
+/// again. Outputs are looked up by their whole source, so two sources
+/// never share an output.
+pub struct MarkdownRenderer {
+    options: ComrakOptions,
+    cache: HashMap<String, String>,
+    /// Sources in the cache, oldest first.
+    order: VecDeque<String>,
+}
+
+impl MarkdownRenderer {
+    pub fn new(options: ComrakOptions) -> Self {
+        MarkdownRenderer {
+            options,
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Switches extensions on or off, dropping the outputs rendered with
+    /// the previous ones.
+    pub fn set_options(&mut self, options: MarkdownOptions) {
+        let extension = &mut self.options.extension;
+        extension.table = options.tables;
+        extension.strikethrough = options.strikethrough;
+        extension.footnotes = options.footnotes;
+        extension.tasklist = options.tasklist;
+        self.cache.clear();
+        self.order.clear();
+    }
+
+    pub fn render(&mut self, source: &str) -> String {
+        if let Some(html) = self.cache.get(source) {
+            return html.clone();
+        }
+        let mut plugins = ComrakPlugins::default();
+        plugins.render.codefence_syntax_highlighter = Some(highlighter());
+        let html = markdown_to_html_with_plugins(source, &self.options, &plugins);
+        let html = sanitizer().clean(&html).to_string();
+        if self.order.len() == CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+        self.order.push_back(source.to_string());
+        self.cache.insert(source.to_string(), html.clone());
+        html
+    }
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        MarkdownRenderer::new(ComrakOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_sources_are_parsed_once() {
+        let mut renderer = MarkdownRenderer::default();
+        let html = renderer.render("# Title");
+        assert_eq!(html, "<h1>Title</h1>\n");
+        assert_eq!(renderer.render("# Title"), html);
+        // A second parse would have queued the source again.
+        assert_eq!(renderer.order.len(), 1);
+        assert_eq!(renderer.render("## Title"), "<h2>Title</h2>\n");
+        assert_eq!(renderer.order.len(), 2);
+    }
+
+    #[test]
+    fn oldest_sources_leave_the_cache_first() {
+        let mut renderer = MarkdownRenderer::default();
+        for n in 0..=CACHE_SIZE {
+            renderer.render(&n.to_string());
+        }
+        assert_eq!(renderer.cache.len(), CACHE_SIZE);
+        assert!(!renderer.cache.contains_key("0"));
+        assert!(renderer.cache.contains_key(&CACHE_SIZE.to_string()));
+    }
+}
@@ -0,0 +1,368 @@
+use serde_json::{ json, Value };
+
+use super::server::CustomSession;
+
+/// Destination of the messages the handlers send to the client. Handlers
+/// take a `&mut dyn Session` so they can run against the websocket as
+/// well as against `testing::MockSession`.
+pub trait Session {
+    fn send(&mut self, message: Value);
+
+    /// The actor context behind the session, for handlers that need to
+    /// spawn futures or message the actor. `None` outside a live websocket.
+    fn actor_context(&mut self) -> Option<&mut CustomSession> {
+        None
+    }
+}
+
+impl Session for CustomSession {
+    fn send(&mut self, message: Value) {
+        self.text(message.to_string());
+    }
+
+    fn actor_context(&mut self) -> Option<&mut CustomSession> {
+        Some(self)
+    }
+}
+
+/// The output functions of Shiny's UI, by the binding the client gives the
+/// element they create. The client picks the binding from the element's
+/// CSS class and drops values of the wrong shape without a word.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputKind {
+    /// `uiOutput`, bound like `htmlOutput`.
+    Ui,
+    /// `htmlOutput`, whose binding takes `{ html, deps }`.
+    Html,
+    /// `textOutput`, whose binding takes a plain string.
+    Text,
+}
+
+impl OutputKind {
+    /// Class of the element the output function creates.
+    pub fn class(self) -> &'static str {
+        match self {
+            OutputKind::Ui | OutputKind::Html => "shiny-html-output",
+            OutputKind::Text => "shiny-text-output",
+        }
+    }
+
+    /// Kind of an element with CSS class `class`, `None` if it isn't an
+    /// output of these kinds. `uiOutput` and `htmlOutput` can't be told
+    /// apart, both are `Html`.
+    pub fn from_class(class: &str) -> Option<Self> {
+        match class {
+            "shiny-html-output" => Some(OutputKind::Html),
+            "shiny-text-output" => Some(OutputKind::Text),
+            _ => None,
+        }
+    }
+
+    /// `content` as the binding of the kind expects it.
+    pub fn value(self, content: &str) -> Value {
+        match self {
+            OutputKind::Ui | OutputKind::Html => json!({ "html": content, "deps": [] }),
+            OutputKind::Text => json!(content),
+        }
+    }
+}
+
+/// Renders `content` into output `id` of kind `kind`.
+pub fn render_output(session: &mut dyn Session, id: &str, kind: OutputKind, content: &str) {
+    render_value(session, id, kind.value(content));
+}
+
+/// Renders `html` into a `uiOutput` or `htmlOutput`.
+pub fn render_ui(session: &mut dyn Session, id: &str, html: &str) {
+    render_output(session, id, OutputKind::Html, html);
+}
+
+pub fn insert_ui(session: &mut dyn Session, selector: &str, position: &str, html: &str) {
+    session.send(json!({
+        "shiny-insert-ui": {
+            "selector": selector,
+            "multiple": false,
+            "where": position,
+            "content": { "html": html, "deps": [] }
+        }
+    }));
+}
+
+/// Removes every element matching `selector`.
+pub fn remove_ui(session: &mut dyn Session, selector: &str) {
+    session.send(json!({
+        "shiny-remove-ui": {
+            "selector": selector,
+            "multiple": true
+        }
+    }));
+}
+
+/// Whether the client reconnects when the websocket closes: `true`,
+/// `false`, or `"force"` to reconnect even without Shiny Server.
+pub fn allow_reconnect(session: &mut dyn Session, value: Value) {
+    session.send(json!({ "allowReconnect": value }));
+}
+
+pub fn show_notification(session: &mut dyn Session, notification: Value) {
+    session.send(json!({
+        "notification": {
+            "type": "show",
+            "message": notification
+        }
+    }));
+}
+
+pub fn remove_notification(session: &mut dyn Session, id: &str) {
+    session.send(json!({
+        "notification": {
+            "type": "remove",
+            "message": id
+        }
+    }));
+}
+
+pub fn update_text_input(session: &mut dyn Session, id: &str, message: Value) {
+    session.send(json!({
+        "inputMessages": [{ "id": id, "message": message }]
+    }));
+}
+
+/// HTML of a modal dialog, as `shiny::modalDialog` builds it. The dialog
+/// can only be closed by the buttons in `footer`.
+pub fn modal_dialog(title: &str, body: &str, footer: &str) -> String {
+    format!(
+        r#"<div id="shiny-modal" class="modal fade" tabindex="-1" data-backdrop="static" data-bs-backdrop="static" data-keyboard="false" data-bs-keyboard="false">
+  <div class="modal-dialog">
+    <div class="modal-content">
+      <div class="modal-header"><h4 class="modal-title">{}</h4></div>
+      <div class="modal-body">{}</div>
+      <div class="modal-footer">{}</div>
+    </div>
+  </div>
+  <script>if (window.bootstrap && !window.bootstrap.Modal.VERSION.match(/^4\./)) {{
+    new bootstrap.Modal(document.getElementById('shiny-modal')).show();
+  }} else {{
+    $('#shiny-modal').modal().focus();
+  }}</script>
+</div>"#,
+        title,
+        body,
+        footer
+    )
+}
+
+/// Shows `html`, usually built with `modal_dialog`, replacing any open
+/// modal.
+pub fn show_modal(session: &mut dyn Session, html: &str) {
+    session.send(json!({
+        "modal": {
+            "type": "show",
+            "message": { "html": html, "deps": [] }
+        }
+    }));
+}
+
+pub fn remove_modal(session: &mut dyn Session) {
+    session.send(json!({
+        "modal": {
+            "type": "remove",
+            "message": null
+        }
+    }));
+}
+
+/// Sends a message to the handler registered on the client with
+/// `Shiny.addCustomMessageHandler(kind, ...)`. The app's handlers live in
+/// static/www/app.js.
+pub fn send_custom_message(session: &mut dyn Session, kind: &str, message: Value) {
+    session.send(json!({ "custom": { kind: message } }));
+}
+
+/// Shows the elements matching `selector`, hidden with `hide_element` or
+/// with Bootstrap's `d-none` class in the page.
+pub fn show_element(session: &mut dyn Session, selector: &str) {
+    send_custom_message(session, "toggle-element", json!({ "selector": selector, "visible": true }));
+}
+
+/// Hides the elements matching `selector`. Shiny counts the outputs in them
+/// as hidden.
+pub fn hide_element(session: &mut dyn Session, selector: &str) {
+    send_custom_message(session, "toggle-element", json!({ "selector": selector, "visible": false }));
+}
+
+/// Shows the elements matching `selector` that are hidden and hides the
+/// others.
+pub fn toggle_element(session: &mut dyn Session, selector: &str) {
+    send_custom_message(session, "toggle-element", json!({ "selector": selector, "visible": null }));
+}
+
+/// Draws a Plotly figure, as built by `plot::get_plot_json`, into the
+/// output `id`. The client renders it with `Plotly.newPlot`.
+pub fn render_plotly(session: &mut dyn Session, id: &str, figure: &str) {
+    let figure: Value = serde_json::from_str(figure).unwrap_or(Value::Null);
+    send_custom_message(session, "render-plotly", json!({ "id": id, "figure": figure }));
+}
+
+/// Scrolls the first element matching `selector` into view. Messages are
+/// handled in order by the client, so calling this right after
+/// `insert_ui` scrolls to the freshly inserted element.
+pub fn scroll_into_view(session: &mut dyn Session, selector: &str) {
+    send_custom_message(
+        session,
+        "scroll-into-view",
+        json!({
+            "selector": selector
+        })
+    );
+}
+
+/// Sends `value` as the new value of output `id`, for outputs whose
+/// binding takes a plain value, such as `downloadButton` which expects
+/// the download URL.
+pub fn render_value(session: &mut dyn Session, id: &str, value: Value) {
+    session.send(json!({
+        "errors": {},
+        "values": { id: value },
+        "inputMessages": []
+    }));
+}
+
+/// Renders `text` into a `textOutput`.
+pub fn render_text(session: &mut dyn Session, id: &str, text: &str) {
+    render_output(session, id, OutputKind::Text, text);
+}
+
+pub enum OutputErrorKind {
+    /// Shown with Shiny's standard output error styling.
+    Error,
+    /// Shown like the messages of `shiny::validate`, in grey.
+    Validation,
+}
+
+impl OutputErrorKind {
+    fn classes(&self) -> Value {
+        match self {
+            OutputErrorKind::Error => json!(["error"]),
+            OutputErrorKind::Validation => json!(["shiny.silent.error", "validation"]),
+        }
+    }
+}
+
+/// Puts output `id` into Shiny's error state. The client adds a
+/// `shiny-output-error-<class>` CSS class per entry of the error type, so
+/// both kinds can be styled or hidden client-side.
+pub fn render_error(session: &mut dyn Session, id: &str, message: &str, kind: OutputErrorKind) {
+    session.send(json!({
+        "errors": {
+            id: {
+                "message": message,
+                "call": null,
+                "type": kind.classes()
+            }
+        },
+        "values": {},
+        "inputMessages": []
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use regex::Regex;
+    use super::*;
+    use crate::server::CustomServer;
+    use crate::testing::{ Driver, MockSession };
+
+    fn rendered(kind: OutputKind) -> Value {
+        let mut session = MockSession::default();
+        render_output(&mut session, "out", kind, "<b>x</b>");
+        session.messages[0]["values"]["out"].clone()
+    }
+
+    #[test]
+    fn ui_outputs_get_html_and_deps() {
+        assert_eq!(OutputKind::Ui.class(), "shiny-html-output");
+        assert_eq!(rendered(OutputKind::Ui), json!({ "html": "<b>x</b>", "deps": [] }));
+    }
+
+    #[test]
+    fn html_outputs_get_html_and_deps() {
+        assert_eq!(OutputKind::from_class("shiny-html-output"), Some(OutputKind::Html));
+        assert_eq!(rendered(OutputKind::Html), json!({ "html": "<b>x</b>", "deps": [] }));
+    }
+
+    #[test]
+    fn text_outputs_get_a_string() {
+        assert_eq!(OutputKind::from_class("shiny-text-output"), Some(OutputKind::Text));
+        assert_eq!(rendered(OutputKind::Text), json!("<b>x</b>"));
+    }
+
+    #[test]
+    fn errors_use_shiny_error_envelope() {
+        let mut session = MockSession::default();
+        render_error(&mut session, "plot1", "n must be positive", OutputErrorKind::Error);
+        render_error(&mut session, "plot1", "check the inputs", OutputErrorKind::Validation);
+        assert_eq!(session.messages[0], json!({
+            "errors": { "plot1": { "message": "n must be positive", "call": null, "type": ["error"] } },
+            "values": {},
+            "inputMessages": []
+        }));
+        assert_eq!(session.messages[1]["errors"]["plot1"]["type"], json!(["shiny.silent.error", "validation"]));
+        assert_eq!(session.output_error("plot1"), Some("check the inputs"));
+        render_ui(&mut session, "plot1", "<div></div>");
+        assert_eq!(session.output_error("plot1"), None, "cleared by the next value");
+    }
+
+    #[test]
+    fn outputs_get_what_their_page_declares() {
+        let pattern = Regex::new(r#"id="([^"]+)" class="(shiny-(?:html|text)-output)"#).unwrap();
+        let page = include_str!("../static/index.html");
+        let declared: HashMap<&str, OutputKind> = pattern
+            .captures_iter(page)
+            .filter_map(|captures| {
+                let kind = OutputKind::from_class(captures.get(2)?.as_str())?;
+                Some((captures.get(1)?.as_str(), kind))
+            })
+            .collect();
+        let mut driver = Driver::new(CustomServer::builder().build());
+        driver.set("markdown", json!("# Title"));
+        driver.click("insert_ui");
+        let mut checked = 0;
+        for message in &driver.session.messages {
+            let values = match message.get("values").and_then(Value::as_object) {
+                Some(values) => values,
+                None => continue,
+            };
+            for (id, value) in values {
+                let kind = match declared.get(id.as_str()) {
+                    Some(kind) => *kind,
+                    None => continue,
+                };
+                let expected = kind.value("");
+                assert_eq!(value.is_string(), expected.is_string(), "{} is declared {:?} but got {}", id, kind, value);
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn inserted_plot_is_scrolled_into_view_after_insertion() {
+        let mut driver = Driver::new(CustomServer::builder().build());
+        driver.session.clear();
+        driver.click("insert_ui");
+        let messages = &driver.session.messages;
+        let inserted = messages
+            .iter()
+            .position(|message| message.get("shiny-insert-ui").is_some())
+            .expect("plot inserted");
+        let scrolled = messages
+            .iter()
+            .position(|message| message.get("custom").and_then(|custom| custom.get("scroll-into-view")).is_some())
+            .expect("plot scrolled into view");
+        assert!(inserted < scrolled);
+        let id = driver.shiny.inserted_ui.elements("#insert_section")[0].0.clone();
+        assert_eq!(messages[scrolled]["custom"]["scroll-into-view"]["selector"], format!("#{}", id));
+    }
+}
@@ -0,0 +1,145 @@
+//! Counters exposed in the Prometheus text format at `/metrics`.
+
+use std::fmt::Write;
+use std::sync::atomic::{ AtomicI64, AtomicU64, Ordering };
+use std::time::Duration;
+use actix_web::{ web, HttpResponse };
+use serde_json::Value;
+
+use super::messages::Session;
+use super::server::CustomSession;
+
+/// Upper bounds, in seconds, of the update latency buckets.
+const LATENCY_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+#[derive(Default)]
+pub struct Histogram {
+    counts: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.counts) {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{}_sum {}\n{}_count {}", name, sum, name, count);
+    }
+}
+
+/// Shared by every session and the `/metrics` route.
+#[derive(Default)]
+pub struct AppMetrics {
+    pub active_sessions: AtomicI64,
+    pub messages_received: AtomicU64,
+    pub messages_sent: AtomicU64,
+    pub samples_generated: AtomicU64,
+    pub notifications_shown: AtomicU64,
+    pub sample_cache_hits: AtomicU64,
+    pub sample_cache_misses: AtomicU64,
+    pub plot_cache_hits: AtomicU64,
+    pub plot_cache_misses: AtomicU64,
+    pub handler_panics: AtomicU64,
+    pub messages_rejected: AtomicU64,
+    pub update_latency: Histogram,
+}
+
+impl AppMetrics {
+    pub fn add_samples(&self, n: u64) {
+        self.samples_generated.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP rusty_shiny_active_sessions Open websocket sessions\n\
+             # TYPE rusty_shiny_active_sessions gauge\n\
+             rusty_shiny_active_sessions {}",
+            self.active_sessions.load(Ordering::Relaxed)
+        );
+        let counters = [
+            ("messages_received", "Messages received from clients", &self.messages_received),
+            ("messages_sent", "Messages sent to clients", &self.messages_sent),
+            ("samples_generated", "Random values drawn", &self.samples_generated),
+            ("notifications_shown", "Notifications sent to clients", &self.notifications_shown),
+            ("sample_cache_hits", "Seeded samples found in the cache", &self.sample_cache_hits),
+            ("sample_cache_misses", "Seeded samples drawn anew", &self.sample_cache_misses),
+            ("plot_cache_hits", "Plots found in a session's cache", &self.plot_cache_hits),
+            ("plot_cache_misses", "Plots rendered anew", &self.plot_cache_misses),
+            ("handler_panics", "Panics caught in session handlers", &self.handler_panics),
+            ("messages_rejected", "Messages dropped for their rate or size", &self.messages_rejected),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(
+                out,
+                "# HELP rusty_shiny_{name}_total {help}\n\
+                 # TYPE rusty_shiny_{name}_total counter\n\
+                 rusty_shiny_{name}_total {value}",
+                name = name,
+                help = help,
+                value = counter.load(Ordering::Relaxed)
+            );
+        }
+        self.update_latency.render(
+            &mut out,
+            "rusty_shiny_update_seconds",
+            "Time spent handling an input message"
+        );
+        out
+    }
+}
+
+/// Counts the messages and notifications sent through `session`.
+pub struct MeteredSession<'a> {
+    session: &'a mut dyn Session,
+    metrics: &'a AppMetrics,
+}
+
+impl<'a> MeteredSession<'a> {
+    pub fn new(session: &'a mut dyn Session, metrics: &'a AppMetrics) -> Self {
+        MeteredSession { session, metrics }
+    }
+}
+
+impl<'a> Session for MeteredSession<'a> {
+    fn send(&mut self, message: Value) {
+        self.metrics.messages_sent.fetch_add(1, Ordering::Relaxed);
+        if message["notification"]["type"] == "show" {
+            self.metrics.notifications_shown.fetch_add(1, Ordering::Relaxed);
+        }
+        self.session.send(message);
+    }
+
+    fn actor_context(&mut self) -> Option<&mut CustomSession> {
+        self.session.actor_context()
+    }
+}
+
+pub async fn metrics(metrics: web::Data<AppMetrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render())
+}
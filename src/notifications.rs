@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use serde_json::Value;
+use shiny_rs::ui::*;
+
+use super::messages::{ Session, show_notification };
+
+pub enum NotificationMode {
+    /// Shown right away, stacking on top of any visible notification.
+    Immediate,
+    /// Shown once every previously queued notification was dismissed.
+    Queued,
+}
+
+/// Serializes the display of queued notifications. The client reports
+/// dismissed notifications through the `notification_dismissed` input.
+#[derive(Default)]
+pub struct Notifications {
+    queue: VecDeque<Value>,
+    showing: Option<String>,
+}
+
+impl Notifications {
+    pub fn show(&mut self, session: &mut dyn Session, mode: NotificationMode, mut notification: Value) {
+        match mode {
+            NotificationMode::Immediate => show_notification(session, notification),
+            NotificationMode::Queued => {
+                if !notification["id"].is_string() {
+                    notification["id"] = Value::from(generate_id());
+                }
+                self.queue.push_back(notification);
+                if self.showing.is_none() {
+                    self.show_next(session);
+                }
+            }
+        }
+    }
+
+    pub fn dismissed(&mut self, session: &mut dyn Session, id: &str) {
+        if self.showing.as_deref() == Some(id) {
+            self.showing = None;
+            self.show_next(session);
+        }
+    }
+
+    fn show_next(&mut self, session: &mut dyn Session) {
+        if let Some(notification) = self.queue.pop_front() {
+            self.showing = notification["id"].as_str().map(String::from);
+            show_notification(session, notification);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::testing::MockSession;
+
+    fn shown(session: &MockSession) -> Vec<&str> {
+        session.notifications().iter().filter_map(|notification| notification["html"].as_str()).collect()
+    }
+
+    #[test]
+    fn queued_notifications_are_shown_one_after_another() {
+        let mut notifications = Notifications::default();
+        let mut session = MockSession::default();
+        for html in ["first", "second", "third"] {
+            notifications.show(&mut session, NotificationMode::Queued, json!({ "html": html }));
+        }
+        assert_eq!(shown(&session), ["first"]);
+        notifications.dismissed(&mut session, "not-shown");
+        assert_eq!(shown(&session), ["first"]);
+        for expected in [vec!["first", "second"], vec!["first", "second", "third"]] {
+            let id = session.notifications().last().unwrap()["id"].as_str().unwrap().to_string();
+            notifications.dismissed(&mut session, &id);
+            assert_eq!(shown(&session), expected);
+        }
+    }
+
+    #[test]
+    fn immediate_notifications_skip_the_queue() {
+        let mut notifications = Notifications::default();
+        let mut session = MockSession::default();
+        notifications.show(&mut session, NotificationMode::Queued, json!({ "html": "queued", "id": "q" }));
+        notifications.show(&mut session, NotificationMode::Queued, json!({ "html": "waiting", "id": "w" }));
+        notifications.show(&mut session, NotificationMode::Immediate, json!({ "html": "now" }));
+        assert_eq!(shown(&session), ["queued", "now"]);
+    }
+}
@@ -0,0 +1,138 @@
+//! The pages the app serves. `/` lists them; each of the others is a Shiny
+//! app of its own, mounted under its path with its websocket, files,
+//! downloads and uploads, whose sessions run the page's `ServerLogic`.
+//! Every page shares the registry, the metrics and the rest of the state
+//! the builder carries, so the admin page and the scheduled jobs see every
+//! session whichever page it's on.
+
+use actix_web::{ http::header, web, HttpRequest, HttpResponse };
+use serde_json::json;
+
+use super::assets;
+use super::idle;
+use super::logic::ServerLogic;
+use super::messages::{ Session, allow_reconnect };
+use super::server::{ self, CustomServer, CustomServerBuilder };
+
+#[derive(Clone, Copy)]
+pub enum Page {
+    /// The simulations and every other demo, run by `AppLogic`.
+    Simulator,
+    /// The markdown editor alone, run by `MarkdownLogic`.
+    Markdown,
+}
+
+impl Page {
+    pub const ALL: [Page; 2] = [Page::Simulator, Page::Markdown];
+
+    /// Where the page is mounted, without slashes.
+    pub fn path(self) -> &'static str {
+        match self {
+            Page::Simulator => "simulator",
+            Page::Markdown => "markdown",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Page::Simulator => "Simulator",
+            Page::Markdown => "Markdown",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Page::Simulator => "Sample distributions, plot and summarize them, and the rest of the demos.",
+            Page::Markdown => "Write markdown and see it rendered as you type.",
+        }
+    }
+
+    /// The page's HTML, under `static/`.
+    fn file(self) -> &'static str {
+        match self {
+            Page::Simulator => "index.html",
+            Page::Markdown => "markdown.html",
+        }
+    }
+
+    /// `builder`, building sessions that run this page's logic.
+    pub fn builder(self, builder: &CustomServerBuilder) -> CustomServerBuilder {
+        match self {
+            Page::Simulator => builder.clone(),
+            Page::Markdown => builder.clone().logic(MarkdownLogic),
+        }
+    }
+}
+
+fn landing_page() -> String {
+    let links: String = Page::ALL
+        .iter()
+        .map(|page| {
+            format!("<li><a href=\"{}/\">{}</a> {}</li>\n", page.path(), page.title(), page.description())
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Shiny-rs example</title>
+<style>
+body {{ max-width: 40em; margin: 4em auto; font-family: sans-serif; color: #222; }}
+li {{ margin-bottom: 0.75em; }}
+</style>
+</head>
+<body>
+<h1>Shiny-rs example</h1>
+<ul>
+{}</ul>
+</body>
+</html>
+"#,
+        links
+    )
+}
+
+pub async fn landing() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(landing_page())
+}
+
+pub async fn page(req: HttpRequest, page: web::Data<Page>) -> HttpResponse {
+    assets::html(&req, page.file())
+}
+
+/// Sends `/simulator` to `/simulator/`, which the page's relative URLs
+/// need.
+pub async fn add_slash(req: HttpRequest) -> HttpResponse {
+    let name = req.path().rsplit('/').next().unwrap_or_default();
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, format!("{}/", name)))
+        .finish()
+}
+
+/// The markdown page's sessions: the editor, and the connection handling
+/// every page does.
+pub struct MarkdownLogic;
+
+impl ServerLogic for MarkdownLogic {
+    fn initialize(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        let _span = shiny.span.clone().entered();
+        allow_reconnect(session, json!("force"));
+        server::initialize_markdown(shiny, session);
+    }
+
+    fn update(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        let _span = shiny.span.clone().entered();
+        if server::update_connection(shiny, session) {
+            server::update_markdown(shiny, session);
+        }
+    }
+
+    fn tick(&self, shiny: &mut CustomServer, session: &mut dyn Session) {
+        idle::check(shiny, session);
+        server::replay_rate_limited(shiny, session);
+        server::flush_markdown(shiny, session);
+    }
+}
@@ -0,0 +1,48 @@
+//! Keeping sessions alive through panics in their handlers. `catch` runs a
+//! handler, turning a panic into an error carrying the panic's message and
+//! a backtrace, which the panic hook installed by `install_hook` captures
+//! while the panicking frames still exist.
+
+use std::backtrace::Backtrace;
+use std::cell::{ Cell, RefCell };
+use std::panic::{ self, AssertUnwindSafe };
+
+thread_local! {
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// A panic caught by `catch`.
+pub struct Caught {
+    pub message: String,
+    pub backtrace: Option<Backtrace>,
+}
+
+/// Captures the backtrace of panics `catch` is catching, leaving the others
+/// to the previous hook.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if CATCHING.with(Cell::get) {
+            BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(Backtrace::force_capture()));
+        } else {
+            previous(info);
+        }
+    }));
+}
+
+/// Runs `f`, returning the panic it raised, if any. State `f` was changing
+/// may be left half changed.
+pub fn catch<R>(f: impl FnOnce() -> R) -> Result<R, Caught> {
+    let catching = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|flag| flag.set(catching));
+    result.map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown panic"));
+        Caught { message, backtrace: BACKTRACE.with(|backtrace| backtrace.borrow_mut().take()) }
+    })
+}
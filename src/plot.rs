@@ -1,25 +1,558 @@
-use rand::distributions::Distribution;
-use statrs::distribution::Normal;
-use plotly::Histogram;
-use plotly::Plot;
-
-pub fn get_dist(n: usize, mean: f64, sd: f64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
-    let mut r = rand::rngs::OsRng;
-    let distribution = Normal::new(mean, sd)?;
-    let vals: Vec<f64> = distribution.sample_iter(&mut r).take(n).collect();
+use rand::distributions::Distribution as _;
+use rand::rngs::StdRng;
+use rand::{ Rng, RngCore, SeedableRng };
+use rayon::prelude::*;
+use statrs::distribution::{ Exp, Gamma, LogNormal, Normal, Poisson, Uniform };
+use plotly::{ BoxPlot, Histogram, Scatter };
+use plotly::histogram::HistNorm;
+use plotly::common::{ Line, LineShape, Marker, MarkerSymbol, Mode };
+use plotly::{ Layout, Plot };
+use plotly::common::Font;
+use plotly::layout::Axis;
+use serde::Deserialize;
+
+use super::format::{ plotly_separators, NumberFormat };
+use super::i18n::Language;
+use super::stats::{ density, outliers, quantile, Bandwidth, OutlierMethod };
+
+#[derive(Clone, PartialEq)]
+pub struct NormalParams {
+    pub mean: f64,
+    pub sd: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct UniformParams {
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ExponentialParams {
+    pub rate: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct GammaParams {
+    pub shape: f64,
+    pub rate: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PoissonParams {
+    pub lambda: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LogNormalParams {
+    pub location: f64,
+    pub scale: f64,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Distribution {
+    Normal(NormalParams),
+    Uniform(UniformParams),
+    Exponential(ExponentialParams),
+    Gamma(GammaParams),
+    Poisson(PoissonParams),
+    LogNormal(LogNormalParams),
+}
+
+impl Distribution {
+    /// Builds the distribution selected in a panel from the values of its
+    /// two numeric inputs. Families with a single parameter ignore the
+    /// second one. Unknown names fall back to a normal distribution.
+    pub fn from_inputs(name: &str, first: f64, second: f64) -> Self {
+        match name {
+            "uniform" => Distribution::Uniform(UniformParams { min: first, max: second }),
+            "exponential" => Distribution::Exponential(ExponentialParams { rate: first }),
+            "gamma" => Distribution::Gamma(GammaParams { shape: first, rate: second }),
+            "poisson" => Distribution::Poisson(PoissonParams { lambda: first }),
+            "lognormal" => Distribution::LogNormal(LogNormalParams { location: first, scale: second }),
+            _ => Distribution::Normal(NormalParams { mean: first, sd: second }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Distribution::Normal(_) => "Normal",
+            Distribution::Uniform(_) => "Uniform",
+            Distribution::Exponential(_) => "Exponential",
+            Distribution::Gamma(_) => "Gamma",
+            Distribution::Poisson(_) => "Poisson",
+            Distribution::LogNormal(_) => "Log-normal",
+        }
+    }
+
+    /// Catalog keys of the labels of the two numeric inputs for this
+    /// family. The second one is `None` for single-parameter families.
+    pub fn param_labels(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            Distribution::Normal(_) => ("param.mean", Some("param.sd")),
+            Distribution::Uniform(_) => ("param.min", Some("param.max")),
+            Distribution::Exponential(_) => ("param.rate", None),
+            Distribution::Gamma(_) => ("param.shape", Some("param.rate")),
+            Distribution::Poisson(_) => ("param.lambda", None),
+            Distribution::LogNormal(_) => ("param.log_mean", Some("param.log_sd")),
+        }
+    }
+}
+
+/// A generator seeded with `seed`, or from the OS when there's none.
+pub fn make_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+pub fn get_dist<R: Rng>(
+    n: usize,
+    dist: &Distribution,
+    r: &mut R
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let vals: Vec<f64> = match dist {
+        Distribution::Normal(p) => Normal::new(p.mean, p.sd)?.sample_iter(&mut *r).take(n).collect(),
+        Distribution::Uniform(p) => Uniform::new(p.min, p.max)?.sample_iter(&mut *r).take(n).collect(),
+        Distribution::Exponential(p) => Exp::new(p.rate)?.sample_iter(&mut *r).take(n).collect(),
+        Distribution::Gamma(p) => Gamma::new(p.shape, p.rate)?.sample_iter(&mut *r).take(n).collect(),
+        Distribution::Poisson(p) => Poisson::new(p.lambda)?.sample_iter(&mut *r).take(n).collect(),
+        Distribution::LogNormal(p) => LogNormal::new(p.location, p.scale)?.sample_iter(&mut *r).take(n).collect(),
+    };
     Ok(vals)
 }
 
-pub fn get_plot(dist1: &[f64], dist2: &[f64]) -> String {
-    let plot_string: String;
-    {
-        let hist1 = Histogram::new(dist1.to_owned());
-        let hist2 = Histogram::new(dist2.to_owned());
-        let mut my_plot = Plot::new();
-        my_plot.add_trace(hist1);
-        my_plot.add_trace(hist2);
-        plot_string = my_plot.to_inline_html(None);
+/// Observations drawn by each task of a parallel sample.
+const CHUNK: usize = 65_536;
+
+/// Draws `n` observations seeded with `seed`, or from the OS when there's
+/// none. Samples of at least `parallel_threshold` observations are drawn
+/// in chunks on all cores, each from its own generator seeded from `seed`
+/// and the chunk's index, so a seed still always gives the same sample.
+pub fn sample(
+    n: usize,
+    dist: &Distribution,
+    seed: Option<u64>,
+    parallel_threshold: usize
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let mut rng = make_rng(seed);
+    if n < parallel_threshold {
+        return get_dist(n, dist, &mut rng);
+    }
+    // Fails on invalid parameters before any chunk gets drawn.
+    get_dist(0, dist, &mut rng)?;
+    let base = seed.unwrap_or_else(|| rng.next_u64());
+    let chunks: Vec<Vec<f64>> = (0..n.div_ceil(CHUNK))
+        .into_par_iter()
+        .map(|chunk| {
+            let seed = base.wrapping_mul(0x9E37_79B9_7F4A_7C15).wrapping_add(chunk as u64);
+            let len = CHUNK.min(n - chunk * CHUNK);
+            get_dist(len, dist, &mut StdRng::seed_from_u64(seed)).unwrap_or_default()
+        })
+        .collect();
+    Ok(chunks.concat())
+}
+
+/// How `plot1` shows the samples.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlotType {
+    Histogram,
+    Density,
+    Boxplot,
+}
+
+impl PlotType {
+    /// Unknown names fall back to a histogram.
+    pub fn from_input(name: &str) -> Self {
+        match name {
+            "density" => PlotType::Density,
+            "boxplot" => PlotType::Boxplot,
+            _ => PlotType::Histogram,
+        }
+    }
+}
+
+/// The percentiles of each sample to plot, as sent by the
+/// `percentile_range` input.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub struct PercentileRange {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Default for PercentileRange {
+    fn default() -> Self {
+        PercentileRange { lower: 0.0, upper: 100.0 }
+    }
+}
+
+impl PercentileRange {
+    pub fn is_valid(&self) -> bool {
+        0.0 <= self.lower && self.lower <= self.upper && self.upper <= 100.0
+    }
+
+    /// The values between the range's percentiles of `values`, in their
+    /// original order.
+    pub fn trim(&self, values: &[f64]) -> Vec<f64> {
+        if *self == PercentileRange::default() {
+            return values.to_vec();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        match (quantile(&sorted, self.lower / 100.0), quantile(&sorted, self.upper / 100.0)) {
+            (Some(lower), Some(upper)) => {
+                values.iter().copied().filter(|x| (lower..=upper).contains(x)).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// A selection dragged over a plot, as sent by the `plot1_brush` input.
+/// Plotly leaves out the axes a selection doesn't span.
+#[derive(Deserialize)]
+pub struct Brush {
+    pub x: Option<[f64; 2]>,
+    pub y: Option<[f64; 2]>,
+}
+
+impl Brush {
+    /// The selected range of sample values, which are on the y axis of box
+    /// plots and on the x axis of the other plots.
+    pub fn value_range(&self, plot_type: PlotType) -> Option<(f64, f64)> {
+        let [a, b] = match plot_type {
+            PlotType::Boxplot => self.y?,
+            _ => self.x?,
+        };
+        Some((a.min(b), a.max(b)))
+    }
+}
+
+/// The values within `[min, max]`, in their original order.
+pub fn crop(values: &[f64], (min, max): (f64, f64)) -> Vec<f64> {
+    values.iter().copied().filter(|x| (min..=max).contains(x)).collect()
+}
+
+/// Plotly's default colors, in the order it gives them to series.
+pub const PALETTE: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+    "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+/// Color, line width and opacity of a series. Series without a color get
+/// the next one of Plotly's palette.
+#[derive(Clone, PartialEq)]
+pub struct SeriesStyle {
+    pub color: Option<String>,
+    pub line_width: f64,
+    pub opacity: f64,
+}
+
+impl Default for SeriesStyle {
+    fn default() -> Self {
+        SeriesStyle { color: None, line_width: 2.0, opacity: 1.0 }
+    }
+}
+
+impl SeriesStyle {
+    fn line(&self) -> Line {
+        let line = Line::new().width(self.line_width);
+        match &self.color {
+            Some(color) => line.color(color.as_str()),
+            None => line,
+        }
+    }
+
+    /// Bars and boxes are filled with the series' color and outlined with
+    /// its line.
+    fn marker(&self) -> Marker {
+        let marker = Marker::new().line(self.line());
+        match &self.color {
+            Some(color) => marker.color(color.as_str()),
+            None => marker,
+        }
+    }
+}
+
+/// The style of series `index`, or the default one if `styles` is shorter.
+fn style_of(styles: &[SeriesStyle], index: usize) -> SeriesStyle {
+    styles.get(index).cloned().unwrap_or_default()
+}
+
+/// Overlays a histogram per `(values, legend entry)` pair. With a `kde`
+/// bandwidth, the bars show densities rather than counts, under the kernel
+/// density estimate of each series.
+pub fn get_histogram(series: &[(&[f64], &str)], styles: &[SeriesStyle], kde: Option<Bandwidth>) -> Plot {
+    let mut my_plot = Plot::new();
+    for (i, (values, name)) in series.iter().enumerate() {
+        let style = style_of(styles, i);
+        let histogram = Histogram::new(values.to_vec())
+            .name(name)
+            .marker(style.marker())
+            .opacity(style.opacity);
+        my_plot.add_trace(match kde {
+            Some(_) => histogram.hist_norm(HistNorm::ProbabilityDensity),
+            None => histogram,
+        });
+    }
+    if let Some(bandwidth) = kde {
+        for (i, (values, name)) in series.iter().enumerate() {
+            my_plot.add_trace(density_trace(values, &format!("{} (KDE)", name), &style_of(styles, i), bandwidth));
+        }
+    }
+    my_plot
+}
+
+/// Overlays a kernel density estimate per series.
+pub fn get_density(series: &[(&[f64], &str)], styles: &[SeriesStyle], bandwidth: Bandwidth) -> Plot {
+    let mut my_plot = Plot::new();
+    for (i, (values, name)) in series.iter().enumerate() {
+        my_plot.add_trace(density_trace(values, name, &style_of(styles, i), bandwidth));
+    }
+    my_plot
+}
+
+fn density_trace(values: &[f64], name: &str, style: &SeriesStyle, bandwidth: Bandwidth) -> Box<Scatter<f64, f64>> {
+    let (x, y): (Vec<f64>, Vec<f64>) = density(values, 200, bandwidth).into_iter().unzip();
+    Scatter::new(x, y)
+        .mode(Mode::Lines)
+        .name(name)
+        .line(style.line())
+        .opacity(style.opacity)
+}
+
+/// A box per series, side by side.
+pub fn get_boxplot(series: &[(&[f64], &str)], styles: &[SeriesStyle]) -> Plot {
+    let mut my_plot = Plot::new();
+    for (i, (values, name)) in series.iter().enumerate() {
+        let style = style_of(styles, i);
+        my_plot.add_trace(
+            BoxPlot::<f64, f64>::new(values.to_vec())
+                .name(name)
+                .marker(style.marker())
+                .line(style.line())
+                .opacity(style.opacity)
+        );
+    }
+    my_plot
+}
+
+/// Marks the outliers of each series with crosses: along the bottom of
+/// histograms and density plots, over the series' box in box plots.
+fn add_outliers(
+    plot: &mut Plot,
+    series: &[(&[f64], &str)],
+    plot_type: PlotType,
+    styles: &[SeriesStyle],
+    method: OutlierMethod
+) {
+    for (i, (values, name)) in series.iter().enumerate() {
+        let found = outliers(values, method);
+        if found.is_empty() {
+            continue;
+        }
+        let marker = Marker::new().symbol(MarkerSymbol::X).size(9);
+        let marker = match style_of(styles, i).color {
+            Some(color) => marker.color(color.as_str()),
+            None => marker,
+        };
+        let legend = format!("{} outliers", name);
+        match plot_type {
+            PlotType::Boxplot => {
+                let x = vec![name.to_string(); found.len()];
+                plot.add_trace(Scatter::new(x, found).mode(Mode::Markers).name(&legend).marker(marker));
+            }
+            PlotType::Histogram | PlotType::Density => {
+                let y = vec![0.0; found.len()];
+                plot.add_trace(Scatter::new(found, y).mode(Mode::Markers).name(&legend).marker(marker));
+            }
+        }
+    }
+}
+
+/// Size of a plot in pixels.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PlotSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PlotSize {
+    /// Fills `width`, with a 5:3 aspect ratio kept between 300 and 600
+    /// pixels high.
+    pub fn for_width(width: usize) -> Self {
+        PlotSize { width, height: (width * 3 / 5).clamp(300, 600) }
+    }
+}
+
+/// Colors of the plot's background, text and grid, matching the page's
+/// theme.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum PlotTheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl PlotTheme {
+    pub fn from_dark_mode(dark: bool) -> Self {
+        if dark { PlotTheme::Dark } else { PlotTheme::Light }
+    }
+
+    /// An axis in the theme's colors.
+    fn axis(&self) -> Axis {
+        match self {
+            PlotTheme::Light => Axis::new(),
+            PlotTheme::Dark => Axis::new().grid_color("#444444").zero_line_color("#666666"),
+        }
+    }
+
+    /// Light plots keep Plotly's default colors.
+    fn apply(&self, layout: Layout) -> Layout {
+        match self {
+            PlotTheme::Light => layout,
+            PlotTheme::Dark => layout
+                .paper_background_color("#222222")
+                .plot_background_color("#222222")
+                .font(Font::new().color("#dddddd"))
+                .x_axis(self.axis())
+                .y_axis(self.axis()),
+        }
+    }
+}
+
+/// How a plot looks, independently of its data. Without a size the plot
+/// gets Plotly's default one.
+#[derive(Clone, PartialEq, Default)]
+pub struct PlotOptions {
+    pub size: Option<PlotSize>,
+    pub theme: PlotTheme,
+    /// Style of each series, in order.
+    pub styles: Vec<SeriesStyle>,
+    /// Language whose separators the axis labels have.
+    pub language: Language,
+    /// Format of the labels of the axis showing the values, Plotly's own
+    /// when `None`.
+    pub axis_format: Option<NumberFormat>,
+    /// Bandwidth of the density plot's estimates and of the overlay.
+    pub bandwidth: Bandwidth,
+    /// Whether histograms have the kernel density estimates over them.
+    pub kde_overlay: bool,
+    /// How the outliers marked on the plot are found, if they are.
+    pub outliers: Option<OutlierMethod>,
+}
+
+impl PlotOptions {
+    fn layout(&self) -> Layout {
+        let layout = self.theme.apply(Layout::new()).separators(&plotly_separators(self.language));
+        match self.size {
+            Some(size) => layout.width(size.width).height(size.height),
+            None => layout,
+        }
+    }
+
+    /// The axis showing the sample values.
+    fn value_axis(&self) -> Axis {
+        let axis = self.theme.axis();
+        match self.axis_format {
+            Some(format) => axis.tick_format(&format.d3()),
+            None => axis,
+        }
+    }
+}
+
+/// Dragging over the plot selects a range, which www/app.js reports as the
+/// `plot1_brush` input, instead of zooming on the client.
+fn build_plot(series: &[(&[f64], &str)], plot_type: PlotType, options: &PlotOptions) -> Plot {
+    let mut plot = match plot_type {
+        PlotType::Histogram => {
+            get_histogram(series, &options.styles, options.kde_overlay.then_some(options.bandwidth))
+        }
+        PlotType::Density => get_density(series, &options.styles, options.bandwidth),
+        PlotType::Boxplot => get_boxplot(series, &options.styles),
+    };
+    if let Some(method) = options.outliers {
+        add_outliers(&mut plot, series, plot_type, &options.styles, method);
+    }
+    let layout = options.layout().drag_mode("select");
+    plot.set_layout(match plot_type {
+        PlotType::Boxplot => layout.y_axis(options.value_axis()),
+        PlotType::Histogram | PlotType::Density => layout.x_axis(options.value_axis()),
+    });
+    plot
+}
+
+pub fn get_plot(series: &[(&[f64], &str)], plot_type: PlotType, options: &PlotOptions) -> String {
+    build_plot(series, plot_type, options).to_inline_html(None)
+}
+
+/// The same plot as `get_plot`, as a Plotly figure spec for `render_plotly`.
+pub fn get_plot_json(series: &[(&[f64], &str)], plot_type: PlotType, options: &PlotOptions) -> String {
+    build_plot(series, plot_type, options).to_json()
+}
+
+/// Quantiles of `dist2` against those of `dist1`, with the `y = x` line
+/// samples of the same distribution fall along. Each `(values, label)`
+/// sample is compared at up to 200 evenly spaced probabilities.
+pub fn get_qq_plot(dist1: (&[f64], &str), dist2: (&[f64], &str), options: &PlotOptions) -> String {
+    let mut plot = Plot::new();
+    let sorted = |values: &[f64]| {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted
+    };
+    let (a, b) = (sorted(dist1.0), sorted(dist2.0));
+    let points = a.len().min(b.len()).min(200);
+    let (x, y): (Vec<f64>, Vec<f64>) = (0..points)
+        .filter_map(|i| {
+            let p = (i as f64 + 0.5) / points as f64;
+            Some((quantile(&a, p)?, quantile(&b, p)?))
+        })
+        .unzip();
+    let range = x.iter().chain(&y).fold(None, |range: Option<(f64, f64)>, v| {
+        Some(range.map_or((*v, *v), |(min, max)| (min.min(*v), max.max(*v))))
+    });
+    plot.add_trace(Scatter::new(x, y).mode(Mode::Markers).name("quantiles"));
+    if let Some((min, max)) = range {
+        plot.add_trace(
+            Scatter::new(vec![min, max], vec![min, max])
+                .mode(Mode::Lines)
+                .name("y = x")
+                .line(Line::new().color("#888888").width(1.0))
+        );
     }
-    plot_string
+    let axis = |title: &str| options.value_axis().title(title.into());
+    plot.set_layout(options.layout().x_axis(axis(dist1.1)).y_axis(axis(dist2.1)));
+    plot.to_inline_html(None)
 }
 
+/// Empirical distribution function of each series, as steps.
+pub fn get_ecdf_plot(series: &[(&[f64], &str)], options: &PlotOptions) -> String {
+    let mut plot = Plot::new();
+    for (i, (values, name)) in series.iter().enumerate() {
+        let mut x = values.to_vec();
+        x.sort_by(|a, b| a.total_cmp(b));
+        let n = x.len() as f64;
+        let y: Vec<f64> = (1..=x.len()).map(|rank| rank as f64 / n).collect();
+        let style = style_of(&options.styles, i);
+        plot.add_trace(
+            Scatter::new(x, y)
+                .mode(Mode::Lines)
+                .name(name)
+                .line(style.line().shape(LineShape::Hv))
+                .opacity(style.opacity)
+        );
+    }
+    plot.set_layout(options.layout().x_axis(options.value_axis()));
+    plot.to_inline_html(None)
+}
+
+/// Line chart of `(x, y)` points.
+pub fn get_line_plot(points: &[(u64, f64)], name: &str, options: &PlotOptions) -> String {
+    let mut plot = Plot::new();
+    let (x, y): (Vec<u64>, Vec<f64>) = points.iter().copied().unzip();
+    plot.add_trace(Scatter::new(x, y).mode(Mode::Lines).name(name));
+    plot.set_layout(options.layout().y_axis(options.value_axis()));
+    plot.to_inline_html(None)
+}
@@ -0,0 +1,339 @@
+//! Plots drawn on the server for download, as PNG or SVG. The page's plot
+//! is drawn by Plotly in the browser, so downloads are redrawn with
+//! plotters from the same samples, plot type and style at the size the user
+//! asked for. Text needs a TrueType font, read from `plot_font` the first
+//! time a plot is drawn.
+
+use std::error::Error;
+use std::io::Cursor;
+use std::sync::OnceLock;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::{ register_font, FontStyle };
+use serde::Deserialize;
+
+use super::format::NumberFormat;
+use super::i18n::Language;
+use super::plot::{ PlotTheme, PlotType, SeriesStyle, PALETTE };
+use super::stats::{ density, quantile, Bandwidth };
+
+const HISTOGRAM_BINS: usize = 30;
+
+/// Screen resolution, at which a plot has the size asked for.
+const BASE_DPI: f64 = 96.0;
+
+/// Largest PNG drawn, in pixels.
+const MAX_PIXELS: f64 = 40_000_000.0;
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Size of a downloaded plot: `width` by `height` pixels at 96 DPI, scaled
+/// up with everything in it for higher resolutions. SVGs ignore the DPI.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+}
+
+impl ImageSize {
+    pub const WIDTH: (u32, u32) = (100, 4000);
+    pub const HEIGHT: (u32, u32) = (100, 4000);
+    pub const DPI: (u32, u32) = (72, 600);
+
+    pub fn validate(&self) -> Result<(), String> {
+        let within = |value: u32, (min, max): (u32, u32), name: &str| {
+            if (min..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!("{} must be between {} and {}", name, min, max))
+            }
+        };
+        within(self.width, Self::WIDTH, "Width")?;
+        within(self.height, Self::HEIGHT, "Height")?;
+        within(self.dpi, Self::DPI, "DPI")?;
+        let (width, height) = self.pixels();
+        if width as f64 * height as f64 > MAX_PIXELS {
+            return Err(String::from("The plot would be too large, lower its size or DPI"));
+        }
+        Ok(())
+    }
+
+    fn scale(&self) -> f64 {
+        self.dpi as f64 / BASE_DPI
+    }
+
+    fn pixels(&self) -> (u32, u32) {
+        let scale = self.scale();
+        ((self.width as f64 * scale).round() as u32, (self.height as f64 * scale).round() as u32)
+    }
+}
+
+/// What `plot1` shows: the samples as plotted, with their legend entries,
+/// and how they look.
+pub struct Figure {
+    pub series: Vec<(String, Vec<f64>)>,
+    pub plot_type: PlotType,
+    pub styles: Vec<SeriesStyle>,
+    pub theme: PlotTheme,
+    pub language: Language,
+    /// Format of the value axis' labels, plotters' own when `None`.
+    pub axis_format: Option<NumberFormat>,
+    pub bandwidth: Bandwidth,
+    /// Whether histograms have the kernel density estimates over them,
+    /// scaled to the counts.
+    pub kde_overlay: bool,
+}
+
+/// Registers `path` as the font of every text, once. Later calls return
+/// the first call's result.
+fn load_font(path: &str) -> Result<(), String> {
+    static LOADED: OnceLock<Result<(), String>> = OnceLock::new();
+    LOADED
+        .get_or_init(|| {
+            let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            register_font("sans-serif", FontStyle::Normal, Box::leak(bytes.into_boxed_slice()))
+                .map_err(|_| format!("{}: not a TrueType font", path))
+        })
+        .clone()
+}
+
+/// Draws `figure` as `format` at `size`, with the text in the font at
+/// `font`.
+pub fn render(figure: &Figure, format: ImageFormat, size: ImageSize, font: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    size.validate()?;
+    load_font(font)?;
+    match format {
+        ImageFormat::Png => {
+            let (width, height) = size.pixels();
+            let mut buffer = vec![0; width as usize * height as usize * 3];
+            {
+                let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+                draw(&root, figure, size.scale())?;
+                root.present()?;
+            }
+            let image = image::RgbImage::from_raw(width, height, buffer).ok_or("plot buffer of the wrong size")?;
+            let mut png = Cursor::new(Vec::new());
+            image.write_to(&mut png, image::ImageOutputFormat::Png)?;
+            Ok(png.into_inner())
+        }
+        ImageFormat::Svg => {
+            let mut svg = String::new();
+            {
+                let root = SVGBackend::with_string(&mut svg, (size.width, size.height)).into_drawing_area();
+                draw(&root, figure, 1.0)?;
+                root.present()?;
+            }
+            Ok(svg.into_bytes())
+        }
+    }
+}
+
+/// `#rrggbb`, as the color inputs send it.
+fn parse_color(color: &str) -> Option<RGBColor> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// The style of series `index`, and its color: its own, or the next of
+/// Plotly's as on the page.
+fn color_of(styles: &[SeriesStyle], index: usize) -> (RGBColor, SeriesStyle) {
+    let style = styles.get(index).cloned().unwrap_or_default();
+    let color = style.color.as_deref().unwrap_or(PALETTE[index % PALETTE.len()]);
+    (parse_color(color).unwrap_or(BLACK), style)
+}
+
+/// Smallest and largest value of every series, widened when they're equal
+/// so the axis has a length.
+fn value_range(series: &[(String, Vec<f64>)]) -> (f64, f64) {
+    let values = series.iter().flat_map(|(_, values)| values.iter().copied());
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| (min.min(x), max.max(x)));
+    if !min.is_finite() {
+        return (0.0, 1.0);
+    }
+    if min == max {
+        return (min - 0.5, max + 0.5);
+    }
+    (min, max)
+}
+
+fn draw<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, figure: &Figure, scale: f64) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (background, foreground) = match figure.theme {
+        PlotTheme::Light => (WHITE, RGBColor(0x44, 0x44, 0x44)),
+        PlotTheme::Dark => (RGBColor(0x22, 0x22, 0x22), RGBColor(0xdd, 0xdd, 0xdd)),
+    };
+    root.fill(&background)?;
+    let px = |size: f64| (size * scale).round() as u32;
+    let font = ("sans-serif", 12.0 * scale).into_font().color(&foreground);
+    let mut chart = ChartBuilder::on(root);
+    chart
+        .margin(px(10.0))
+        .x_label_area_size(px(30.0))
+        .y_label_area_size(px(50.0));
+    let (min, max) = value_range(&figure.series);
+    let legend_box = px(6.0) as i32;
+    let value_label = |value: &f64| {
+        figure.axis_format.map_or_else(|| value.to_string(), |format| format.format(*value, figure.language))
+    };
+    match figure.plot_type {
+        PlotType::Histogram => {
+            let bin = (max - min) / HISTOGRAM_BINS as f64;
+            let counts: Vec<Vec<f64>> = figure.series
+                .iter()
+                .map(|(_, values)| {
+                    let mut counts = vec![0.0; HISTOGRAM_BINS];
+                    for x in values {
+                        counts[(((x - min) / bin) as usize).min(HISTOGRAM_BINS - 1)] += 1.0;
+                    }
+                    counts
+                })
+                .collect();
+            let top = counts.iter().flatten().fold(1.0_f64, |top, count| top.max(*count));
+            let mut chart = chart.build_cartesian_2d(min..max, 0.0..top * 1.05)?;
+            let mut mesh = chart.configure_mesh();
+            mesh.disable_mesh().label_style(font.clone()).axis_style(foreground);
+            if figure.axis_format.is_some() {
+                mesh.x_label_formatter(&value_label);
+            }
+            mesh.draw()?;
+            for (index, ((name, _), counts)) in figure.series.iter().zip(&counts).enumerate() {
+                let (color, style) = color_of(&figure.styles, index);
+                let bars = counts.iter().enumerate().map(|(i, count)| {
+                    let from = min + i as f64 * bin;
+                    Rectangle::new([(from, 0.0), (from + bin, *count)], color.mix(style.opacity * 0.75).filled())
+                });
+                chart
+                    .draw_series(bars)?
+                    .label(name.as_str())
+                    .legend(move |(x, y)| {
+                        Rectangle::new([(x, y - legend_box), (x + 2 * legend_box, y + legend_box)], color.filled())
+                    });
+            }
+            if figure.kde_overlay {
+                for (index, (_, values)) in figure.series.iter().enumerate() {
+                    let (color, style) = color_of(&figure.styles, index);
+                    let scale = values.len() as f64 * bin;
+                    let curve = density(values, 200, figure.bandwidth)
+                        .into_iter()
+                        .filter(|(x, _)| (min..=max).contains(x))
+                        .map(|(x, y)| (x, (y * scale).min(top * 1.05)));
+                    chart.draw_series(LineSeries::new(curve, color.mix(style.opacity).stroke_width(px(style.line_width))))?;
+                }
+            }
+            chart
+                .configure_series_labels()
+                .label_font(font)
+                .background_style(background.mix(0.8))
+                .border_style(foreground)
+                .draw()?;
+        }
+        PlotType::Density => {
+            let curves: Vec<Vec<(f64, f64)>> = figure.series
+                .iter()
+                .map(|(_, values)| density(values, 200, figure.bandwidth))
+                .collect();
+            let points = curves.iter().flatten();
+            let (left, right, top) = points.fold((min, max, 0.0_f64), |(left, right, top), (x, y)| {
+                (left.min(*x), right.max(*x), top.max(*y))
+            });
+            let mut chart = chart.build_cartesian_2d(left..right, 0.0..top.max(f64::EPSILON) * 1.05)?;
+            let mut mesh = chart.configure_mesh();
+            mesh.disable_mesh().label_style(font.clone()).axis_style(foreground);
+            if figure.axis_format.is_some() {
+                mesh.x_label_formatter(&value_label);
+            }
+            mesh.draw()?;
+            for (index, ((name, _), curve)) in figure.series.iter().zip(curves).enumerate() {
+                let (color, style) = color_of(&figure.styles, index);
+                let stroke = color.mix(style.opacity).stroke_width(px(style.line_width));
+                chart
+                    .draw_series(LineSeries::new(curve, stroke))?
+                    .label(name.as_str())
+                    .legend(move |(x, y)| {
+                        PathElement::new([(x, y), (x + 2 * legend_box, y)], color.stroke_width(2))
+                    });
+            }
+            chart
+                .configure_series_labels()
+                .label_font(font)
+                .background_style(background.mix(0.8))
+                .border_style(foreground)
+                .draw()?;
+        }
+        PlotType::Boxplot => {
+            let count = figure.series.len().max(1);
+            let names: Vec<&str> = figure.series.iter().map(|(name, _)| name.as_str()).collect();
+            let mut chart = chart.build_cartesian_2d(-0.5..count as f64 - 0.5, min..max)?;
+            let name_label = |x: &f64| {
+                let index = x.round();
+                match names.get(index as usize) {
+                    Some(name) if (x - index).abs() < 1e-9 && index >= 0.0 => name.to_string(),
+                    _ => String::new(),
+                }
+            };
+            let mut mesh = chart.configure_mesh();
+            mesh
+                .disable_mesh()
+                .x_labels(count)
+                .x_label_formatter(&name_label)
+                .label_style(font)
+                .axis_style(foreground);
+            if figure.axis_format.is_some() {
+                mesh.y_label_formatter(&value_label);
+            }
+            mesh.draw()?;
+            for (index, (_, values)) in figure.series.iter().enumerate() {
+                let mut sorted = values.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let (q1, median, q3) = match (quantile(&sorted, 0.25), quantile(&sorted, 0.5), quantile(&sorted, 0.75)) {
+                    (Some(q1), Some(median), Some(q3)) => (q1, median, q3),
+                    _ => continue,
+                };
+                // Whiskers reach the furthest values within 1.5 IQR of the box.
+                let fence = 1.5 * (q3 - q1);
+                let low = sorted.iter().copied().find(|x| *x >= q1 - fence).unwrap_or(q1);
+                let high = sorted.iter().rev().copied().find(|x| *x <= q3 + fence).unwrap_or(q3);
+                let (color, style) = color_of(&figure.styles, index);
+                let stroke = color.stroke_width(px(style.line_width));
+                let x = index as f64;
+                chart.draw_series([
+                    Rectangle::new([(x - 0.3, q1), (x + 0.3, q3)], color.mix(style.opacity * 0.5).filled()),
+                    Rectangle::new([(x - 0.3, q1), (x + 0.3, q3)], stroke),
+                ])?;
+                chart.draw_series([
+                    PathElement::new([(x - 0.3, median), (x + 0.3, median)], stroke),
+                    PathElement::new([(x, q3), (x, high)], stroke),
+                    PathElement::new([(x, q1), (x, low)], stroke),
+                ])?;
+                let outliers = sorted.iter().filter(|value| **value < low || **value > high);
+                chart.draw_series(outliers.map(|value| Circle::new((x, *value), px(2.0), stroke)))?;
+            }
+        }
+    }
+    Ok(())
+}
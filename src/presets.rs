@@ -0,0 +1,81 @@
+//! Named presets: the settings of every panel and the seed, saved under a
+//! name in the storage and applied by picking the name in the `preset`
+//! select.
+
+use serde_json::{ json, Map, Value };
+use tracing::warn;
+
+use super::bookmark;
+use super::dist_module;
+use super::i18n::t;
+use super::messages::{ Session, update_text_input };
+use super::updates::{ update_select, SelectUpdate };
+use super::notifications::NotificationMode;
+use super::server::CustomServer;
+use super::stats::escape_html;
+
+pub const MAX_NAME_LENGTH: usize = 50;
+
+/// Fills the `preset` select with the names of the user's presets, keeping
+/// `selected` selected.
+pub fn render_choices(shiny: &CustomServer, session: &mut dyn Session, selected: &str) {
+    let names = shiny.storage.presets(shiny.user.as_deref()).unwrap_or_else(|e| {
+        warn!(error = %e, "presets could not be loaded");
+        Vec::new()
+    });
+    let choices = std::iter::once((String::new(), String::from("Choose a preset")))
+        .chain(names.into_iter().map(|name| (name.clone(), name)))
+        .collect();
+    update_select(session, "preset", SelectUpdate {
+        choices: Some(choices),
+        selected: Some(selected.to_string()),
+        ..Default::default()
+    });
+}
+
+/// Saves the current settings under the name in `preset_name`.
+pub fn save(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let name = shiny.input.get_string("preset_name").unwrap_or_default();
+    if shiny.validators.validate(session, "preset_name", &json!(name)).is_err() {
+        return;
+    }
+    let name = name.trim();
+    let mut params = Map::new();
+    for panel in &shiny.panels {
+        if let Value::Object(panel_params) = panel.parameters(&shiny.input) {
+            params.extend(panel_params);
+        }
+    }
+    if let Err(e) = shiny.storage.save_preset(shiny.user.as_deref(), name, &Value::Object(params)) {
+        warn!(error = %e, "preset could not be saved");
+        return;
+    }
+    render_choices(shiny, session, name);
+    update_text_input(session, "preset_name", json!({ "value": "" }));
+    shiny.notifications.show(session, NotificationMode::Immediate, json!({
+        "html": t!(shiny, "preset.saved", name = escape_html(name)),
+        "action": "",
+        "deps": [],
+        "closeButton": true,
+        "type": "message"
+    }));
+}
+
+/// Applies the preset picked in `preset`, resampling every panel right away
+/// rather than once the client reports the new values.
+pub fn apply(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let name = shiny.input.get_string("preset").unwrap_or_default();
+    if name.is_empty() {
+        return;
+    }
+    match shiny.storage.preset(shiny.user.as_deref(), &name) {
+        Ok(Some(params)) => {
+            bookmark::restore(shiny, session, &params);
+            for index in 0..shiny.panels.len() {
+                dist_module::resample(shiny, session, index);
+            }
+        }
+        Ok(None) => warn!(preset = %name, "no such preset"),
+        Err(e) => warn!(error = %e, "preset could not be loaded"),
+    }
+}
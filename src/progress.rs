@@ -0,0 +1,279 @@
+//! Progress shown while the session waits on long work. `Progress` is
+//! Shiny's progress notification. `with_progress` runs work split in steps
+//! off the actor, on the shared worker pool when the session has one, with
+//! a bar in an output of the page and a Cancel button the work checks
+//! between steps. While the work waits for a worker, a notification tells
+//! where it is in the queue:
+//!
+//! ```ignore
+//! with_progress(shiny, session, "fit_progress", models.len(), move |steps| {
+//!     let mut fits = Vec::new();
+//!     for model in models {
+//!         fits.push(model.fit());
+//!         steps.advance(format!("{} of {} models", fits.len(), steps.total()))?;
+//!     }
+//!     Ok(fits)
+//! }, |_shiny, session, fits| match fits {
+//!     Ok(fits) => render_fits(session, &fits),
+//!     Err(Cancelled) => render_text(session, "fits", "Cancelled"),
+//! });
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use actix::{ fut, ActorFutureExt, Addr, AsyncContext, Handler, Message };
+use serde_json::json;
+use shiny_rs::ui::*;
+use tokio::sync::oneshot;
+use tracing::{ error, warn };
+
+use super::i18n::t;
+use super::messages::{
+    OutputErrorKind, Session, remove_notification, render_error, render_ui, send_custom_message
+};
+use super::metrics::MeteredSession;
+use super::notifications::NotificationMode;
+use super::server::{ CustomServer, CustomSession };
+use super::workers::{ QueueFull, QueuePosition, Submit };
+
+/// A progress notification, shown with Shiny's progress bar.
+pub struct Progress {
+    id: String,
+}
+
+impl Progress {
+    pub fn open(session: &mut dyn Session, message: &str) -> Self {
+        let progress = Progress { id: generate_id() };
+        progress.send(session, "open", json!({}));
+        progress.send(session, "update", json!({ "message": message, "value": 0.0 }));
+        progress
+    }
+
+    /// Sets the bar to `value`, between 0 and 1.
+    pub fn update(&self, session: &mut dyn Session, value: f64, detail: &str) {
+        self.send(session, "update", json!({ "value": value, "detail": detail }));
+    }
+
+    pub fn close(self, session: &mut dyn Session) {
+        self.send(session, "close", json!({}));
+    }
+
+    fn send(&self, session: &mut dyn Session, kind: &str, mut message: serde_json::Value) {
+        message["id"] = json!(self.id);
+        message["style"] = json!("notification");
+        session.send(json!({
+            "progress": {
+                "type": kind,
+                "message": message
+            }
+        }));
+    }
+}
+
+/// Returned by `Steps::advance` once the user cancelled the work.
+#[derive(Debug)]
+pub struct Cancelled;
+
+/// What the work of `with_progress` reports its steps to.
+pub struct Steps {
+    output: String,
+    total: usize,
+    done: usize,
+    cancel: Arc<AtomicBool>,
+    addr: Addr<CustomServer>,
+}
+
+impl Steps {
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Marks one more step done, with `detail` below the bar. Fails once
+    /// the user clicked Cancel, so the work stops at its next `?`.
+    pub fn advance(&mut self, detail: impl Into<String>) -> Result<(), Cancelled> {
+        self.done = (self.done + 1).min(self.total);
+        self.addr.do_send(ProgressStep {
+            output: self.output.clone(),
+            value: self.done as f64 / self.total.max(1) as f64,
+            detail: detail.into(),
+        });
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Sent by `Steps::advance` to move the bar.
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ProgressStep {
+    output: String,
+    value: f64,
+    detail: String,
+}
+
+impl Handler<ProgressStep> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: ProgressStep, session: &mut Self::Context) {
+        if !self.progress_bars.contains_key(&msg.output) {
+            return;
+        }
+        let mut session = MeteredSession::new(session, &self.metrics);
+        send_custom_message(&mut session, "progress-bar", json!({
+            "id": msg.output,
+            "value": msg.value,
+            "detail": msg.detail
+        }));
+    }
+}
+
+fn bar(output: &str, cancel: &str) -> String {
+    format!(
+        r#"<div class="progress"><div class="progress-bar" role="progressbar" style="width: 0%"></div></div>
+<small class="progress-detail"></small>
+<button id="{}_cancel" type="button" class="btn btn-default btn-sm action-button">{}</button>"#,
+        output,
+        cancel
+    )
+}
+
+/// Id of the notification telling where the work behind `output` waits.
+fn queue_notification(output: &str) -> String {
+    format!("{}_queue", output)
+}
+
+impl Handler<QueuePosition> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: QueuePosition, session: &mut Self::Context) {
+        if !self.progress_bars.contains_key(&msg.job) {
+            return;
+        }
+        let metrics = self.metrics.clone();
+        let mut session = MeteredSession::new(session, &metrics);
+        show_queue_position(self, &mut session, &msg.job, msg.position);
+    }
+}
+
+/// Shows the position of the work behind `output` in the worker pool's
+/// queue, or removes the notification once it runs.
+fn show_queue_position(shiny: &mut CustomServer, session: &mut dyn Session, output: &str, position: usize) {
+    let id = queue_notification(output);
+    if position == 0 {
+        remove_notification(session, &id);
+        return;
+    }
+    shiny.notifications.show(session, NotificationMode::Immediate, json!({
+        "html": t!(shiny, "workers.queued", position = position),
+        "action": "",
+        "deps": [],
+        "closeButton": false,
+        "id": id,
+        "type": "message"
+    }));
+}
+
+/// Waits for the result of the work behind `output`, then removes its bar
+/// and hands the result to `done`.
+fn finish<R, D>(session: &mut CustomSession, output: String, result: oneshot::Receiver<Result<R, Cancelled>>, done: D)
+where
+    R: Send + 'static,
+    D: FnOnce(&mut CustomServer, &mut CustomSession, Result<R, Cancelled>) + 'static,
+{
+    session.spawn(fut::wrap_future::<_, CustomServer>(result).map(move |result, shiny, session| {
+        shiny.progress_bars.remove(&output);
+        render_ui(session, &output, "");
+        remove_notification(session, &queue_notification(&output));
+        match result {
+            Ok(result) => done(shiny, session, result),
+            Err(e) => {
+                error!(output = %output, error = %e, "work behind a progress bar failed");
+                render_error(session, &output, &t!(shiny, "progress.failed"), OutputErrorKind::Error);
+            }
+        }
+    }));
+}
+
+/// Runs `work` off the actor, so the session keeps answering meanwhile,
+/// with a bar of `steps` steps and a Cancel button rendered into `output`.
+/// The work goes to the session's worker pool, or to the blocking thread
+/// pool without one; a pool with a full queue refuses it. Once the work
+/// returns, the bar is removed and `done` gets its result. Does nothing
+/// while `output` shows a bar already.
+pub fn with_progress<R, W, D>(
+    shiny: &mut CustomServer,
+    session: &mut CustomSession,
+    output: &str,
+    steps: usize,
+    work: W,
+    done: D
+)
+where
+    R: Send + 'static,
+    W: FnOnce(&mut Steps) -> Result<R, Cancelled> + Send + 'static,
+    D: FnOnce(&mut CustomServer, &mut CustomSession, Result<R, Cancelled>) + 'static,
+{
+    if shiny.progress_bars.contains_key(output) {
+        return;
+    }
+    let cancel = Arc::new(AtomicBool::new(false));
+    shiny.progress_bars.insert(output.to_string(), cancel.clone());
+    render_ui(session, output, &bar(output, &t!(shiny, "progress.cancel")));
+    let mut steps = Steps { output: output.to_string(), total: steps, done: 0, cancel, addr: session.address() };
+    let (sender, result) = oneshot::channel();
+    // Work cancelled while it waited for a worker doesn't start.
+    let job = move || {
+        let result = match steps.cancel.load(Ordering::Relaxed) {
+            true => Err(Cancelled),
+            false => work(&mut steps),
+        };
+        let _ = sender.send(result);
+    };
+    let output = output.to_string();
+    let pool = match &shiny.worker_pool {
+        Some(pool) => pool.clone(),
+        None => {
+            tokio::task::spawn_blocking(job);
+            finish(session, output, result, done);
+            return;
+        }
+    };
+    let submit = pool.send(Submit { job: output.clone(), work: Box::new(job), positions: session.address().recipient() });
+    session.spawn(fut::wrap_future::<_, CustomServer>(submit).map(move |queued, shiny, session| {
+        match queued {
+            Ok(Ok(position)) => {
+                if position > 0 {
+                    show_queue_position(shiny, session, &output, position);
+                }
+                finish(session, output, result, done);
+            }
+            Ok(Err(QueueFull)) | Err(_) => {
+                warn!(output = %output, "worker pool refused the work");
+                shiny.progress_bars.remove(&output);
+                render_error(session, &output, &t!(shiny, "workers.busy"), OutputErrorKind::Error);
+            }
+        }
+    }));
+}
+
+/// Cancels the work behind a bar if the event is a click on its Cancel
+/// button. Buttons report 0 when bound, which isn't a click.
+pub fn observe_cancel(shiny: &CustomServer) {
+    let output = match shiny.event.strip_suffix("_cancel:shiny.action") {
+        Some(output) => output,
+        None => return,
+    };
+    if shiny.input.get_u64(&shiny.event).unwrap_or(0) == 0 {
+        return;
+    }
+    if let Some(cancel) = shiny.progress_bars.get(output) {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Cancels every running work, for sessions that end.
+pub fn cancel_all(shiny: &CustomServer) {
+    for cancel in shiny.progress_bars.values() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,255 @@
+//! Guards against clients flooding their session: a token bucket limits
+//! the rate of input messages and inputs past a size are dropped. Clients
+//! that keep breaking the limits get disconnected. The value of a rate
+//! limited input stays in the pool, and the input is processed once, with
+//! whatever value it has by then, when the bucket refills. Only recent
+//! violations count, so long sessions breaking a limit now and then stay
+//! connected.
+
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+use super::clock::Clock;
+
+/// Token bucket allowing bursts of up to `capacity` messages and a
+/// sustained rate of `refill_per_sec` messages per second.
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+    clock: Clock,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, refill_per_sec: f64, clock: Clock) -> Self {
+        TokenBucket {
+            capacity,
+            refill_per_sec,
+            tokens: capacity,
+            last_refill: clock.now(),
+            clock,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Whether a token is available, without taking it.
+    pub fn available(&mut self) -> bool {
+        self.refill();
+        self.tokens >= 1.0
+    }
+
+    /// Time until a token is available.
+    pub fn wait(&mut self) -> Duration {
+        self.refill();
+        let missing = (1.0 - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing / self.refill_per_sec.max(f64::EPSILON))
+    }
+
+    /// Takes a token if one is available.
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// How long a rejected message counts towards disconnecting the client.
+pub const VIOLATION_WINDOW: Duration = Duration::from_secs(60);
+
+/// What `MessageGuard::check` decided about a message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    RateLimited,
+    TooLarge,
+}
+
+/// The limits of a session's input messages, and how often the client
+/// broke them.
+pub struct MessageGuard {
+    bucket: TokenBucket,
+    /// Largest input value accepted, in bytes of JSON.
+    max_size: usize,
+    /// Rejected messages within `VIOLATION_WINDOW` after which the client
+    /// is disconnected, 0 to never disconnect it.
+    max_violations: u32,
+    /// When the messages within the window were rejected, oldest first.
+    violations: VecDeque<Instant>,
+    /// Keys of the rate limited inputs not processed since, oldest first.
+    pending: VecDeque<String>,
+    clock: Clock,
+}
+
+impl MessageGuard {
+    pub fn new(burst: f64, per_sec: f64, max_size: usize, max_violations: u32, clock: Clock) -> Self {
+        MessageGuard {
+            bucket: TokenBucket::new(burst, per_sec, clock.clone()),
+            max_size,
+            max_violations,
+            violations: VecDeque::new(),
+            pending: VecDeque::new(),
+            clock,
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Checks a message of `size` bytes, counting it as a violation unless
+    /// it's accepted. Oversized messages take a token too.
+    pub fn check(&mut self, size: usize) -> Verdict {
+        let verdict = match (self.bucket.try_acquire(), size <= self.max_size) {
+            (_, false) => Verdict::TooLarge,
+            (false, true) => Verdict::RateLimited,
+            (true, true) => Verdict::Accepted,
+        };
+        let now = self.clock.now();
+        while self.violations.front().is_some_and(|at| now.duration_since(*at) >= VIOLATION_WINDOW) {
+            self.violations.pop_front();
+        }
+        if verdict != Verdict::Accepted {
+            self.violations.push_back(now);
+        }
+        verdict
+    }
+
+    /// Keeps input `key` to be processed once the bucket refills. Returns
+    /// `true` if nothing was waiting before. An input limited again while
+    /// it waits is processed once.
+    pub fn defer(&mut self, key: &str) -> bool {
+        let first = self.pending.is_empty();
+        if !self.pending.iter().any(|pending| pending == key) {
+            self.pending.push_back(key.to_string());
+        }
+        first
+    }
+
+    /// Stops waiting for input `key`, processed since.
+    pub fn forget(&mut self, key: &str) {
+        self.pending.retain(|pending| pending != key);
+    }
+
+    /// The oldest input waiting, if the bucket has a token for it. The
+    /// token is left for `check` to take.
+    pub fn next_pending(&mut self) -> Option<String> {
+        if self.pending.is_empty() || !self.bucket.available() {
+            return None;
+        }
+        self.pending.pop_front()
+    }
+
+    /// Time until the inputs waiting can be processed.
+    pub fn wait(&mut self) -> Duration {
+        self.bucket.wait()
+    }
+
+    /// Whether the client broke the limits often enough lately to be
+    /// disconnected.
+    pub fn exhausted(&self) -> bool {
+        self.max_violations > 0 && self.violations.len() >= self.max_violations as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use serde_json::json;
+    use super::*;
+    use crate::config::Config;
+    use crate::server::CustomServer;
+    use crate::testing::Driver;
+
+    #[test]
+    fn inputs_limited_again_wait_once() {
+        let clock = Clock::default();
+        clock.freeze();
+        let mut guard = MessageGuard::new(1.0, 1000.0, 100, 0, clock.clone());
+        assert_eq!(guard.check(10), Verdict::Accepted);
+        assert_eq!(guard.check(10), Verdict::RateLimited);
+        assert!(guard.defer("a"));
+        assert!(!guard.defer("b"));
+        assert!(!guard.defer("a"));
+        assert_eq!(guard.next_pending(), None, "no token yet");
+        clock.advance(Duration::from_millis(2));
+        assert_eq!(guard.next_pending().as_deref(), Some("a"));
+        guard.forget("b");
+        assert_eq!(guard.next_pending(), None);
+        assert_eq!(guard.check(1000), Verdict::TooLarge);
+    }
+
+    #[test]
+    fn sparse_violations_never_disconnect() {
+        let clock = Clock::default();
+        clock.freeze();
+        let mut guard = MessageGuard::new(1.0, 1.0, 100, 3, clock.clone());
+        // An hour long session, rate limited twice a minute.
+        for _ in 0..120 {
+            assert_eq!(guard.check(10), Verdict::Accepted);
+            assert_eq!(guard.check(10), Verdict::RateLimited);
+            assert!(!guard.exhausted());
+            clock.advance(Duration::from_secs(30));
+        }
+        for _ in 0..3 {
+            guard.check(10);
+        }
+        assert!(guard.exhausted(), "floods still disconnect");
+    }
+
+    fn driver(burst: f64, per_sec: f64) -> Driver {
+        let config = Config { message_burst: burst, messages_per_sec: per_sec, ..Config::default() };
+        Driver::with_inputs(CustomServer::builder().config(config).build(), &[
+            ("dist1-n:shiny.number", json!(50)),
+            ("dist1-mean:shiny.number", json!(0)),
+            ("dist1-sd:shiny.number", json!(1)),
+        ])
+    }
+
+    fn sample_size(driver: &Driver) -> Option<usize> {
+        driver.shiny.panels[0].sample.value().map(Vec::len)
+    }
+
+    #[test]
+    fn floods_are_dropped_and_the_last_value_processed_later() {
+        let mut driver = driver(5.0, 20.0);
+        for n in 1..=30 {
+            driver.set_number("dist1-n", n as f64);
+        }
+        let rejected = driver.shiny.metrics.messages_rejected.load(Ordering::Relaxed);
+        assert!(rejected >= 20, "{} rejected", rejected);
+        assert!(sample_size(&driver) < Some(30));
+        let warnings = driver.session.notifications()
+            .iter()
+            .filter(|notification| notification["id"] == "rate_limit_warning")
+            .count();
+        assert_eq!(warnings, 1);
+        driver.advance(Duration::from_millis(25));
+        assert!(sample_size(&driver) < Some(30));
+        // A token is back after 50ms.
+        driver.advance(Duration::from_millis(25));
+        assert_eq!(sample_size(&driver), Some(30));
+    }
+
+    #[test]
+    fn normal_rates_pass() {
+        let mut driver = driver(5.0, 20.0);
+        for n in 1..=10 {
+            driver.set_number("dist1-n", n as f64);
+            assert_eq!(sample_size(&driver), Some(n));
+            driver.advance(Duration::from_millis(50));
+        }
+        assert_eq!(driver.shiny.metrics.messages_rejected.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,95 @@
+/// A value derived from a set of dependencies, recomputed only when the
+/// dependencies actually change.
+///
+/// `K` holds the dependencies' values after coercion (e.g. the parsed
+/// parameters of a distribution), so an input event that leaves them
+/// unchanged doesn't mark the value dirty.
+pub struct Reactive<K, T> {
+    key: Option<K>,
+    value: Option<T>,
+    dirty: bool,
+    version: u64,
+}
+
+impl<K: PartialEq, T> Reactive<K, T> {
+    pub fn new() -> Self {
+        Reactive {
+            key: None,
+            value: None,
+            dirty: true,
+            version: 0,
+        }
+    }
+
+    /// Records the current value of the dependencies, marking the value
+    /// dirty if they differ from the previous ones.
+    pub fn track(&mut self, key: K) {
+        if self.key.as_ref() != Some(&key) {
+            self.key = Some(key);
+            self.dirty = true;
+        }
+    }
+
+    /// Forces the next refresh to recompute the value.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Recomputes the value if it is dirty. Returns `Ok(true)` if it was
+    /// recomputed. On error the previous value is kept and the value stays
+    /// dirty.
+    pub fn try_refresh<E>(&mut self, compute: impl FnOnce(&K) -> Result<T, E>) -> Result<bool, E> {
+        let key = match (&self.key, self.dirty) {
+            (Some(key), true) => key,
+            _ => return Ok(false),
+        };
+        self.value = Some(compute(key)?);
+        self.dirty = false;
+        self.version += 1;
+        Ok(true)
+    }
+
+    pub fn refresh(&mut self, compute: impl FnOnce(&K) -> T) -> bool {
+        self.try_refresh(|key| Ok::<T, std::convert::Infallible>(compute(key)))
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// Sets a value computed elsewhere, such as one saved earlier, as the
+    /// value for `key`.
+    pub fn set(&mut self, key: K, value: T) {
+        self.key = Some(key);
+        self.value = Some(value);
+        self.dirty = false;
+        self.version += 1;
+    }
+
+    /// Sets a value that doesn't come from the dependencies, such as
+    /// imported data. The next key tracked recomputes the value.
+    pub fn replace(&mut self, value: T) {
+        self.key = None;
+        self.value = Some(value);
+        self.dirty = false;
+        self.version += 1;
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// The dependencies last tracked.
+    pub fn key(&self) -> Option<&K> {
+        self.key.as_ref()
+    }
+
+    /// Incremented every time the value is recomputed, so other reactive
+    /// values can depend on this one.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl<K: PartialEq, T> Default for Reactive<K, T> {
+    fn default() -> Self {
+        Reactive::new()
+    }
+}
@@ -0,0 +1,214 @@
+//! Reattaching after a dropped websocket. The page reports a token of its
+//! own as the `reconnect_token` input on every connection. When a session
+//! with a token stops, its state is kept for the grace window, and a later
+//! session reporting the same token takes it over: inputs, samples and
+//! inserted plots. If the old session hasn't noticed the drop yet, the new
+//! one detaches it first.
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+use actix::{ fut, ActorFutureExt, Addr, AsyncContext, Message };
+use serde_json::Value;
+use tracing::{ debug, info };
+
+use super::bookmark;
+use super::dist_module::{ self, SampleKey, BASE_PANELS };
+use super::messages::Session;
+use super::server::{ build_plot, render_inserted_count, CustomServer, CustomSession };
+
+/// Tokens are 32 hex digits, anything much longer isn't one.
+const MAX_TOKEN_LENGTH: usize = 64;
+
+/// What a session reconnecting picks up from the one it replaces.
+pub struct SavedSession {
+    inputs: Value,
+    /// Numbers of the panels added to the page, still in it.
+    added_panels: Vec<u64>,
+    samples: Vec<Option<(SampleKey, Vec<f64>)>>,
+    inserted: Vec<(String, String)>,
+}
+
+/// Asks a live session to hand its state over and stop.
+#[derive(Message)]
+#[rtype(result = "Option<SavedSession>")]
+pub struct Detach;
+
+struct Saved {
+    user: Option<String>,
+    at: Instant,
+    session: SavedSession,
+}
+
+struct Live {
+    session_id: String,
+    user: Option<String>,
+    addr: Addr<CustomServer>,
+}
+
+/// Sessions by token: the live ones, and the state of the stopped ones
+/// until their grace window ends. Clones share the sessions.
+#[derive(Clone, Default)]
+pub struct ReconnectStore {
+    grace: Duration,
+    saved: Arc<Mutex<HashMap<String, Saved>>>,
+    live: Arc<Mutex<HashMap<String, Live>>>,
+}
+
+impl ReconnectStore {
+    pub fn new(grace: Duration) -> Self {
+        ReconnectStore { grace, ..ReconnectStore::default() }
+    }
+
+    /// Drops the state of the sessions whose grace window ended. Run on a
+    /// timer, so the state of pages that never come back goes too.
+    pub fn sweep(&self) {
+        self.sweep_at(Instant::now());
+    }
+
+    fn sweep_at(&self, now: Instant) {
+        self.saved.lock().unwrap().retain(|_, saved| now.duration_since(saved.at) < self.grace);
+    }
+
+    fn save(&self, token: String, user: Option<String>, session: SavedSession) {
+        if self.grace.is_zero() {
+            return;
+        }
+        self.sweep();
+        self.saved.lock().unwrap().insert(token, Saved { user, at: Instant::now(), session });
+    }
+
+    fn take(&self, token: &str, user: Option<&str>) -> Option<SavedSession> {
+        self.sweep();
+        let mut saved = self.saved.lock().unwrap();
+        match saved.get(token) {
+            Some(entry) if entry.user.as_deref() == user => saved.remove(token).map(|entry| entry.session),
+            _ => None,
+        }
+    }
+}
+
+/// The state `restore` brings back.
+fn snapshot(shiny: &CustomServer) -> SavedSession {
+    SavedSession {
+        inputs: bookmark::snapshot(shiny),
+        added_panels: shiny.panels[BASE_PANELS..].iter().map(|panel| panel.number()).collect(),
+        samples: shiny.panels
+            .iter()
+            .map(|panel| Some((panel.sample.key()?.clone(), panel.sample.value()?.clone())))
+            .collect(),
+        inserted: shiny.inserted_ui.elements("#insert_section"),
+    }
+}
+
+fn restore(shiny: &mut CustomServer, session: &mut dyn Session, saved: SavedSession) {
+    info!("session state restored after reconnecting");
+    for number in saved.added_panels {
+        dist_module::track(shiny, number);
+    }
+    bookmark::restore(shiny, session, &saved.inputs);
+    for (panel, sample) in shiny.panels.iter_mut().zip(saved.samples) {
+        if let Some((key, values)) = sample {
+            panel.sample.set(key, values);
+        }
+    }
+    shiny.inserted_ui.restore(session, "#insert_section", "afterBegin", saved.inserted);
+    render_inserted_count(shiny, session);
+    build_plot(shiny, session);
+}
+
+/// Ties the session to `token`, taking over the state of the session the
+/// token was last reported by, if it is the same user's. Only the first
+/// token a session reports counts.
+pub fn claim(shiny: &mut CustomServer, session: &mut CustomSession, token: &str) {
+    if shiny.reconnect_token.is_some() || token.is_empty() || token.len() > MAX_TOKEN_LENGTH {
+        return;
+    }
+    shiny.reconnect_token = Some(token.to_string());
+    let user = shiny.user.clone();
+    let previous = shiny.reconnect.live.lock().unwrap().insert(token.to_string(), Live {
+        session_id: shiny.id.clone(),
+        user: user.clone(),
+        addr: session.address(),
+    });
+    if let Some(saved) = shiny.reconnect.take(token, user.as_deref()) {
+        restore(shiny, session, saved);
+        return;
+    }
+    if let Some(previous) = previous.filter(|previous| previous.user == user) {
+        debug!(previous = %previous.session_id, "detaching the session being reconnected");
+        let detached = previous.addr.send(Detach);
+        session.spawn(fut::wrap_future::<_, CustomServer>(detached).map(|result, shiny, session| {
+            if let Ok(Some(saved)) = result {
+                restore(shiny, session, saved);
+            }
+        }));
+    }
+}
+
+/// Keeps the state of a stopping session for its token, if it reported
+/// one and wasn't detached.
+pub fn release(shiny: &mut CustomServer) {
+    let token = match shiny.reconnect_token.take() {
+        Some(token) => token,
+        None => return,
+    };
+    {
+        let mut live = shiny.reconnect.live.lock().unwrap();
+        if live.get(&token).is_some_and(|live| live.session_id == shiny.id) {
+            live.remove(&token);
+        }
+    }
+    shiny.reconnect.save(token, shiny.user.clone(), snapshot(shiny));
+}
+
+/// Hands the state over to the session reconnecting, which stops this
+/// one.
+pub fn detach(shiny: &mut CustomServer) -> Option<SavedSession> {
+    shiny.reconnect_token.take()?;
+    Some(snapshot(shiny))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::config::Config;
+
+    fn saved() -> SavedSession {
+        SavedSession { inputs: json!({}), added_panels: vec![], samples: vec![], inserted: vec![] }
+    }
+
+    fn store(grace_ms: u64) -> ReconnectStore {
+        ReconnectStore::new(Duration::from_millis(grace_ms))
+    }
+
+    #[test]
+    fn state_is_kept_within_the_grace_window() {
+        let store = store(10_000);
+        store.save("token".to_string(), Some("ana".to_string()), saved());
+        assert!(store.take("token", Some("bob")).is_none(), "only for the same user");
+        assert!(store.take("token", Some("ana")).is_some());
+        assert!(store.take("token", Some("ana")).is_none(), "taken once");
+    }
+
+    #[test]
+    fn sweeps_drop_state_once_the_grace_window_ends() {
+        let store = store(10_000);
+        store.save("token".to_string(), None, saved());
+        let saved_at = store.saved.lock().unwrap()["token"].at;
+        store.sweep_at(saved_at + Duration::from_millis(9_999));
+        assert_eq!(store.saved.lock().unwrap().len(), 1, "kept within the window");
+        store.sweep_at(saved_at + Duration::from_millis(10_000));
+        assert!(store.saved.lock().unwrap().is_empty(), "dropped without another session saving or taking");
+        assert!(store.take("token", None).is_none());
+    }
+
+    #[test]
+    fn no_grace_keeps_nothing() {
+        let config = Config { reconnect_grace: 0, ..Config::default() };
+        let store = ReconnectStore::new(config.reconnect_grace());
+        store.save("token".to_string(), None, saved());
+        assert!(store.take("token", None).is_none());
+    }
+}
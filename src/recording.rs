@@ -0,0 +1,148 @@
+//! Recording sessions and replaying them. With `record_dir` set, every
+//! session writes its inputs to `<record_dir>/<session id>.jsonl`, one
+//! entry per line with the milliseconds since the session started. `cargo
+//! run -- --replay <file>` feeds a recording to a fresh session, against a
+//! `MockSession` rather than a websocket, and prints what the session sent
+//! for each entry, so a regression seen in the browser can be reproduced
+//! and diffed. Samples only come out the same when a seed was set.
+//!
+//! Shiny's initial message can't be read back from the input pool as a
+//! whole, so the first entry only has the inputs the session reads when it
+//! starts.
+
+use std::fs::File;
+use std::io::{ self, BufRead, BufReader, BufWriter, Write };
+use std::path::Path;
+use std::time::{ Duration, Instant };
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Map, Value };
+use tracing::warn;
+
+use super::bookmark;
+use super::config::Config;
+use super::server::CustomServer;
+use super::testing::MockSession;
+
+/// Inputs read when a session starts, besides the bookmarked ones.
+const INITIAL_KEYS: [&str; 12] = [
+    ".clientdata_url_search",
+    "language",
+    ".clientdata_output_plot1_width",
+    "current_tab",
+    "plot_width:shiny.number",
+    "plot_height:shiny.number",
+    "plot_dpi:shiny.number",
+    "plot_format",
+    "md_tables",
+    "md_strikethrough",
+    "md_footnotes",
+    "md_tasklist",
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Init,
+    Update,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub at_ms: u64,
+    pub kind: EntryKind,
+    /// The initial inputs, or the one that changed.
+    pub inputs: Map<String, Value>,
+}
+
+/// Writes a session's entries, flushing each so a crash loses none.
+pub struct Recorder {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(dir: impl AsRef<Path>, session_id: &str) -> io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let file = File::create(dir.as_ref().join(format!("{}.jsonl", session_id)))?;
+        Ok(Recorder { file: BufWriter::new(file), started: Instant::now() })
+    }
+
+    fn write(&mut self, kind: EntryKind, inputs: Map<String, Value>) {
+        let entry = Entry { at_ms: self.started.elapsed().as_millis() as u64, kind, inputs };
+        let result = serde_json::to_writer(&mut self.file, &entry)
+            .map_err(io::Error::from)
+            .and_then(|_| self.file.write_all(b"\n"))
+            .and_then(|_| self.file.flush());
+        if let Err(e) = result {
+            warn!(error = %e, "input could not be recorded");
+        }
+    }
+}
+
+/// Records the inputs `shiny` starts with.
+pub fn record_init(shiny: &mut CustomServer) {
+    if shiny.recorder.is_none() {
+        return;
+    }
+    let mut inputs = match bookmark::snapshot(shiny) {
+        Value::Object(inputs) => inputs,
+        _ => Map::new(),
+    };
+    for key in INITIAL_KEYS {
+        if let Some(value) = shiny.input.get(key) {
+            inputs.insert(key.to_string(), value.clone());
+        }
+    }
+    if let Some(recorder) = &mut shiny.recorder {
+        recorder.write(EntryKind::Init, inputs);
+    }
+}
+
+/// Records the input that just changed.
+pub fn record_update(shiny: &mut CustomServer) {
+    if let Some(recorder) = &mut shiny.recorder {
+        let value = shiny.input.get(&shiny.event).cloned().unwrap_or(Value::Null);
+        recorder.write(EntryKind::Update, Map::from_iter([(shiny.event.clone(), value)]));
+    }
+}
+
+/// Replays the recording at `path` in a session configured by `config`,
+/// writing a line per entry with the messages the session sent to `out`.
+/// The time between entries is waited out, ticking meanwhile, so debounced
+/// outputs render as they did.
+pub fn replay(path: impl AsRef<Path>, config: Config, mut out: impl Write) -> io::Result<()> {
+    let mut shiny = CustomServer::builder().config(config).build();
+    let mut session = MockSession::default();
+    let started = Instant::now();
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, e))
+        })?;
+        let at = Duration::from_millis(entry.at_ms);
+        while started.elapsed() < at {
+            std::thread::sleep((at - started.elapsed()).min(Duration::from_millis(100)));
+            session.tick(&mut shiny);
+        }
+        match entry.kind {
+            EntryKind::Init => {
+                for (key, value) in &entry.inputs {
+                    shiny.input.insert(key, value.clone());
+                }
+                session.initialize(&mut shiny);
+            }
+            EntryKind::Update => {
+                for (key, value) in entry.inputs {
+                    session.update(&mut shiny, &key, value);
+                }
+            }
+        }
+        let event = if matches!(entry.kind, EntryKind::Init) { "Init" } else { shiny.event.as_str() };
+        writeln!(out, "{}", json!({ "at_ms": entry.at_ms, "event": event, "sent": session.messages }))?;
+        session.clear();
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use actix::Addr;
+
+use super::server::CustomServer;
+
+struct Entry {
+    /// Signed in user of the session, `None` when the app is open.
+    user: Option<String>,
+    addr: Addr<CustomServer>,
+}
+
+/// Live sessions by id, shared between the websocket actors and the HTTP
+/// handlers that need to reach a session's state.
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl SessionRegistry {
+    pub fn register(&self, id: &str, user: Option<&str>, addr: Addr<CustomServer>) {
+        let entry = Entry { user: user.map(String::from), addr };
+        self.sessions.lock().unwrap().insert(id.to_string(), entry);
+    }
+
+    pub fn unregister(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Addr<CustomServer>> {
+        self.sessions.lock().unwrap().get(id).map(|entry| entry.addr.clone())
+    }
+
+    /// Session `id` if `user` started it, for requests acting on a
+    /// session on behalf of whoever sent them. Other users' sessions are
+    /// `None`, as unknown ones.
+    pub fn get_owned(&self, id: &str, user: Option<&str>) -> Option<Addr<CustomServer>> {
+        let sessions = self.sessions.lock().unwrap();
+        let entry = sessions.get(id).filter(|entry| entry.user.as_deref() == user)?;
+        Some(entry.addr.clone())
+    }
+
+    pub fn all(&self) -> Vec<Addr<CustomServer>> {
+        self.sessions.lock().unwrap().values().map(|entry| entry.addr.clone()).collect()
+    }
+}
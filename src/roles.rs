@@ -0,0 +1,44 @@
+//! What signed in users may do. Viewers look at the simulations, editors
+//! may also insert plots and stream, and admins may also use `/admin`.
+//! Editor-only controls are only rendered for editors, and their inputs
+//! are ignored for everyone else with `require_role!`.
+
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    /// Whether this role may do what `role` may, every role being allowed
+    /// what the roles below it are.
+    pub fn allows(&self, role: Role) -> bool {
+        *self >= role
+    }
+}
+
+/// Returns from the calling handler, such as `update`, unless the session's
+/// user has at least `role`. Meant as the first statement of the branch
+/// handling an input only some users may change.
+///
+/// ```ignore
+/// if changed!(shiny, ("insert_ui:shiny.action")) {
+///     require_role!(shiny, Role::Editor);
+///     ...
+/// }
+/// ```
+macro_rules! require_role {
+    ($shiny:ident, $role:expr) => {
+        if !$shiny.role.allows($role) {
+            tracing::warn!(event = %$shiny.event, role = ?$shiny.role, "input not allowed for the role, ignored");
+            return;
+        }
+    };
+}
+
+pub(crate) use require_role;
@@ -0,0 +1,78 @@
+//! Jobs run on a timer rather than in response to an input. Each job makes
+//! an update which is sent to every live session found in the registry, so
+//! sessions change without their user doing anything.
+
+use std::time::Duration;
+use actix::Message;
+use actix_web::rt::{ self, time };
+
+use super::registry::SessionRegistry;
+
+/// Sent to every session when a job runs.
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub enum Scheduled {
+    /// Shown as a notification.
+    Notice(String),
+    /// Reloads the History tab, for runs recorded by the user's other
+    /// sessions.
+    RefreshRuns,
+    /// Reloads the page, whose files changed, in development mode.
+    Reload,
+}
+
+pub struct Job {
+    name: &'static str,
+    every: Duration,
+    /// The update to send, if any this time.
+    run: Box<dyn Fn() -> Option<Scheduled> + Send>,
+}
+
+impl Job {
+    pub fn new(
+        name: &'static str,
+        every: Duration,
+        run: impl Fn() -> Option<Scheduled> + Send + 'static
+    ) -> Self {
+        Job { name, every, run: Box::new(run) }
+    }
+}
+
+pub struct Scheduler {
+    registry: SessionRegistry,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(registry: SessionRegistry) -> Self {
+        Scheduler { registry, jobs: Vec::new() }
+    }
+
+    pub fn job(mut self, job: Job) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Runs every job on its own timer, the first time one period from now.
+    /// Jobs run until the server stops.
+    pub fn start(self) {
+        for job in self.jobs {
+            let registry = self.registry.clone();
+            rt::spawn(async move {
+                let mut interval = time::interval_at(time::Instant::now() + job.every, job.every);
+                loop {
+                    interval.tick().await;
+                    let update = match (job.run)() {
+                        Some(update) => update,
+                        None => continue,
+                    };
+                    let sessions = registry.all();
+                    tracing::debug!(job = job.name, sessions = sessions.len(), "scheduled job ran");
+                    for session in sessions {
+                        session.do_send(update.clone());
+                    }
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Per-session storage for transient values that handlers need across
+/// updates but that don't deserve a field on `CustomServer`.
+#[derive(Default)]
+pub struct Scratch {
+    values: HashMap<String, Box<dyn Any>>,
+}
+
+impl Scratch {
+    pub fn insert<T: Any>(&mut self, key: &str, value: T) {
+        self.values.insert(key.to_string(), Box::new(value));
+    }
+
+    /// Returns the value stored under `key`, or `None` if there is none or
+    /// it is not a `T`.
+    pub fn get<T: Any>(&self, key: &str) -> Option<&T> {
+        self.values.get(key)?.downcast_ref()
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use super::*;
+    use crate::config::Config;
+    use crate::server::CustomServer;
+    use crate::testing::Driver;
+
+    #[test]
+    fn values_are_typed() {
+        let mut scratch = Scratch::default();
+        scratch.insert("count", 3_u32);
+        assert_eq!(scratch.get::<u32>("count"), Some(&3));
+        assert_eq!(scratch.get::<bool>("count"), None);
+        scratch.remove("count");
+        assert_eq!(scratch.get::<u32>("count"), None);
+    }
+
+    #[test]
+    fn values_survive_across_flushes() {
+        let config = Config { max_markdown_length: 10, ..Config::default() };
+        let mut driver = Driver::new(CustomServer::builder().config(config).build());
+        let warnings = |driver: &Driver| driver.session.custom_messages("toggle-element")
+            .iter()
+            .filter(|message| message["selector"] == "#markdown_warning")
+            .map(|message| message["visible"].clone())
+            .collect::<Vec<_>>();
+        driver.set("markdown", json!("longer than ten"));
+        assert_eq!(warnings(&driver), vec![json!(true)]);
+        // Past the count's throttle, so the next change flushes again.
+        driver.shiny.clock.advance(Duration::from_millis(100));
+        driver.set("markdown", json!("still longer than ten"));
+        assert_eq!(driver.session.output("markdown_count"), Some("21 / 10 characters"));
+        assert_eq!(driver.shiny.scratch.get::<bool>("markdown_warned"), Some(&true));
+        assert_eq!(warnings(&driver), vec![json!(true)], "shown once");
+        driver.shiny.clock.advance(Duration::from_millis(100));
+        driver.set("markdown", json!("short"));
+        assert_eq!(warnings(&driver), vec![json!(true), json!(false)]);
+        assert_eq!(driver.shiny.scratch.get::<bool>("markdown_warned"), None);
+    }
+}
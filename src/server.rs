@@ -1,75 +1,817 @@
-use actix::Actor;
-use serde_json::json;
+use actix::{ Actor, ActorContext, Addr, AsyncContext, Handler, Message, MessageResponse, MessageResult };
+use actix_web_actors::ws::{ CloseCode, CloseReason };
+use serde_json::{ json, Value };
 use shiny_rs::shiny_rs_derive::ShinyHandler;
 use shiny_rs::changed;
-use shiny_rs::session::*;
+use shiny_rs::session::ShinyContext;
 use shiny_rs::session::input_pool::InputPool;
 use shiny_rs::session::traits::*;
 use shiny_rs::ui::*;
-use std::time::Instant;
-use comrak::{ markdown_to_html, ComrakOptions };
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::{ Duration, Instant };
+use tracing::{ debug, error, info, info_span, warn, Instrument, Span };
 
-use super::plot::{ get_plot, get_dist };
+use super::plot::{
+    get_plot, get_plot_json, get_dist, get_line_plot, Distribution, NormalParams, PlotType,
+    PercentileRange, Brush, PlotSize, PlotOptions, PlotTheme, SeriesStyle, crop, get_qq_plot,
+    get_ecdf_plot
+};
+use super::insert::{ InsertedUi, PendingRemovals };
+use super::messages::{
+    Session, render_ui, render_text, render_error, update_text_input, scroll_into_view,
+    render_value, send_custom_message, render_plotly, modal_dialog, show_modal, remove_modal,
+    allow_reconnect, show_element, hide_element, OutputErrorKind
+};
+use super::changes::LastValues;
+use super::links::InputLinks;
+use super::updates::{
+    update_numeric_input, update_slider, update_select, update_checkbox, update_date, NumericUpdate,
+    SliderUpdate, SelectUpdate, CheckboxUpdate, DateUpdate
+};
+use super::tasks::TaskRegistry;
+use super::markdown::{ MarkdownRenderer, MarkdownOptions };
+use super::scratch::Scratch;
+use super::events::{ InputEvents, log_input_changes };
+use super::notifications::{ Notifications, NotificationMode };
+use super::validation::{ Validators, Validator };
+use super::rate_limit::{ MessageGuard, Verdict };
+use super::registry::SessionRegistry;
+use super::reactive::Reactive;
+use super::dist_module::{ self, DistPanel, SampleId, BASE_PANELS, SEED_KEY };
+use super::upload::{ Upload, SetUpload };
+use super::import::{ self, Table };
+use super::files::{ FileStore, SessionFiles };
+use super::latency::{ self, Latency };
+use super::workers::WorkerPool;
+use super::progress;
+use super::plot_export::{ Figure, ImageSize };
+use super::simulate::simulate_large;
+use super::stats::{ summary_table, outliers_panel, escape_html, Bandwidth, OutlierMethod };
+use super::bookmark::{ self, BookmarkStore };
+use super::app_state::{ AppState, Announcement, Broadcast, Join, Leave };
+use super::scheduler::Scheduled;
+use super::reconnect::{ self, Detach, ReconnectStore, SavedSession };
+use super::idle::{ self, IdleTimer };
+use super::panics::{ self, Caught };
+use super::logic::{ AppLogic, ServerLogic };
+use super::recording::{ self, Recorder };
+use super::chat::{ self, ChatMessage };
+use super::config::{ Config, PlotBackend };
+use super::metrics::{ AppMetrics, MeteredSession };
+use super::shutdown::Restarting;
+use super::stream::LiveStream;
+use super::debounce::{ Debounced, Throttled };
+use super::clock::Clock;
+use super::typed_inputs::{ InputError, TypedInputs, report_input_error };
+use super::datatable::{ DataTable, SortColumn };
+use super::history::History;
+use super::tests::results_table;
+use super::cache::{ Lru, SampleCache };
+use super::frames::{ Encoding, FrameEncoder };
+use super::admin::{ GetSessionInfo, SessionInfo, Disconnect };
+use super::auth::User;
+use super::roles::{ Role, require_role };
+use super::storage::{ Storage, runs_table };
+use super::presets;
+use super::i18n::{ self, t, Language, LANGUAGE_KEY };
 
-fn sample_dist(n: u64, mean: f64, sd: f64) -> Vec<f64> {
-    get_dist(n as usize, mean, sd).unwrap_or_default()
+const MAX_INSERTED_PLOTS: usize = 5;
+const MAX_ANNOUNCEMENT_LENGTH: usize = 200;
+const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+const MARKDOWN_DEBOUNCE: Duration = Duration::from_millis(300);
+const MARKDOWN_COUNT_THROTTLE: Duration = Duration::from_millis(100);
+const HISTORY_LIMIT: usize = 50;
+const PLOT_CACHE_SIZE: usize = 16;
+const PLOT_WIDTH_KEY: &str = ".clientdata_output_plot1_width";
+const PLOT_RESIZE_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Bandwidth of the estimates until the manual slider is moved.
+const KDE_MANUAL_DEFAULT: f64 = 0.5;
+/// Range of the linked number and slider.
+const LINKED_RANGE: (f64, f64) = (0.0, 100.0);
+/// Tabs showing the plot or the summary table. Neither is computed before
+/// one of them was shown.
+const PLOT_TABS: [&str; 2] = ["Simulation", "Data"];
+const DIAGNOSTICS_TAB: &str = "Diagnostics";
+const RUNS_TAB: &str = "History";
+/// Latest runs listed in the History tab.
+const RUNS_SHOWN: usize = 20;
+
+/// Plot type, options, plotted percentiles, zoomed in range, and identity
+/// and legend entry of every sample the plot was built with.
+type PlotKey = (PlotType, PlotOptions, PercentileRange, Option<(f64, f64)>, Vec<(SampleId, String)>);
+
+/// Options, and version and legend entry of every panel's sample the
+/// diagnostic plots were built with.
+type DiagnosticsKey = (PlotOptions, Vec<(u64, String)>);
+
+fn sample_dist<R: rand::Rng>(n: u64, dist: &Distribution, rng: &mut R) -> Vec<f64> {
+    get_dist(n as usize, dist, rng).unwrap_or_default()
 }
 
-fn build_plot(session: &mut CustomSession, dist1: &[f64], dist2: &[f64]) {
-    let my_plot = get_plot(dist1, dist2);
-    render_ui(session, "plot1", &my_plot);
+fn render_seed(shiny: &CustomServer, session: &mut dyn Session) {
+    let seed = shiny.input.get_u64(SEED_KEY);
+    render_text(
+        session,
+        "active_seed",
+        &seed.map_or(t!(shiny, "seed.random"), |seed| t!(shiny, "seed.value", seed = seed))
+    );
 }
 
-fn validate_range(session: &mut CustomSession, n: u64) -> bool {
-    if (1..=10000).contains(&n) {
-        true
-    } else {
-        show_notification(
+/// Greets the signed in user, next to a button to sign out.
+fn render_account(shiny: &CustomServer, session: &mut dyn Session) {
+    if let Some(user) = &shiny.user {
+        render_ui(
             session,
-            json!({
-                "html": "Number out of range",
-                "action": "",
-                "deps": [],
-                "closeButton": true,
-                "id": generate_id(),
-                "type": "error"
-            })
+            "account",
+            &format!(
+                r#"{} <form method="post" action="../logout" class="d-inline"><button type="submit" class="btn btn-link btn-sm">{}</button></form>"#,
+                t!(shiny, "account.greeting", user = escape_html(user)),
+                t!(shiny, "account.sign_out")
+            )
         );
-        false
     }
 }
 
+/// Renders the controls only editors may use, or says who may use them.
+fn render_editor_controls(shiny: &CustomServer, session: &mut dyn Session) {
+    let editor = shiny.role.allows(Role::Editor);
+    let controls = |html: String| {
+        if editor {
+            html
+        } else {
+            format!("<p class=\"text-muted\">{}</p>", t!(shiny, "editor.only"))
+        }
+    };
+    render_ui(session, "insert_controls", &controls(format!(
+        r#"<button id="insert_ui" type="button" class="btn btn-default action-button">{}</button>
+<button id="remove_ui" type="button" class="btn btn-default action-button">{}</button>"#,
+        t!(shiny, "insert.button"),
+        t!(shiny, "insert.remove_button")
+    )));
+    render_ui(session, "stream_controls", &controls(format!(
+        r#"<button id="stream_toggle" type="button" class="btn btn-default action-button">{}</button>
+<div class="form-group shiny-input-container">
+  <label class="control-label" id="stream_rate-label" for="stream_rate">{}</label>
+  <input id="stream_rate" type="number" class="form-control" value="500" min="100" step="100"/>
+</div>"#,
+        stream_label(shiny),
+        t!(shiny, "stream.rate")
+    )));
+}
+
+/// Label of the button starting and stopping the stream.
+fn stream_label(shiny: &CustomServer) -> String {
+    if shiny.stream.is_running() {
+        t!(shiny, "stream.stop")
+    } else {
+        t!(shiny, "stream.start")
+    }
+}
+
+/// Switches the text the server rendered to the language just picked.
+/// Editor controls are relabeled rather than rendered again, which would
+/// reset the stream rate.
+fn render_language(shiny: &mut CustomServer, session: &mut dyn Session) {
+    render_seed(shiny, session);
+    render_account(shiny, session);
+    if shiny.role.allows(Role::Editor) {
+        for (id, label) in [
+            ("insert_ui", t!(shiny, "insert.button")),
+            ("remove_ui", t!(shiny, "insert.remove_button")),
+            ("stream_toggle", stream_label(shiny)),
+            ("stream_rate", t!(shiny, "stream.rate")),
+        ] {
+            update_text_input(session, id, json!({ "label": label }));
+        }
+    } else {
+        render_editor_controls(shiny, session);
+    }
+    render_inserted_count(shiny, session);
+    for index in 0..shiny.panels.len() {
+        dist_module::render_labels(shiny, session, index);
+    }
+    render_markdown_count(shiny, session);
+    render_markdown_warning(shiny, session);
+}
+
+/// Shows the streaming controls in streaming mode. Leaving it stops the
+/// stream.
+fn set_stream_mode(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if shiny.inputs().get_bool("stream_mode").unwrap_or(false) {
+        show_element(session, "#stream_controls");
+        return;
+    }
+    hide_element(session, "#stream_controls");
+    if let (true, Some(context)) = (shiny.stream.is_running(), session.actor_context()) {
+        shiny.stream.stop(context);
+        update_text_input(session, "stream_toggle", json!({ "label": stream_label(shiny) }));
+    }
+}
+
+/// Renders the plot, the summary table and the outliers panel, unless
+/// nothing they're built from changed since they were last rendered. Also
+/// refreshes the diagnostic plots.
+pub(crate) fn build_plot(shiny: &mut CustomServer, session: &mut dyn Session) {
+    render_diagnostics(shiny, session);
+    if !PLOT_TABS.iter().any(|tab| shiny.initialized_tabs.contains(*tab)) {
+        return;
+    }
+    let start = Instant::now();
+    let mut labels: Vec<(SampleId, String)> = shiny.panels
+        .iter()
+        .map(|panel| (panel.sample_id(), panel.label(&shiny.input)))
+        .collect();
+    if let Some(upload) = &shiny.uploaded {
+        labels.push((SampleId::Drawn(shiny.uploads), t!(shiny, "upload.series", name = upload.name)));
+    }
+    let plot_type = PlotType::from_input(&shiny.input.get_string("plot_type").unwrap_or_default());
+    let options = shiny.plot_options();
+    shiny.plot.track((plot_type, options, shiny.percentile_range, shiny.zoom, labels));
+    let mut values: Vec<&[f64]> = shiny.panels
+        .iter()
+        .map(|panel| panel.sample.value().map_or(&[][..], |dist| &dist[..]))
+        .collect();
+    if let Some(upload) = &shiny.uploaded {
+        values.push(&upload.values);
+    }
+    let backend = shiny.config.plot_backend;
+    let summary_format = shiny.config.summary_format;
+    let (cache, metrics) = (&mut shiny.plot_cache, &shiny.metrics);
+    let rebuilt = shiny.plot.refresh(|key| {
+        if let Some(cached) = cache.get(key) {
+            metrics.plot_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return cached.clone();
+        }
+        metrics.plot_cache_misses.fetch_add(1, Ordering::Relaxed);
+        let (plot_type, options, range, zoom, labels) = key;
+        let series: Vec<(&[f64], &str)> = values
+            .iter()
+            .zip(labels)
+            .map(|(values, (_, label))| (*values, label.as_str()))
+            .collect();
+        let table = summary_table(&series, summary_format, options.language);
+        let trimmed: Vec<Vec<f64>> = values.iter().map(|values| plotted(values, range, *zoom)).collect();
+        let series: Vec<(&[f64], &str)> = trimmed
+            .iter()
+            .zip(labels)
+            .map(|(values, (_, label))| (&values[..], label.as_str()))
+            .collect();
+        let plot = match backend {
+            PlotBackend::Html => get_plot(&series, *plot_type, options),
+            PlotBackend::Json => get_plot_json(&series, *plot_type, options),
+        };
+        let outliers = options.outliers.map_or(String::new(), |method| {
+            outliers_panel(&series, method, summary_format, options.language)
+        });
+        cache.insert(key.clone(), (plot.clone(), table.clone(), outliers.clone()));
+        (plot, table, outliers)
+    });
+    if rebuilt {
+        if let Some((plot, table, outliers)) = shiny.plot.value() {
+            match backend {
+                PlotBackend::Html => render_ui(session, "plot1", plot),
+                PlotBackend::Json => render_plotly(session, "plot1", plot),
+            }
+            render_ui(session, "summary_table", table);
+            render_ui(session, "outliers", outliers);
+        }
+        render_datatable(shiny, session);
+        debug!(elapsed = ?start.elapsed(), "plot rendered");
+    }
+}
+
+/// The part of `values` `plot1` shows: the values within the plotted
+/// percentiles, cropped to the zoomed range.
+fn plotted(values: &[f64], range: &PercentileRange, zoom: Option<(f64, f64)>) -> Vec<f64> {
+    let trimmed = range.trim(values);
+    match zoom {
+        Some(zoom) => crop(&trimmed, zoom),
+        None => trimmed,
+    }
+}
+
+/// Points the plot's download link at the size and format picked.
+fn render_plot_download(shiny: &CustomServer, session: &mut dyn Session) {
+    let inputs = shiny.inputs();
+    let format = shiny.input.get_string("plot_format").unwrap_or_else(|| String::from("png"));
+    let number = |key, default| inputs.get_u64(key).unwrap_or(default);
+    render_value(
+        session,
+        "download_plot",
+        json!(format!(
+            "download/plot?session={}&format={}&width={}&height={}&dpi={}",
+            shiny.id,
+            format,
+            number("plot_width:shiny.number", 800),
+            number("plot_height:shiny.number", 480),
+            number("plot_dpi:shiny.number", 96)
+        ))
+    );
+}
+
+/// Renders the QQ plot and the ECDFs of the panels' samples once the
+/// Diagnostics tab was shown, and again whenever a sample changes.
+fn render_diagnostics(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if !shiny.initialized_tabs.contains(DIAGNOSTICS_TAB) {
+        return;
+    }
+    let labels: Vec<(u64, String)> = shiny.panels
+        .iter()
+        .map(|panel| (panel.sample.version(), panel.label(&shiny.input)))
+        .collect();
+    let mut options = shiny.plot_options();
+    options.size = None;
+    shiny.diagnostics.track((options, labels));
+    let values: Vec<&[f64]> = shiny.panels
+        .iter()
+        .map(|panel| panel.sample.value().map_or(&[][..], |dist| &dist[..]))
+        .collect();
+    let rebuilt = shiny.diagnostics.refresh(|(options, labels)| {
+        let series: Vec<(&[f64], &str)> = values
+            .iter()
+            .zip(labels)
+            .map(|(values, (_, label))| (*values, label.as_str()))
+            .collect();
+        let qq = match series[..] {
+            [first, second, ..] => get_qq_plot(first, second, options),
+            _ => String::new(),
+        };
+        (qq, get_ecdf_plot(&series, options))
+    });
+    if let (true, Some((qq, ecdf))) = (rebuilt, shiny.diagnostics.value()) {
+        render_ui(session, "qq_plot", qq);
+        render_ui(session, "ecdf_plot", ecdf);
+    }
+}
+
+/// Shows an error in place of the plot. The plot is invalidated so it gets
+/// rendered again once the error is fixed, even if the samples didn't change.
+pub(crate) fn plot_error(
+    shiny: &mut CustomServer,
+    session: &mut dyn Session,
+    message: &str,
+    kind: OutputErrorKind
+) {
+    shiny.plot.invalidate();
+    render_error(session, "plot1", message, kind);
+}
+
+/// Computes the outputs of `tab` the first time it's shown. Later visits
+/// find them kept up to date by `update`.
+fn show_tab(shiny: &mut CustomServer, session: &mut dyn Session, tab: &str) {
+    let plot_shown = PLOT_TABS.iter().any(|tab| shiny.initialized_tabs.contains(*tab));
+    if !shiny.initialized_tabs.insert(tab.to_string()) {
+        return;
+    }
+    debug!(tab, "tab initialized");
+    if PLOT_TABS.contains(&tab) && !plot_shown {
+        // Also renders the sample table when showing the Data tab.
+        build_plot(shiny, session);
+    } else if tab == "Data" {
+        render_datatable(shiny, session);
+    }
+    if tab == DIAGNOSTICS_TAB {
+        render_diagnostics(shiny, session);
+    }
+    if tab == RUNS_TAB {
+        // Runs can't be from the future.
+        update_date(session, "runs_since", DateUpdate {
+            max: Some(chrono::Utc::now().date_naive()),
+            ..Default::default()
+        });
+        render_runs(shiny, session);
+    }
+}
+
+/// Lists the latest runs of the session's user, since the date picked if
+/// any, once the History tab was shown.
+pub(crate) fn render_runs(shiny: &CustomServer, session: &mut dyn Session) {
+    if !shiny.initialized_tabs.contains(RUNS_TAB) {
+        return;
+    }
+    let since = shiny.inputs().get_date("runs_since").ok();
+    let html = match shiny.storage.runs(shiny.user.as_deref(), since, RUNS_SHOWN) {
+        Ok(runs) if runs.is_empty() => format!("<p class=\"text-muted\">{}</p>", t!(shiny, "runs.empty")),
+        Ok(runs) => runs_table(&runs),
+        Err(e) => {
+            warn!(error = %e, "runs could not be loaded");
+            format!("<p class=\"text-danger\">{}</p>", t!(shiny, "runs.failed"))
+        }
+    };
+    render_ui(session, "runs", &html);
+}
+
+/// Renders the current page of the sample table, once the Data tab was
+/// shown.
+fn render_datatable(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if !shiny.initialized_tabs.contains("Data") {
+        return;
+    }
+    let mut series: Vec<(&[f64], String)> = shiny.panels
+        .iter()
+        .map(|panel| {
+            (panel.sample.value().map_or(&[][..], |dist| &dist[..]), panel.label(&shiny.input))
+        })
+        .collect();
+    if let Some(upload) = &shiny.uploaded {
+        series.push((&upload.values, t!(shiny, "upload.series", name = upload.name)));
+    }
+    let series: Vec<(&[f64], &str)> = series
+        .iter()
+        .map(|(values, label)| (*values, label.as_str()))
+        .collect();
+    let table = shiny.datatable.render(&series, shiny.config.datatable_format, shiny.language);
+    render_ui(session, "datatable", &table);
+}
+
+pub(crate) fn render_inserted_count(shiny: &CustomServer, session: &mut dyn Session) {
+    render_text(
+        session,
+        "inserted_count",
+        &t!(shiny, "insert.count", count = shiny.inserted_ui.count("#insert_section"))
+    );
+}
+
 #[derive(ShinyHandler)]
 pub struct CustomServer {
+    pub id: String,
+    /// The signed in user, `None` when the app is open to anyone.
+    pub user: Option<String>,
+    /// Editor when the app is open to anyone.
+    pub role: Role,
+    /// Parent of everything logged on behalf of the session.
+    pub(crate) span: Span,
+    connected_at: chrono::DateTime<chrono::Utc>,
+    /// Input messages received, rate limited ones included.
+    input_count: u64,
     hb: Instant,
+    pub(crate) idle: IdleTimer,
+    pub(crate) latency: Latency,
     pub input: InputPool,
     pub event: String,
+    /// What the session does with its start, its inputs and its ticks.
+    /// `initialize`, `update` and `tick` hand the events over to it.
+    logic: Arc<dyn ServerLogic>,
+    /// Writes the inputs to a file, with `record_dir` set.
+    pub(crate) recorder: Option<Recorder>,
+    /// Scratch directory, removed when the session stops.
+    pub(crate) files: Option<SessionFiles>,
     initialize: fn(&mut Self, session: &mut <Self as Actor>::Context),
     update: fn(&mut Self, session: &mut <Self as Actor>::Context),
     tick: fn(&mut Self, session: &mut <Self as Actor>::Context),
-    dist1: Vec<f64>,
-    dist2: Vec<f64>,
-    hb_interval: std::time::Duration,
-    client_timeout: std::time::Duration
+    initialized_tabs: HashSet<String>,
+    pub(crate) panels: Vec<DistPanel>,
+    /// Number of the next panel added.
+    pub(crate) next_panel: u64,
+    uploaded: Option<Upload>,
+    uploads: u64,
+    /// The columns of the data pasted in the Data tab, while it parses.
+    pasted: Option<Table>,
+    /// Cancel flags of the work behind the progress bars shown, by output.
+    pub(crate) progress_bars: HashMap<String, Arc<AtomicBool>>,
+    stream: LiveStream,
+    /// The plot, the summary table and the outliers panel.
+    plot: Reactive<PlotKey, (String, String, String)>,
+    plot_cache: Lru<PlotKey, (String, String, String)>,
+    pub(crate) sample_cache: SampleCache,
+    pub(crate) storage: Storage,
+    frames: FrameEncoder,
+    percentile_range: PercentileRange,
+    /// Range of sample values the plot is zoomed in on.
+    zoom: Option<(f64, f64)>,
+    /// Follows the width of `plot1`, which Shiny reports on resize. `None`
+    /// until it was first reported.
+    plot_size: Option<PlotSize>,
+    plot_resize: Debounced,
+    plot_theme: PlotTheme,
+    datatable: DataTable,
+    diagnostics: Reactive<DiagnosticsKey, (String, String)>,
+    history: History,
+    pub(crate) inserted_ui: InsertedUi,
+    removals: PendingRemovals,
+    pub(crate) last_values: LastValues,
+    links: InputLinks,
+    pub tasks: TaskRegistry,
+    pub(crate) markdown: MarkdownRenderer,
+    markdown_preview: Debounced,
+    markdown_count: Throttled,
+    pub scratch: Scratch,
+    pub input_events: InputEvents,
+    pub(crate) notifications: Notifications,
+    pub(crate) validators: Validators,
+    /// Language of the text the server renders, picked with `language`.
+    pub(crate) language: Language,
+    message_guard: MessageGuard,
+    /// The time of the debounced and rate limited inputs.
+    pub clock: Clock,
+    registry: SessionRegistry,
+    bookmarks: BookmarkStore,
+    pub(crate) reconnect: ReconnectStore,
+    /// Token of the page, once it reported it.
+    pub(crate) reconnect_token: Option<String>,
+    pub(crate) app_state: Option<Addr<AppState>>,
+    /// Runs the large simulations, shared with the other sessions.
+    pub(crate) worker_pool: Option<Addr<WorkerPool>>,
+    pub(crate) metrics: Arc<AppMetrics>,
+    pub(crate) config: Config,
+    hb_interval: Duration,
+    client_timeout: Duration
 }
 
 impl CustomServer {
-    pub fn new(
-        initialize: fn(&mut Self, session: &mut <Self as Actor>::Context),
-        update: fn(&mut Self, session: &mut <Self as Actor>::Context),
-        tick: fn(&mut Self, session: &mut <Self as Actor>::Context),
-    ) -> Self {
+    pub fn builder() -> CustomServerBuilder {
+        CustomServerBuilder::default()
+    }
+
+    /// The logic the session runs, shared with every other session built
+    /// by the same builder.
+    pub fn logic(&self) -> Arc<dyn ServerLogic> {
+        self.logic.clone()
+    }
+
+    pub fn inputs(&self) -> TypedInputs<'_> {
+        TypedInputs::new(&self.input)
+    }
+
+    /// Largest websocket frame the session takes. Frames somewhat over
+    /// `max_message_size` still reach the guard, which tells the user about
+    /// them; the codec refuses larger ones outright.
+    pub fn max_frame_size(&self) -> usize {
+        (self.config.max_message_size * 4).max(64 * 1024)
+    }
+
+    /// `plot1` as the download draws it: the plotted values of each series,
+    /// zoomed and trimmed like on the page.
+    pub fn figure(&self) -> Figure {
+        let mut series: Vec<(String, Vec<f64>)> = self.panels
+            .iter()
+            .map(|panel| {
+                let values = panel.sample.value().map_or(&[][..], |values| &values[..]);
+                (panel.label(&self.input), plotted(values, &self.percentile_range, self.zoom))
+            })
+            .collect();
+        if let Some(upload) = &self.uploaded {
+            series.push((
+                t!(self, "upload.series", name = upload.name),
+                plotted(&upload.values, &self.percentile_range, self.zoom)
+            ));
+        }
+        let options = self.plot_options();
+        Figure {
+            series,
+            plot_type: PlotType::from_input(&self.input.get_string("plot_type").unwrap_or_default()),
+            styles: options.styles,
+            theme: options.theme,
+            language: options.language,
+            axis_format: options.axis_format,
+            bandwidth: options.bandwidth,
+            kde_overlay: options.kde_overlay,
+        }
+    }
+
+    /// The look of `plot1`: its size, theme, and the style picked for each
+    /// panel's series. Uploaded samples get the default color.
+    fn plot_options(&self) -> PlotOptions {
+        let inputs = self.inputs();
+        let line_width = inputs.get_f64("line_width").unwrap_or(2.0);
+        let opacity = inputs.get_f64("opacity").unwrap_or(1.0);
+        let mut styles: Vec<SeriesStyle> = self.panels
+            .iter()
+            .map(|panel| SeriesStyle { color: panel.color(&self.input), line_width, opacity })
+            .collect();
+        styles.push(SeriesStyle { color: None, line_width, opacity });
+        PlotOptions {
+            size: self.plot_size,
+            theme: self.plot_theme,
+            styles,
+            language: self.language,
+            axis_format: self.config.axis_format,
+            bandwidth: Bandwidth::from_inputs(
+                &inputs.get_json::<String>("kde_bandwidth").unwrap_or_default(),
+                inputs.get_f64("kde_manual").unwrap_or(KDE_MANUAL_DEFAULT)
+            ),
+            kde_overlay: inputs.get_bool("kde_overlay").unwrap_or(false),
+            outliers: OutlierMethod::from_input(&inputs.get_json::<String>("outlier_method").unwrap_or_default()),
+        }
+    }
+}
+
+/// Settings and shared state of a new session. Sessions built without an
+/// `AppState` neither send nor receive announcements and chat messages.
+/// Clones share the state, so one builder can start every session.
+#[derive(Clone, Default)]
+pub struct CustomServerBuilder {
+    config: Config,
+    registry: SessionRegistry,
+    bookmarks: BookmarkStore,
+    reconnect: ReconnectStore,
+    app_state: Option<Addr<AppState>>,
+    worker_pool: Option<Addr<WorkerPool>>,
+    metrics: Arc<AppMetrics>,
+    sample_cache: SampleCache,
+    storage: Storage,
+    files: FileStore,
+    user: Option<User>,
+    logic: Option<Arc<dyn ServerLogic>>,
+}
+
+impl CustomServerBuilder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn registry(mut self, registry: SessionRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn bookmarks(mut self, bookmarks: BookmarkStore) -> Self {
+        self.bookmarks = bookmarks;
+        self
+    }
+
+    pub fn reconnect(mut self, reconnect: ReconnectStore) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn app_state(mut self, app_state: Addr<AppState>) -> Self {
+        self.app_state = Some(app_state);
+        self
+    }
+
+    pub fn worker_pool(mut self, worker_pool: Addr<WorkerPool>) -> Self {
+        self.worker_pool = Some(worker_pool);
+        self
+    }
+
+    pub fn metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn sample_cache(mut self, sample_cache: SampleCache) -> Self {
+        self.sample_cache = sample_cache;
+        self
+    }
+
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn files(mut self, files: FileStore) -> Self {
+        self.files = files;
+        self
+    }
+
+    pub fn user(mut self, user: Option<User>) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Replaces `AppLogic` as what sessions do.
+    pub fn logic(mut self, logic: impl ServerLogic + 'static) -> Self {
+        self.logic = Some(Arc::new(logic));
+        self
+    }
+
+    pub fn build(self) -> CustomServer {
+        let config = self.config;
+        let panels: Vec<DistPanel> = (1..=BASE_PANELS as u64).map(DistPanel::new).collect();
+        let no_markup = || Validator::Pattern(
+            regex::Regex::new("^[^<>]*$").expect("valid pattern"),
+            "validation.no_markup"
+        );
+        let validators = panels
+            .iter()
+            .fold(Validators::default(), |validators, panel| {
+                panel.add_validators(validators, config.max_sample_size)
+            })
+            .add("announcement", Validator::Required)
+            .add("announcement", Validator::MaxLength(MAX_ANNOUNCEMENT_LENGTH))
+            .add("chat_message", Validator::Required)
+            .add("chat_message", Validator::MaxLength(MAX_CHAT_MESSAGE_LENGTH))
+            .add("preset_name", Validator::Required)
+            .add("preset_name", Validator::MaxLength(presets::MAX_NAME_LENGTH))
+            .add("preset_name", no_markup())
+            .add("text1", no_markup())
+            .add("text2", no_markup())
+            .add("paste_data", Validator::Parse(import::check));
+        let validators = [
+            ("plot_width:shiny.number", ImageSize::WIDTH),
+            ("plot_height:shiny.number", ImageSize::HEIGHT),
+            ("plot_dpi:shiny.number", ImageSize::DPI),
+        ]
+            .into_iter()
+            .fold(validators, |validators, (key, (min, max))| {
+                validators
+                    .add(key, Validator::Required)
+                    .add(key, Validator::Range { min: min as f64, max: max as f64 })
+                    .add(key, Validator::Step(1.0))
+            });
+        let id = generate_id();
+        let clock = Clock::default();
+        let recorder = config.record_dir.as_ref().and_then(|dir| {
+            Recorder::create(dir, &id)
+                .map_err(|e| warn!(error = %e, dir = %dir, "session can't be recorded"))
+                .ok()
+        });
+        let files = self.files
+            .open(&id)
+            .map_err(|e| warn!(error = %e, "session has no scratch directory"))
+            .ok();
         CustomServer {
+            span: info_span!("session", id = %id, user = self.user.as_ref().map_or("", |user| &user.name)),
+            id,
+            role: self.user.as_ref().map_or(Role::Editor, |user| user.role),
+            user: self.user.map(|user| user.name),
+            connected_at: chrono::Utc::now(),
+            input_count: 0,
             hb: Instant::now(),
+            idle: IdleTimer::new(config.idle_timeout(), config.idle_warning()),
+            latency: Latency::default(),
             input: InputPool::new(),
             event: String::from("Init"),
-            dist1: vec!(),
-            dist2: vec!(),
-            initialize,
-            update,
-            tick,
-            hb_interval: std::time::Duration::from_secs(5),
-            client_timeout: std::time::Duration::from_secs(10),
+            logic: self.logic.unwrap_or_else(|| Arc::new(AppLogic)),
+            recorder,
+            files,
+            initialized_tabs: HashSet::new(),
+            panels,
+            next_panel: BASE_PANELS as u64 + 1,
+            uploaded: None,
+            uploads: 0,
+            pasted: None,
+            progress_bars: HashMap::new(),
+            stream: LiveStream::new(Duration::from_millis(500)),
+            plot: Reactive::new(),
+            plot_cache: Lru::new(PLOT_CACHE_SIZE),
+            sample_cache: self.sample_cache,
+            storage: self.storage,
+            frames: FrameEncoder::new(config.binary_threshold, config.compression),
+            percentile_range: PercentileRange::default(),
+            zoom: None,
+            plot_size: None,
+            plot_resize: Debounced::new(PLOT_WIDTH_KEY, PLOT_RESIZE_DEBOUNCE, clock.clone()),
+            plot_theme: PlotTheme::default(),
+            datatable: DataTable::default(),
+            diagnostics: Reactive::new(),
+            history: History::new(HISTORY_LIMIT),
+            inserted_ui: InsertedUi::new(MAX_INSERTED_PLOTS),
+            removals: PendingRemovals::default(),
+            last_values: LastValues::default(),
+            links: InputLinks::default()
+                .both("text1", "text2", as_label, as_label)
+                .both("link_number:shiny.number", "link_slider", as_linked_value, as_linked_value),
+            tasks: TaskRegistry::default(),
+            markdown: MarkdownRenderer::default(),
+            markdown_preview: Debounced::new("markdown", MARKDOWN_DEBOUNCE, clock.clone()),
+            markdown_count: Throttled::new("markdown", MARKDOWN_COUNT_THROTTLE, clock.clone()),
+            scratch: Scratch::default(),
+            input_events: InputEvents::default(),
+            notifications: Notifications::default(),
+            validators,
+            language: Language::default(),
+            message_guard: MessageGuard::new(
+                config.message_burst,
+                config.messages_per_sec,
+                config.max_message_size,
+                config.max_violations,
+                clock.clone()
+            ),
+            clock,
+            registry: self.registry,
+            bookmarks: self.bookmarks,
+            reconnect: self.reconnect,
+            reconnect_token: None,
+            app_state: self.app_state,
+            worker_pool: self.worker_pool,
+            metrics: self.metrics,
+            initialize: |shiny, session| {
+                recording::record_init(shiny);
+                let logic = shiny.logic();
+                run(shiny, session, |shiny, session| logic.initialize(shiny, session));
+            },
+            update: |shiny, session| {
+                let start = Instant::now();
+                shiny.metrics.messages_received.fetch_add(1, Ordering::Relaxed);
+                shiny.input_count += 1;
+                recording::record_update(shiny);
+                let logic = shiny.logic();
+                run(shiny, session, |shiny, session| logic.update(shiny, session));
+                shiny.metrics.update_latency.observe(start.elapsed());
+                shiny.latency.record(&shiny.event, start.elapsed());
+            },
+            tick: |shiny, session| {
+                let logic = shiny.logic();
+                run(shiny, session, |shiny, session| logic.tick(shiny, session));
+            },
+            hb_interval: config.hb_interval(),
+            client_timeout: config.client_timeout(),
+            config,
         }
     }
 }
@@ -77,100 +819,992 @@ impl CustomServer {
 impl Actor for CustomServer {
     type Context = ShinyContext<Self>;
     fn started(&mut self, session: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        info!("session started");
+        self.metrics.active_sessions.fetch_add(1, Ordering::Relaxed);
         self.hb(session);
+        self.registry.register(&self.id, self.user.as_deref(), session.address());
+        if let Some(app_state) = &self.app_state {
+            app_state.do_send(Join {
+                id: self.id.clone(),
+                announcements: session.address().recipient(),
+                chat: session.address().recipient()
+            });
+        }
+        let events = self.input_events.subscribe();
+        self.tasks.spawn(log_input_changes(events).instrument(self.span.clone()));
+    }
+    fn stopped(&mut self, _session: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        if self.hb.elapsed() > self.client_timeout {
+            warn!("heartbeat timed out");
+        }
+        info!("session stopped");
+        self.metrics.active_sessions.fetch_sub(1, Ordering::Relaxed);
+        self.registry.unregister(&self.id);
+        if let Some(app_state) = &self.app_state {
+            app_state.do_send(Leave(self.id.clone()));
+        }
+        reconnect::release(self);
+        self.tasks.cancel_all();
+        progress::cancel_all(self);
+        self.files = None;
+    }
+}
+
+/// Asks a session for its scratch directory, `None` when it couldn't be
+/// created.
+#[derive(Message)]
+#[rtype(result = "Option<SessionFiles>")]
+pub struct GetFiles;
+
+impl Handler<GetFiles> for CustomServer {
+    type Result = MessageResult<GetFiles>;
+    fn handle(&mut self, _msg: GetFiles, _session: &mut Self::Context) -> Self::Result {
+        MessageResult(self.files.clone())
+    }
+}
+
+/// Asks a session for the language its text is rendered in.
+#[derive(Message)]
+#[rtype(result = "Language")]
+pub struct GetLanguage;
+
+impl Handler<GetLanguage> for CustomServer {
+    type Result = MessageResult<GetLanguage>;
+    fn handle(&mut self, _msg: GetLanguage, _session: &mut Self::Context) -> Self::Result {
+        MessageResult(self.language)
+    }
+}
+
+/// Asks a session for its current distributions.
+#[derive(Message)]
+#[rtype(result = "Distributions")]
+pub struct GetDistributions;
+
+/// The sample of every panel, by namespace.
+#[derive(MessageResponse)]
+pub struct Distributions(pub Vec<(String, Vec<f64>)>);
+
+impl Handler<GetDistributions> for CustomServer {
+    type Result = Distributions;
+    fn handle(&mut self, _msg: GetDistributions, _session: &mut Self::Context) -> Distributions {
+        Distributions(
+            self.panels
+                .iter()
+                .map(|panel| {
+                    (panel.namespace().to_string(), panel.sample.value().cloned().unwrap_or_default())
+                })
+                .collect()
+        )
+    }
+}
+
+/// Asks a session for its plot, to draw it for download.
+#[derive(Message)]
+#[rtype(result = "Figure")]
+pub struct GetFigure;
+
+impl Handler<GetFigure> for CustomServer {
+    type Result = MessageResult<GetFigure>;
+    fn handle(&mut self, _msg: GetFigure, _session: &mut Self::Context) -> Self::Result {
+        MessageResult(self.figure())
+    }
+}
+
+/// Asks a session for its markdown, rendered.
+#[derive(Message)]
+#[rtype(result = "RenderedMarkdown")]
+pub struct GetMarkdown;
+
+#[derive(MessageResponse)]
+pub struct RenderedMarkdown(pub String);
+
+impl Handler<GetMarkdown> for CustomServer {
+    type Result = RenderedMarkdown;
+    fn handle(&mut self, _msg: GetMarkdown, _session: &mut Self::Context) -> RenderedMarkdown {
+        let md_string = self.input.get_string("markdown").unwrap_or_default();
+        RenderedMarkdown(self.markdown.render(&md_string))
+    }
+}
+
+impl Handler<GetSessionInfo> for CustomServer {
+    type Result = SessionInfo;
+    fn handle(&mut self, _msg: GetSessionInfo, _session: &mut Self::Context) -> SessionInfo {
+        SessionInfo {
+            id: self.id.clone(),
+            connected_at: self.connected_at,
+            since_heartbeat: self.hb.elapsed(),
+            input_events: self.input_count,
+            parameters: self.panels.iter().map(|panel| panel.describe(&self.input)).collect(),
+        }
+    }
+}
+
+impl Handler<Disconnect> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, _msg: Disconnect, session: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        info!("disconnected by an administrator");
+        self.reconnect_token = None;
+        allow_reconnect(&mut MeteredSession::new(session, &self.metrics), json!(false));
+        session.close(Some(CloseReason {
+            code: CloseCode::Policy,
+            description: Some(String::from("Disconnected by an administrator")),
+        }));
+        session.stop();
+    }
+}
+
+impl Handler<Detach> for CustomServer {
+    type Result = Option<SavedSession>;
+    fn handle(&mut self, _msg: Detach, session: &mut Self::Context) -> Option<SavedSession> {
+        let _span = self.span.clone().entered();
+        info!("replaced by a reconnected session");
+        let saved = reconnect::detach(self);
+        allow_reconnect(&mut MeteredSession::new(session, &self.metrics), json!(false));
+        session.close(Some(CloseReason {
+            code: CloseCode::Normal,
+            description: Some(String::from("Reconnected elsewhere")),
+        }));
+        session.stop();
+        saved
+    }
+}
+
+impl Handler<SetUpload> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: SetUpload, session: &mut Self::Context) {
+        self.uploaded = Some(msg.0);
+        self.uploads += 1;
+        run(self, session, build_plot);
+    }
+}
+
+impl Handler<Announcement> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: Announcement, session: &mut Self::Context) {
+        let mut session = MeteredSession::new(session, &self.metrics);
+        self.notifications.show(&mut session, NotificationMode::Immediate, json!({
+            "html": escape_html(&msg.0),
+            "action": "",
+            "deps": [],
+            "closeButton": true,
+            "type": "message"
+        }));
+    }
+}
+
+impl Handler<Scheduled> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: Scheduled, session: &mut Self::Context) {
+        let mut session = MeteredSession::new(session, &self.metrics);
+        match msg {
+            Scheduled::Notice(text) => {
+                self.notifications.show(&mut session, NotificationMode::Immediate, json!({
+                    "html": escape_html(&text),
+                    "action": "",
+                    "deps": [],
+                    "closeButton": true,
+                    "type": "message"
+                }));
+            }
+            Scheduled::RefreshRuns => render_runs(self, &mut session),
+            Scheduled::Reload => send_custom_message(&mut session, "reload", json!({})),
+        }
+    }
+}
+
+impl Handler<ChatMessage> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, msg: ChatMessage, session: &mut Self::Context) {
+        chat::show(&mut MeteredSession::new(session, &self.metrics), &msg);
+    }
+}
+
+impl Handler<Restarting> for CustomServer {
+    type Result = ();
+    fn handle(&mut self, _msg: Restarting, session: &mut Self::Context) {
+        let mut metered = MeteredSession::new(session, &self.metrics);
+        self.notifications.show(&mut metered, NotificationMode::Immediate, json!({
+            "html": t!(self, "server.restarting"),
+            "action": "",
+            "deps": [],
+            "closeButton": false,
+            "id": "server_restarting",
+            "type": "warning"
+        }));
+        session.close(Some(CloseReason { code: CloseCode::Restart, description: None }));
+        session.stop();
     }
 }
 
-type CustomSession = ShinyContext<CustomServer>;
+pub type CustomSession = ShinyContext<CustomServer>;
 
-pub fn initialize(shiny: &mut CustomServer, session: &mut CustomSession) {
-    shiny.dist1 = sample_dist(
-        shiny.input.get_u64("n-1:shiny.number").unwrap_or(0),
-        shiny.input.get_f64("mean-1:shiny.number").unwrap_or(0.0),
-        shiny.input.get_f64("sd-1:shiny.number").unwrap_or(0.1)
+/// Runs `handler` against the websocket, counting what it sends and
+/// sending large messages as binary frames once the client asked for them.
+/// A panicking handler is reported rather than stopping the session.
+fn run(
+    shiny: &mut CustomServer,
+    session: &mut CustomSession,
+    handler: impl FnOnce(&mut CustomServer, &mut dyn Session)
+) {
+    let metrics = shiny.metrics.clone();
+    let mut session = shiny.frames.wrap(session);
+    let mut session = MeteredSession::new(&mut session, &metrics);
+    if let Err(caught) = panics::catch(|| handler(shiny, &mut session)) {
+        report_panic(shiny, &mut session, caught);
+    }
+}
+
+/// Logs a panic with its backtrace under a new error id, and shows the id
+/// to the user so the panic can be found from their report.
+fn report_panic(shiny: &mut CustomServer, session: &mut dyn Session, caught: Caught) {
+    let _span = shiny.span.clone().entered();
+    let error_id = generate_id();
+    shiny.metrics.handler_panics.fetch_add(1, Ordering::Relaxed);
+    let backtrace = caught.backtrace.map_or(String::new(), |backtrace| backtrace.to_string());
+    error!(error_id = %error_id, event = %shiny.event, panic = %caught.message, %backtrace, "handler panicked");
+    shiny.notifications.show(session, NotificationMode::Immediate, json!({
+        "html": t!(shiny, "error.report", id = error_id),
+        "action": "",
+        "deps": [],
+        "closeButton": true,
+        "type": "error"
+    }));
+}
+
+pub fn initialize(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let _span = shiny.span.clone().entered();
+    i18n::set_language(shiny);
+    let search = shiny.input.get_string(".clientdata_url_search").unwrap_or_default();
+    let user = shiny.user.as_deref();
+    if let Some(state) = bookmark::bookmark_id(&search).and_then(|id| shiny.bookmarks.get(user, id)) {
+        bookmark::restore(shiny, session, &state);
+    }
+    let state = bookmark::snapshot(shiny);
+    shiny.history.reset(state);
+    for index in 0..shiny.panels.len() {
+        dist_module::initialize(shiny, index);
+        dist_module::render_limits(shiny, session, index);
+    }
+    shiny.plot_size = plot_size(shiny);
+    allow_reconnect(session, json!("force"));
+    set_theme(shiny, session);
+    let tab = shiny.input.get_string("current_tab").unwrap_or_else(|| String::from("Simulation"));
+    show_tab(shiny, session, &tab);
+    render_seed(shiny, session);
+    render_account(shiny, session);
+    render_editor_controls(shiny, session);
+    set_stream_mode(shiny, session);
+    presets::render_choices(shiny, session, "");
+    initialize_markdown(shiny, session);
+    render_value(
+        session,
+        "download_dist",
+        json!(format!("download/dist.csv?session={}", shiny.id))
     );
-    shiny.dist2 = sample_dist(
-        shiny.input.get_u64("n-2:shiny.number").unwrap_or(0),
-        shiny.input.get_f64("mean-2:shiny.number").unwrap_or(0.0),
-        shiny.input.get_f64("sd-2:shiny.number").unwrap_or(0.1)
+    render_plot_download(shiny, session);
+    if shiny.language != Language::default() {
+        for index in 0..shiny.panels.len() {
+            dist_module::render_labels(shiny, session, index);
+        }
+    }
+    send_custom_message(
+        session,
+        "upload-url",
+        json!({ "url": format!("upload?session={}", shiny.id) })
     );
-    build_plot(session, &shiny.dist1, &shiny.dist2);
 }
 
-pub fn update(shiny: &mut CustomServer, session: &mut CustomSession) {
-    if changed!(shiny, ("markdown")) {
-        let md_string = shiny.input.get_string("markdown").unwrap_or_default();
-        if md_string.len() > 5000 {
-            show_notification(session, args!({
-                "html": "Exceeded 5,000 characters!",
-                "id": "markdown_warning",
-                "type": "error",
-                "closeButton": true
-            }));
+pub fn update(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let _span = shiny.span.clone().entered();
+    if !update_connection(shiny, session) {
+        return;
+    }
+    if changed!(shiny, (LANGUAGE_KEY)) {
+        i18n::set_language(shiny);
+        render_language(shiny, session);
+        // The tables and the axis labels have the language's separators.
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("current_tab")) {
+        let tab = shiny.input.get_string("current_tab").unwrap_or_default();
+        show_tab(shiny, session, &tab);
+    }
+    update_markdown(shiny, session);
+    if shiny.plot_resize.observe(&shiny.event) {
+        if let Some(session) = session.actor_context() {
+            session.run_later(shiny.plot_resize.wait(), |shiny, session| {
+                run(shiny, session, resize_plot);
+            });
         }
-        let render = markdown_to_html(&md_string, &ComrakOptions::default());
-        render_ui(session, "rendered_md", &render);
     }
     if changed!(shiny, ("insert_ui:shiny.action")) {
-        let dist1 = sample_dist(50, -1.0, 0.5);
-        let dist2 = sample_dist(50, -1.0, 0.5);
-        insert_ui(
+        require_role!(shiny, Role::Editor);
+        if shiny.inserted_ui.count("#insert_section") == MAX_INSERTED_PLOTS {
+            shiny.notifications.show(session, NotificationMode::Queued, json!({
+                "html": t!(shiny, "insert.max", max = MAX_INSERTED_PLOTS),
+                "action": "",
+                "deps": [],
+                "closeButton": true,
+                "type": "warning"
+            }));
+        }
+        let normal = Distribution::Normal(NormalParams { mean: -1.0, sd: 0.5 });
+        let mut rng = rand::thread_rng();
+        let dist1 = sample_dist(50, &normal, &mut rng);
+        let dist2 = sample_dist(50, &normal, &mut rng);
+        shiny.metrics.add_samples(100);
+        let id = shiny.inserted_ui.insert(
             session,
             "#insert_section",
             "afterBegin",
-            &get_plot(&dist1, &dist2)
-        )
+            &get_plot(&[(&dist1, "dist1: Normal"), (&dist2, "dist2: Normal")], PlotType::Histogram, &shiny.plot_options())
+        );
+        scroll_into_view(session, &format!("#{}", id));
+        render_inserted_count(shiny, session);
+    }
+    if changed!(shiny, ("simulate_large:shiny.action")) {
+        if let Some(session) = session.actor_context() {
+            simulate_large(shiny, session);
+        }
+    }
+    if changed!(shiny, ("bookmark:shiny.action")) {
+        let id = shiny.bookmarks.save(shiny.user.as_deref(), bookmark::snapshot(shiny));
+        send_custom_message(
+            session,
+            "update-query-string",
+            json!({ "search": format!("?bookmark={}", id) })
+        );
+        shiny.notifications.show(session, NotificationMode::Immediate, json!({
+            "html": t!(shiny, "bookmark.saved"),
+            "action": "",
+            "deps": [],
+            "closeButton": true,
+            "id": "bookmark_saved",
+            "type": "message"
+        }));
+    }
+    if changed!(shiny, ("save_preset:shiny.action")) {
+        presets::save(shiny, session);
+    }
+    if changed!(shiny, ("reset_defaults:shiny.action")) {
+        reset_defaults(shiny, session);
+    }
+    if changed!(shiny, ("runs_since")) {
+        render_runs(shiny, session);
+    }
+    if changed!(shiny, ("paste_data")) {
+        parse_pasted(shiny, session);
+    }
+    if changed!(shiny, ("paste_apply:shiny.action")) {
+        apply_pasted(shiny, session);
+    }
+    if changed!(shiny, ("preset")) {
+        presets::apply(shiny, session);
+    }
+    if changed!(shiny, ("restore_run")) {
+        let id = shiny.input.get("restore_run").and_then(|id| id.as_i64()).unwrap_or_default();
+        match shiny.storage.params(shiny.user.as_deref(), id) {
+            Ok(Some(params)) => bookmark::restore(shiny, session, &params),
+            Ok(None) => warn!(id, "no such run"),
+            Err(e) => warn!(error = %e, "run could not be loaded"),
+        }
+    }
+    if changed!(shiny, ("undo:shiny.action")) {
+        if let Some(changes) = shiny.history.undo() {
+            bookmark::restore(shiny, session, &changes);
+        }
+    }
+    if changed!(shiny, ("redo:shiny.action")) {
+        if let Some(changes) = shiny.history.redo() {
+            bookmark::restore(shiny, session, &changes);
+        }
+    }
+    if changed!(shiny, ("flash_title:shiny.action")) {
+        send_custom_message(session, "flash-title", json!({ "text": t!(shiny, "flash.title"), "times": 3 }));
+    }
+    if changed!(shiny, ("announce:shiny.action")) {
+        let text = shiny.input.get_string("announcement").unwrap_or_default();
+        let valid = shiny.validators.validate(session, "announcement", &json!(text)).is_ok();
+        if let (Some(app_state), true) = (&shiny.app_state, valid) {
+            app_state.do_send(Broadcast(Announcement(text)));
+        }
+    }
+    if changed!(shiny, ("chat_send:shiny.action")) {
+        chat::send(shiny, session);
+    }
+    if changed!(shiny, ("stream_mode")) {
+        set_stream_mode(shiny, session);
+    }
+    if changed!(shiny, ("stream_toggle:shiny.action")) {
+        require_role!(shiny, Role::Editor);
+        if let Some(session) = session.actor_context() {
+            if shiny.stream.is_running() {
+                shiny.stream.stop(session);
+            } else {
+                shiny.stream.start(session, |shiny, session| (shiny.tick)(shiny, session));
+            }
+            update_text_input(session, "stream_toggle", json!({ "label": stream_label(shiny) }));
+        }
+    }
+    if changed!(shiny, ("stream_rate:shiny.number")) {
+        require_role!(shiny, Role::Editor);
+        match shiny.inputs().get_u64("stream_rate:shiny.number") {
+            Ok(rate) => {
+                shiny.stream.set_rate(rate);
+                if let (true, Some(session)) = (shiny.stream.is_running(), session.actor_context()) {
+                    shiny.stream.start(session, |shiny, session| (shiny.tick)(shiny, session));
+                }
+            }
+            Err(e) => report_input_error(session, shiny.language, &e),
+        }
     }
     if changed!(shiny, ("remove_ui:shiny.action")) {
-        remove_ui(session, "#insert_section div")
-    }
-    if changed!(shiny, ("n-1:shiny.number", "mean-1:shiny.number", "sd-1:shiny.number")) {
-        let n = shiny.input.get_u64("n-1:shiny.number").unwrap_or(0);
-        if validate_range(session, n) {
-            shiny.dist1 = sample_dist(
-                n,
-                shiny.input.get_f64("mean-1:shiny.number").unwrap_or(0.0),
-                shiny.input.get_f64("sd-1:shiny.number").unwrap_or(0.1)
+        require_role!(shiny, Role::Editor);
+        let count = shiny.inserted_ui.count("#insert_section");
+        show_modal(session, &modal_dialog(
+            &t!(shiny, "insert.remove_title"),
+            &t!(shiny, "insert.remove_body", count = count),
+            &format!(
+                r#"<button id="cancel_remove" type="button" class="btn btn-default action-button">{}</button>
+<button id="confirm_remove" type="button" class="btn btn-danger action-button">{}</button>"#,
+                t!(shiny, "dialog.cancel"),
+                t!(shiny, "dialog.remove")
             )
+        ));
+    }
+    // The dialog's buttons are created anew each time and report 0 when
+    // bound, so only clicks count.
+    let clicked = |shiny: &CustomServer, key| shiny.input.get_u64(key).unwrap_or(0) > 0;
+    if changed!(shiny, ("cancel_remove:shiny.action")) && clicked(shiny, "cancel_remove:shiny.action") {
+        remove_modal(session);
+    }
+    if changed!(shiny, ("confirm_remove:shiny.action")) && clicked(shiny, "confirm_remove:shiny.action") {
+        require_role!(shiny, Role::Editor);
+        remove_modal(session);
+        shiny.removals.remove(session, "#insert_section div", |shiny, session| {
+            shiny.inserted_ui.clear("#insert_section");
+            render_inserted_count(shiny, session);
+        });
+    }
+    if changed!(shiny, ("notification_dismissed")) {
+        let id = shiny.input.get_string("notification_dismissed").unwrap_or_default();
+        shiny.notifications.dismissed(session, &id);
+    }
+    if changed!(shiny, ("latency")) {
+        latency::set_enabled(shiny, session);
+    }
+    if changed!(shiny, (latency::PONG_KEY)) {
+        latency::pong(shiny, session);
+    }
+    if changed!(shiny, ("remove_ui_done")) {
+        let id = shiny.input.get_string("remove_ui_done").unwrap_or_default();
+        if let Some(on_removed) = shiny.removals.acknowledge(&id) {
+            on_removed(shiny, session);
         }
-        build_plot(session, &shiny.dist1, &shiny.dist2);
     }
-    if changed!(shiny, ("n-2:shiny.number", "mean-2:shiny.number", "sd-2:shiny.number")) {
-        let n = shiny.input.get_u64("n-2:shiny.number").unwrap_or(0);
-        if validate_range(session, n) {
-            shiny.dist2 = sample_dist(
-                n,
-                shiny.input.get_f64("mean-2:shiny.number").unwrap_or(0.0),
-                shiny.input.get_f64("sd-2:shiny.number").unwrap_or(0.1)
-            )
+    if changed!(shiny, ("randomize_seed:shiny.action")) {
+        let seed = rand::random::<u32>() as u64;
+        shiny.input.insert(SEED_KEY, json!(seed));
+        update_text_input(session, "seed", json!({ "value": seed }));
+    }
+    if changed!(shiny, ("randomize_seed:shiny.action", SEED_KEY)) {
+        render_seed(shiny, session);
+    }
+    if changed!(shiny, ("datatable_sort")) {
+        let column = shiny.input.get_string("datatable_sort").unwrap_or_default();
+        if let Some(column) = SortColumn::from_input(&column) {
+            shiny.datatable.sort_by(column);
+            render_datatable(shiny, session);
         }
-        build_plot(session, &shiny.dist1, &shiny.dist2);
     }
-    if changed!(shiny, ("text1")) {
-        let val = shiny.input.get_string("text1").unwrap_or_default();
-        update_text_input(
-            session,
-            "text2",
-            json!({
-                "label": val
-            })
-        )
+    if changed!(shiny, ("datatable_page")) {
+        if let Some(page) = shiny.input.get_u64("datatable_page") {
+            shiny.datatable.set_page(page as usize);
+            render_datatable(shiny, session);
+        }
     }
-    if changed!(shiny, ("text2")) {
-        let val = shiny.input.get_string("text2").unwrap_or_default();
-        update_text_input(
-            session,
-            "text1",
-            json!({
-                "label": val
-            })
-        )
+    if changed!(shiny, ("percentile_range")) {
+        match shiny.inputs().get_json::<PercentileRange>("percentile_range") {
+            Ok(range) if range.is_valid() => {
+                shiny.percentile_range = range;
+                build_plot(shiny, session);
+            }
+            Ok(_) => report_input_error(session, shiny.language, &InputError::Invalid {
+                key: String::from("percentile_range"),
+                message: t!(shiny, "percentiles.invalid"),
+            }),
+            Err(e) => report_input_error(session, shiny.language, &e),
+        }
+    }
+    if changed!(shiny, ("plot1_brush")) {
+        let plot_type = PlotType::from_input(&shiny.input.get_string("plot_type").unwrap_or_default());
+        match shiny.inputs().get_json::<Brush>("plot1_brush") {
+            Ok(brush) => {
+                if let Some(range) = brush.value_range(plot_type) {
+                    shiny.zoom = Some(range);
+                    build_plot(shiny, session);
+                }
+            }
+            Err(e) => report_input_error(session, shiny.language, &e),
+        }
+    }
+    if changed!(shiny, ("reset_zoom:shiny.action")) {
+        shiny.zoom = None;
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("plot1_click")) {
+        // Box plots have the trace names on the x axis.
+        let coordinate = |value: &serde_json::Value| match value.as_f64() {
+            Some(x) => format!("{:.3}", x),
+            None => value.as_str().unwrap_or_default().to_string(),
+        };
+        match shiny.inputs().get_json::<[serde_json::Value; 2]>("plot1_click") {
+            Ok([x, y]) => render_text(
+                session,
+                "plot1_click_info",
+                &t!(shiny, "plot.click", x = coordinate(&x), y = coordinate(&y))
+            ),
+            Err(e) => report_input_error(session, shiny.language, &e),
+        }
+    }
+    if changed!(shiny, ("run_tests:shiny.action")) {
+        let sample = |index: usize| shiny.panels[index].sample.value().map_or(&[][..], |dist| &dist[..]);
+        let table = results_table(sample(0), sample(1));
+        render_ui(session, "test_results", &table);
+    }
+    if changed!(shiny, ("line_width", "opacity", "kde_overlay", "kde_manual", "outlier_method")) {
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("kde_bandwidth")) {
+        set_bandwidth_rule(shiny, session);
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("dark_mode")) {
+        set_theme(shiny, session);
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("plot_type")) {
+        build_plot(shiny, session);
+    }
+    if changed!(shiny, ("plot_width:shiny.number", "plot_height:shiny.number", "plot_dpi:shiny.number", "plot_format")) {
+        let mut valid = true;
+        for key in ["plot_width:shiny.number", "plot_height:shiny.number", "plot_dpi:shiny.number"] {
+            let value = json!(shiny.input.get_f64(key));
+            valid &= shiny.validators.validate(session, key, &value).is_ok();
+        }
+        if valid {
+            render_plot_download(shiny, session);
+        }
+    }
+    if changed!(shiny, ("add_panel:shiny.action")) {
+        dist_module::add(shiny, session);
+    }
+    if let Some(index) = dist_module::remove_clicked(shiny) {
+        dist_module::remove(shiny, session, index);
+    }
+    for index in 0..shiny.panels.len() {
+        dist_module::server(shiny, session, index);
+    }
+    if shiny.links.is_source(&shiny.event) {
+        follow_links(shiny, session);
     }
+    let state = bookmark::snapshot(shiny);
+    shiny.history.record(state);
 }
 
-pub fn tick(_shiny: &mut CustomServer, _session: &mut CustomSession) {
+/// Sets the settings back to what the page starts with: the panels, the
+/// seed, the plot with its density curves and outliers, the markdown
+/// extensions and the History filter. The
+/// client reports the values that changed, which updates the rest.
+fn reset_defaults(shiny: &mut CustomServer, session: &mut dyn Session) {
+    for index in 0..shiny.panels.len() {
+        dist_module::reset(shiny, session, index);
+    }
+    update_numeric_input(session, "seed", NumericUpdate { value: Some(None), ..Default::default() });
+    update_select(session, "plot_type", SelectUpdate {
+        selected: Some(String::from("histogram")),
+        ..Default::default()
+    });
+    let style = SeriesStyle::default();
+    update_slider(session, "line_width", SliderUpdate { value: Some(style.line_width), ..Default::default() });
+    update_slider(session, "opacity", SliderUpdate { value: Some(style.opacity), ..Default::default() });
+    update_checkbox(session, "kde_overlay", CheckboxUpdate { value: Some(false), ..Default::default() });
+    update_select(session, "kde_bandwidth", SelectUpdate {
+        selected: Some(String::from("scott")),
+        ..Default::default()
+    });
+    update_slider(session, "kde_manual", SliderUpdate { value: Some(KDE_MANUAL_DEFAULT), ..Default::default() });
+    update_select(session, "outlier_method", SelectUpdate {
+        selected: Some(String::from("none")),
+        ..Default::default()
+    });
+    for id in ["md_tables", "md_strikethrough", "md_footnotes", "md_tasklist"] {
+        update_checkbox(session, id, CheckboxUpdate { value: Some(true), ..Default::default() });
+    }
+    update_date(session, "runs_since", DateUpdate { value: Some(None), ..Default::default() });
+}
+
+/// Shows the bandwidth slider when the manual bandwidth is picked.
+fn set_bandwidth_rule(shiny: &CustomServer, session: &mut dyn Session) {
+    if shiny.inputs().get_json::<String>("kde_bandwidth").is_ok_and(|rule| rule == "manual") {
+        show_element(session, "#kde_manual_container");
+    } else {
+        hide_element(session, "#kde_manual_container");
+    }
 }
 
-pub fn create_server() -> CustomServer {
-    CustomServer::new(initialize, update, tick)
+/// Parses the data pasted in the Data tab, showing what went wrong below
+/// the box, or its columns in the column select.
+fn parse_pasted(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let text = shiny.input.get_string("paste_data").unwrap_or_default();
+    shiny.pasted = None;
+    if shiny.validators.validate(session, "paste_data", &json!(text)).is_ok() && !text.trim().is_empty() {
+        shiny.pasted = import::parse(&text).ok();
+    }
+    let (choices, schema) = match &shiny.pasted {
+        Some(table) => {
+            let choices = table.columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| (index.to_string(), column.name.clone()))
+                .collect();
+            let schema = t!(
+                shiny,
+                "import.columns",
+                columns = table.columns.len(),
+                rows = table.rows,
+                format = table.format.describe(shiny.language)
+            );
+            (choices, schema)
+        }
+        None => (Vec::new(), String::new()),
+    };
+    let selected = choices.first().map(|(index, _): &(String, String)| index.clone()).unwrap_or_default();
+    update_select(session, "paste_column", SelectUpdate {
+        choices: Some(choices),
+        selected: Some(selected),
+        ..Default::default()
+    });
+    render_text(session, "paste_schema", &schema);
+}
+
+/// Replaces the sample of the panel picked with the pasted column picked.
+fn apply_pasted(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let target = shiny.input.get_string("paste_target").unwrap_or_default();
+    let column = shiny.inputs().get_json::<String>("paste_column").ok().and_then(|index| index.parse::<usize>().ok());
+    let index = shiny.panels.iter().position(|panel| panel.namespace() == target);
+    let (index, column) = match (index, column.and_then(|column| shiny.pasted.as_ref()?.columns.get(column))) {
+        (Some(index), Some(column)) => (index, column),
+        _ => return,
+    };
+    let (name, values) = (column.name.clone(), column.values.clone());
+    shiny.notifications.show(session, NotificationMode::Immediate, json!({
+        "html": t!(shiny, "import.applied", column = escape_html(&name), panel = target),
+        "action": "",
+        "deps": [],
+        "closeButton": true,
+        "type": "message"
+    }));
+    dist_module::import(shiny, session, index, name, values);
+}
+
+/// Each text input labels the other.
+fn as_label(value: &Value) -> Option<Value> {
+    Some(json!({ "label": value }))
+}
+
+/// The linked number and slider set each other, within the slider's range.
+fn as_linked_value(value: &Value) -> Option<Value> {
+    let (min, max) = LINKED_RANGE;
+    value.as_f64().map(|x| json!({ "value": x.clamp(min, max) }))
+}
+
+/// Updates the inputs linked to the one that changed, if its value is
+/// valid.
+fn follow_links(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let key = shiny.event.clone();
+    let value = shiny.input.get(&key).cloned().unwrap_or(Value::Null);
+    shiny.input_events.publish(&key, value.clone());
+    if shiny.validators.validate(session, &key, &value).is_ok() {
+        let input = &shiny.input;
+        shiny.links.follow(session, &key, &value, |key| input.get(key).cloned());
+    }
+}
+
+/// Handles what every page's client reports about the connection itself
+/// and clicks on the Cancel button of progress bars, and counts the update
+/// as activity. Returns false when the update was rate limited or too large
+/// and must be ignored.
+pub fn update_connection(shiny: &mut CustomServer, session: &mut dyn Session) -> bool {
+    debug!(event = %shiny.event, "input changed");
+    idle::record_activity(shiny, session);
+    if !guard_message(shiny, session) {
+        return false;
+    }
+    if changed!(shiny, ("reconnect_token")) {
+        let token = shiny.input.get_string("reconnect_token").unwrap_or_default();
+        if let Some(session) = session.actor_context() {
+            reconnect::claim(shiny, session, &token);
+        }
+    }
+    if changed!(shiny, ("binary_encoding")) {
+        let encoding = shiny.input.get_string("binary_encoding").unwrap_or_default();
+        shiny.frames.encoding = Encoding::from_input(&encoding);
+        debug!(encoding = ?shiny.frames.encoding, "binary encoding negotiated");
+    }
+    if changed!(shiny, ("compression")) {
+        let supported = shiny.input.get("compression").and_then(|value| value.as_array());
+        shiny.frames.negotiate_compression(supported.map_or(&[][..], |values| &values[..]));
+        debug!(compression = ?shiny.frames.compression, "compression negotiated");
+    }
+    progress::observe_cancel(shiny);
+    true
+}
+
+/// Checks the input that just changed against the rate and size limits.
+/// Oversized inputs are dropped; rate limited ones are processed later, by
+/// `replay_rate_limited`. The user is told, once per run of rate limited
+/// messages; clients rejected too often are disconnected.
+fn guard_message(shiny: &mut CustomServer, session: &mut dyn Session) -> bool {
+    let size = shiny.input.get(&shiny.event).map_or(0, |value| value.to_string().len());
+    let verdict = shiny.message_guard.check(size);
+    match verdict {
+        Verdict::Accepted => {
+            shiny.message_guard.forget(&shiny.event);
+            shiny.scratch.remove("rate_limited");
+            return true;
+        }
+        Verdict::RateLimited => {
+            let key = shiny.event.clone();
+            if let (true, Some(session)) = (shiny.message_guard.defer(&key), session.actor_context()) {
+                session.run_later(shiny.message_guard.wait(), |shiny, session| {
+                    run(shiny, session, replay_rate_limited);
+                });
+            }
+            if shiny.scratch.get::<bool>("rate_limited").is_none() {
+                shiny.notifications.show(session, NotificationMode::Immediate, json!({
+                    "html": t!(shiny, "rate_limit.warning"),
+                    "action": "",
+                    "deps": [],
+                    "closeButton": true,
+                    "id": "rate_limit_warning",
+                    "type": "warning"
+                }));
+                shiny.scratch.insert("rate_limited", true);
+            }
+        }
+        Verdict::TooLarge => {
+            warn!(event = %shiny.event, size, "input too large, ignored");
+            // Nothing may read the value, so it's dropped from the pool.
+            let key = shiny.event.clone();
+            shiny.input.insert(&key, serde_json::Value::Null);
+            shiny.notifications.show(session, NotificationMode::Immediate, json!({
+                "html": t!(shiny, "message.too_large", max = shiny.message_guard.max_size()),
+                "action": "",
+                "deps": [],
+                "closeButton": true,
+                "id": "message_size_warning",
+                "type": "warning"
+            }));
+        }
+    }
+    shiny.metrics.messages_rejected.fetch_add(1, Ordering::Relaxed);
+    if shiny.message_guard.exhausted() {
+        warn!(?verdict, "too many messages rejected, disconnecting");
+        // Reconnecting would only start the flood over.
+        shiny.reconnect_token = None;
+        allow_reconnect(session, json!(false));
+        shiny.notifications.show(session, NotificationMode::Immediate, json!({
+            "html": t!(shiny, "message.disconnected"),
+            "action": "",
+            "deps": [],
+            "closeButton": false,
+            "id": "message_guard_disconnect",
+            "type": "error"
+        }));
+        if let Some(session) = session.actor_context() {
+            session.close(Some(CloseReason {
+                code: CloseCode::Policy,
+                description: Some(String::from("Too many rejected messages")),
+            }));
+            session.stop();
+        }
+    }
+    false
+}
+
+/// Processes the inputs rate limited earlier, each once and with the value
+/// it has now, for as long as the bucket has tokens. Ticks retry those
+/// still waiting.
+pub fn replay_rate_limited(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let event = shiny.event.clone();
+    let logic = shiny.logic();
+    while let Some(key) = shiny.message_guard.next_pending() {
+        debug!(%key, "processing rate limited input");
+        shiny.event = key;
+        logic.update(shiny, session);
+    }
+    shiny.event = event;
+}
+
+/// Sets up the markdown editor: its extensions, its warning and its
+/// download link.
+pub fn initialize_markdown(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let options = markdown_options(shiny);
+    shiny.markdown.set_options(options);
+    render_markdown_warning(shiny, session);
+    render_value(
+        session,
+        "download_md",
+        json!(format!("download/markdown.html?session={}", shiny.id))
+    );
+}
+
+/// Reacts to the markdown editor's inputs.
+pub fn update_markdown(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if changed!(shiny, ("md_tables", "md_strikethrough", "md_footnotes", "md_tasklist")) {
+        let options = markdown_options(shiny);
+        shiny.markdown.set_options(options);
+        render_markdown_preview(shiny, session);
+    }
+    if shiny.markdown_preview.observe(&shiny.event) {
+        let md_string = shiny.input.get_string("markdown").unwrap_or_default();
+        shiny.input_events.publish("markdown", json!(md_string));
+    }
+    if shiny.markdown_count.observe(&shiny.event) {
+        flush_markdown(shiny, session);
+        if let Some(session) = session.actor_context() {
+            for wait in [shiny.markdown_count.wait(), shiny.markdown_preview.wait()] {
+                session.run_later(wait, |shiny, session| {
+                    run(shiny, session, flush_markdown);
+                });
+            }
+        }
+    }
+}
+
+/// Reads the extension checkboxes of the markdown editor. Checkboxes the
+/// client didn't send yet count as unchecked.
+fn markdown_options(shiny: &CustomServer) -> MarkdownOptions {
+    let inputs = shiny.inputs();
+    let checked = |key| inputs.get_bool(key).unwrap_or(false);
+    MarkdownOptions {
+        tables: checked("md_tables"),
+        strikethrough: checked("md_strikethrough"),
+        footnotes: checked("md_footnotes"),
+        tasklist: checked("md_tasklist"),
+    }
+}
+
+fn render_markdown_preview(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let md_string = shiny.input.get_string("markdown").unwrap_or_default();
+    let start = Instant::now();
+    let render = shiny.markdown.render(&md_string);
+    debug!(elapsed = ?start.elapsed(), "markdown rendered");
+    render_ui(session, "rendered_md", &render);
+}
+
+/// Updates the character count and the markdown preview, each once its
+/// rate limit lets it.
+pub fn flush_markdown(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let count_ready = shiny.markdown_count.ready();
+    let preview_ready = shiny.markdown_preview.ready();
+    if !count_ready && !preview_ready {
+        return;
+    }
+    let md_string = shiny.input.get_string("markdown").unwrap_or_default();
+    let max_length = shiny.config.max_markdown_length;
+    if count_ready {
+        let warned = shiny.scratch.get::<bool>("markdown_warned").is_some();
+        let length = md_string.chars().count();
+        if length <= max_length && warned {
+            hide_element(session, "#markdown_warning");
+            shiny.scratch.remove("markdown_warned");
+        } else if length > max_length && !warned {
+            show_element(session, "#markdown_warning");
+            shiny.scratch.insert("markdown_warned", true);
+        }
+        render_markdown_count(shiny, session);
+    }
+    if preview_ready {
+        render_markdown_preview(shiny, session);
+    }
+}
+
+/// The banner shown while the text is over the limit.
+fn render_markdown_warning(shiny: &CustomServer, session: &mut dyn Session) {
+    let warning = t!(shiny, "markdown.exceeded", max = shiny.config.max_markdown_length);
+    render_text(session, "markdown_warning", &warning);
+}
+
+fn render_markdown_count(shiny: &CustomServer, session: &mut dyn Session) {
+    let length = shiny.input.get_string("markdown").unwrap_or_default().chars().count();
+    let count = t!(shiny, "markdown.count", count = length, max = shiny.config.max_markdown_length);
+    render_text(session, "markdown_count", &count);
+}
+
+/// Switches the page's stylesheet and the plots' colors to the theme picked
+/// with the `dark_mode` checkbox.
+fn set_theme(shiny: &mut CustomServer, session: &mut dyn Session) {
+    let dark = shiny.inputs().get_bool("dark_mode").unwrap_or(false);
+    shiny.plot_theme = PlotTheme::from_dark_mode(dark);
+    send_custom_message(session, "set-theme", json!({ "dark": dark }));
+}
+
+/// The size `plot1` should have for its current width. Hidden outputs
+/// report a width of 0, which keeps the previous size.
+fn plot_size(shiny: &CustomServer) -> Option<PlotSize> {
+    match shiny.input.get_u64(PLOT_WIDTH_KEY) {
+        Some(width) if width > 0 => Some(PlotSize::for_width(width as usize)),
+        _ => shiny.plot_size,
+    }
+}
+
+/// Rebuilds the plot for the width of `plot1` once it stopped changing.
+fn resize_plot(shiny: &mut CustomServer, session: &mut dyn Session) {
+    if shiny.plot_resize.ready() {
+        shiny.plot_size = plot_size(shiny);
+        build_plot(shiny, session);
+    }
+}
+
+pub fn tick(shiny: &mut CustomServer, session: &mut dyn Session) {
+    idle::check(shiny, session);
+    replay_rate_limited(shiny, session);
+    latency::tick(shiny, session);
+    flush_markdown(shiny, session);
+    resize_plot(shiny, session);
+    if shiny.stream.is_due() {
+        let last = shiny.stream.points().last().map_or(0.0, |(_, y)| *y);
+        let step = Distribution::Normal(NormalParams { mean: last, sd: 1.0 });
+        if let Some(y) = sample_dist(1, &step, &mut rand::thread_rng()).first() {
+            shiny.stream.push(*y);
+            shiny.metrics.add_samples(1);
+        }
+        let options = shiny.plot_options();
+        let plot = get_line_plot(shiny.stream.points(), &t!(shiny, "stream.series"), &options);
+        render_ui(session, "stream_plot", &plot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Driver;
+
+    #[test]
+    fn markdown_is_counted_in_characters() {
+        let config = Config { max_markdown_length: 6, ..Config::default() };
+        let mut driver = Driver::new(CustomServer::builder().config(config).build());
+        driver.set("markdown", json!("ñandú😀"));
+        assert_eq!(driver.session.output("markdown_count"), Some("6 / 6 characters"));
+        let warnings = driver.session.custom_messages("toggle-element")
+            .into_iter()
+            .filter(|message| message["selector"] == "#markdown_warning")
+            .count();
+        assert_eq!(warnings, 0, "not over the limit");
+    }
 }
@@ -0,0 +1,60 @@
+//! Graceful shutdown: on SIGTERM or Ctrl-C every session is told the
+//! server is restarting and its websocket is closed before the HTTP server
+//! stops.
+
+use actix::{ Actor, Context, Handler, Message, ResponseFuture };
+use futures_util::future::join_all;
+
+use super::registry::SessionRegistry;
+
+/// Sent to every session before the server stops.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct Restarting;
+
+/// Notifies every live session, resolving once all of them handled it.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Shutdown;
+
+pub struct ShutdownCoordinator {
+    registry: SessionRegistry,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(registry: SessionRegistry) -> Self {
+        ShutdownCoordinator { registry }
+    }
+}
+
+impl Actor for ShutdownCoordinator {
+    type Context = Context<Self>;
+}
+
+impl Handler<Shutdown> for ShutdownCoordinator {
+    type Result = ResponseFuture<()>;
+    fn handle(&mut self, _msg: Shutdown, _ctx: &mut Self::Context) -> Self::Result {
+        let sessions = self.registry.all();
+        tracing::info!(sessions = sessions.len(), "shutting down");
+        Box::pin(async move {
+            join_all(sessions.iter().map(|session| session.send(Restarting))).await;
+        })
+    }
+}
+
+/// Resolves on SIGTERM or Ctrl-C.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{ signal, SignalKind };
+        let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+        tokio::select! {
+            _ = terminate.recv() => {},
+            _ = tokio::signal::ctrl_c() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -0,0 +1,48 @@
+use rand::distributions::Distribution;
+use statrs::distribution::Normal;
+
+use super::i18n::{ t, translate, Language };
+use super::messages::render_text;
+use super::progress::{ with_progress, Cancelled, Steps };
+use super::server::{ CustomServer, CustomSession };
+
+pub const LARGE_SAMPLE: usize = 10_000_000;
+const BATCH: usize = 100_000;
+
+/// Mean and standard deviation of `LARGE_SAMPLE` draws of a standard
+/// normal, a batch per step, reporting progress in `language`.
+fn sample_large(steps: &mut Steps, language: Language) -> Result<(f64, f64), Cancelled> {
+    let normal = Normal::new(0.0, 1.0).expect("valid parameters");
+    let mut rng = rand::thread_rng();
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    for batch in 0..LARGE_SAMPLE / BATCH {
+        for x in normal.sample_iter(&mut rng).take(BATCH) {
+            sum += x;
+            sum_sq += x * x;
+        }
+        steps.advance(translate(language, "simulation.progress", &[
+            ("done", ((batch + 1) * BATCH).to_string()),
+            ("total", LARGE_SAMPLE.to_string()),
+        ]))?;
+    }
+    let n = LARGE_SAMPLE as f64;
+    let mean = sum / n;
+    Ok((mean, (sum_sq / n - mean * mean).sqrt()))
+}
+
+/// Samples `LARGE_SAMPLE` values with a progress bar in `large_progress`,
+/// showing the result in `large_result` unless the user cancelled.
+pub fn simulate_large(shiny: &mut CustomServer, session: &mut CustomSession) {
+    let language = shiny.language;
+    let work = move |steps: &mut Steps| sample_large(steps, language);
+    with_progress(shiny, session, "large_progress", LARGE_SAMPLE / BATCH, work, |shiny, session, result| {
+        let text = match result {
+            Ok((mean, sd)) => {
+                shiny.metrics.add_samples(LARGE_SAMPLE as u64);
+                t!(shiny, "simulation.result", mean = format!("{:.5}", mean), sd = format!("{:.5}", sd), n = LARGE_SAMPLE)
+            }
+            Err(Cancelled) => t!(shiny, "simulation.cancelled"),
+        };
+        render_text(session, "large_result", &text);
+    });
+}
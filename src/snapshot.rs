@@ -0,0 +1,211 @@
+//! Golden files of what sessions show. A `Snapshot` gathers outputs as a
+//! `MockSession` received them, UI inserted into the page and the plot as
+//! the download draws it, into text that is compared with a file checked
+//! in next to the tests. A change to `plot.rs` or `server.rs` that changes
+//! what the client gets then fails the comparison, showing the first line
+//! that differs, and so does a missing file. With `UPDATE_GOLDEN=1` set,
+//! the file is written instead, to be reviewed in the diff.
+//!
+//! Samples are only the same from run to run with a seed. The ids made up
+//! for each render, such as those of Plotly's divs, are replaced by
+//! `id-1`, `id-2` and so on, in the order they first appear.
+//!
+//! ```no_run
+//! # use rusty_shiny::config::Config;
+//! # use rusty_shiny::plot_export::ImageSize;
+//! # use rusty_shiny::server::CustomServer;
+//! # use rusty_shiny::snapshot::Snapshot;
+//! # use rusty_shiny::testing::Driver;
+//! # use serde_json::json;
+//! let mut driver = Driver::with_inputs(CustomServer::builder().build(), &[("seed:shiny.number", json!(42))]);
+//! driver.set("markdown", json!("# Title"));
+//! Snapshot::default()
+//!     .outputs(&driver.session, &["summary", "plot1", "rendered_md"])
+//!     .plot_svg(&driver.shiny, ImageSize { width: 640, height: 480, dpi: 96 }, &Config::default().plot_font)
+//!     .assert_golden("tests/golden/seeded_session.txt");
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use regex::Regex;
+
+use super::plot_export::{ self, ImageFormat, ImageSize };
+use super::server::CustomServer;
+use super::testing::MockSession;
+
+/// Environment variable rewriting the golden files rather than comparing.
+pub const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+/// Client-visible output, one section per output, in the order added.
+#[derive(Default)]
+pub struct Snapshot {
+    sections: Vec<(String, String)>,
+}
+
+/// `text` with the ids generated for a render replaced by stable ones.
+/// Generated ids are those of `id` attributes and `"id"` fields that are
+/// at least 12 letters and digits, with a digit among them.
+pub fn normalize(text: &str) -> String {
+    let pattern = Regex::new(r#"(?:\bid="|"id":\s*")([A-Za-z0-9]{12,})""#).expect("valid pattern");
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    let mut order = Vec::new();
+    for captures in pattern.captures_iter(text) {
+        let id = captures.get(1).expect("one group").as_str();
+        if id.chars().any(|c| c.is_ascii_digit()) && !ids.contains_key(id) {
+            ids.insert(id, format!("id-{}", ids.len() + 1));
+            order.push(id);
+        }
+    }
+    // Longer ids first, so no id is replaced inside another.
+    order.sort_by_key(|id| std::cmp::Reverse(id.len()));
+    order.iter().fold(text.to_string(), |text, id| text.replace(id, &ids[id]))
+}
+
+impl Snapshot {
+    /// Adds `text` as section `name`.
+    pub fn section(mut self, name: &str, text: &str) -> Self {
+        self.sections.push((name.to_string(), normalize(text)));
+        self
+    }
+
+    /// Adds the latest value of each of `ids`, or of its error. Outputs
+    /// never rendered are left empty.
+    pub fn outputs(self, session: &MockSession, ids: &[&str]) -> Self {
+        ids.iter().fold(self, |snapshot, id| {
+            let text = match (session.output(id), session.output_error(id)) {
+                (_, Some(error)) => format!("error: {}", error),
+                (Some(value), None) => value.to_string(),
+                (None, None) => String::new(),
+            };
+            snapshot.section(&format!("output {}", id), &text)
+        })
+    }
+
+    /// Adds the HTML inserted at `selector`, in the order it was sent.
+    pub fn inserted(self, session: &MockSession, selector: &str) -> Self {
+        let html = session.inserted(selector).join("\n");
+        self.section(&format!("inserted {}", selector), &html)
+    }
+
+    /// Adds `plot1` as an SVG of `size`, with the text in the font at
+    /// `font`. Panics when it can't be drawn.
+    pub fn plot_svg(self, shiny: &CustomServer, size: ImageSize, font: &str) -> Self {
+        let svg = plot_export::render(&shiny.figure(), ImageFormat::Svg, size, font)
+            .unwrap_or_else(|e| panic!("plot could not be drawn: {}", e));
+        self.section("plot svg", &String::from_utf8_lossy(&svg))
+    }
+
+    pub fn text(&self) -> String {
+        self.sections
+            .iter()
+            .map(|(name, text)| format!("=== {} ===\n{}\n", name, text))
+            .collect()
+    }
+
+    /// Compares the snapshot with the golden file at `path`, panicking at
+    /// the first line that differs or when there's no file. Writes the
+    /// file instead when `UPDATE_GOLDEN` is set.
+    pub fn assert_golden(&self, path: impl AsRef<Path>) {
+        let update = std::env::var_os(UPDATE_VAR).is_some_and(|value| value != "0");
+        self.compare(path.as_ref(), update);
+    }
+
+    fn compare(&self, path: &Path, update: bool) {
+        let actual = self.text();
+        if update {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+            }
+            std::fs::write(path, &actual).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            return;
+        }
+        let expected = match std::fs::read_to_string(path) {
+            Ok(expected) => expected,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => panic!(
+                "{} is missing. Run with {}=1 to write it.",
+                path.display(),
+                UPDATE_VAR
+            ),
+            Err(e) => panic!("{}: {}", path.display(), e),
+        };
+        if expected == actual {
+            return;
+        }
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = actual.lines();
+        for line in 1.. {
+            let (expected, actual) = (expected_lines.next(), actual_lines.next());
+            if expected.is_none() && actual.is_none() {
+                panic!("{} differs in its line endings", path.display());
+            }
+            if expected != actual {
+                panic!(
+                    "{} differs at line {}:\n expected: {}\n   actual: {}\nRerun with {}=1 to accept the new output.",
+                    path.display(),
+                    line,
+                    expected.unwrap_or("<end of file>"),
+                    actual.unwrap_or("<end of file>"),
+                    UPDATE_VAR
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::{ json, Value };
+    use super::*;
+    use crate::config::Config;
+    use crate::dist_module::SEED_KEY;
+    use crate::testing::Driver;
+
+    fn golden(name: &str) -> String {
+        format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn seeded_session() -> Driver {
+        let mut inputs = vec![(SEED_KEY.to_string(), json!(42))];
+        for (panel, dist_type) in [("dist1", "normal"), ("dist2", "gamma")] {
+            inputs.extend([
+                (format!("{}-dist_type", panel), json!(dist_type)),
+                (format!("{}-n:shiny.number", panel), json!(200)),
+                (format!("{}-mean:shiny.number", panel), json!(2)),
+                (format!("{}-sd:shiny.number", panel), json!(1.5)),
+            ]);
+        }
+        let inputs: Vec<(&str, Value)> = inputs.iter().map(|(key, value)| (key.as_str(), value.clone())).collect();
+        Driver::with_inputs(CustomServer::builder().build(), &inputs)
+    }
+
+    #[test]
+    fn seeded_session_matches_golden_file() {
+        let mut driver = seeded_session();
+        driver.set("markdown", json!("# Title\n\nSome *emphasis* and `code`."));
+        driver.advance(Duration::from_millis(300));
+        Snapshot::default()
+            .outputs(&driver.session, &["active_seed", "summary_table", "plot1", "markdown_count", "rendered_md"])
+            .assert_golden(golden("seeded_session.txt"));
+    }
+
+    #[test]
+    fn plot_svg_matches_golden_file() {
+        let driver = seeded_session();
+        Snapshot::default()
+            .plot_svg(&driver.shiny, ImageSize { width: 640, height: 480, dpi: 96 }, &Config::default().plot_font)
+            .assert_golden(golden("plot.svg.txt"));
+    }
+
+    #[test]
+    fn generated_ids_are_numbered_in_order() {
+        let text = r#"<div id="a1b2c3d4e5f6"></div><div id="short1"></div>{"id": "f6e5d4c3b2a1"} a1b2c3d4e5f6"#;
+        assert_eq!(normalize(text), r#"<div id="id-1"></div><div id="short1"></div>{"id": "id-2"} id-1"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing")]
+    fn missing_golden_files_fail() {
+        Snapshot::default().section("a", "b").compare(Path::new(&golden("missing.txt")), false);
+    }
+}
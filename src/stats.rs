@@ -0,0 +1,268 @@
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::format::NumberFormat;
+use super::i18n::{ translate, Language };
+
+/// Samples at least this long are summarized on all cores.
+const PARALLEL_MIN: usize = 100_000;
+
+/// Most outliers listed per series.
+const OUTLIERS_LISTED: usize = 50;
+
+/// Descriptive statistics of a sample.
+#[derive(Serialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub sd: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+    pub skewness: f64,
+}
+
+impl Summary {
+    /// `None` for an empty sample.
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        let parallel = values.len() >= PARALLEL_MIN;
+        let sum = |f: &(dyn Fn(f64) -> f64 + Sync)| -> f64 {
+            if parallel {
+                values.par_iter().map(|x| f(*x)).sum()
+            } else {
+                values.iter().map(|x| f(*x)).sum()
+            }
+        };
+        let mean = sum(&|x| x) / n;
+        let (m2, m3) = (sum(&|x| (x - mean).powi(2)) / n, sum(&|x| (x - mean).powi(3)) / n);
+        let mut sorted = values.to_vec();
+        if parallel {
+            sorted.par_sort_unstable_by(|a, b| a.total_cmp(b));
+        } else {
+            sorted.sort_by(|a, b| a.total_cmp(b));
+        }
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        };
+        Some(Summary {
+            mean,
+            sd: if values.len() > 1 { (m2 * n / (n - 1.0)).sqrt() } else { 0.0 },
+            median,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            skewness: if m2 > 0.0 { m3 / m2.powf(1.5) } else { 0.0 },
+        })
+    }
+}
+
+/// The `p`th quantile (0 to 1) of a sorted sample, interpolating linearly
+/// between the closest ranks.
+pub fn quantile(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p.clamp(0.0, 1.0) * last as f64;
+    let below = rank.floor() as usize;
+    let above = rank.ceil() as usize;
+    Some(sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64))
+}
+
+/// How the bandwidth of a kernel density estimate is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Bandwidth {
+    /// Scott's rule, 1.06 σ n^(-1/5), best for normal samples.
+    #[default]
+    Scott,
+    /// Silverman's rule of thumb, 0.9 min(σ, IQR / 1.34) n^(-1/5), which
+    /// oversmooths skewed and multimodal samples less.
+    Silverman,
+    /// A bandwidth in the unit of the values.
+    Manual(f64),
+}
+
+impl Bandwidth {
+    /// The bandwidth picked with the `kde_bandwidth` select, `manual`
+    /// taking the value of the slider.
+    pub fn from_inputs(rule: &str, manual: f64) -> Self {
+        match rule {
+            "silverman" => Bandwidth::Silverman,
+            "manual" => Bandwidth::Manual(manual),
+            _ => Bandwidth::Scott,
+        }
+    }
+
+    /// The bandwidth for `values`, 1 when the rule gives none, as for a
+    /// constant sample.
+    fn of(&self, values: &[f64], summary: &Summary) -> f64 {
+        let n = values.len() as f64;
+        let h = match self {
+            Bandwidth::Scott => 1.06 * summary.sd * n.powf(-0.2),
+            Bandwidth::Silverman => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let iqr = match (quantile(&sorted, 0.75), quantile(&sorted, 0.25)) {
+                    (Some(q3), Some(q1)) => q3 - q1,
+                    _ => 0.0,
+                };
+                let spread = if iqr > 0.0 { summary.sd.min(iqr / 1.34) } else { summary.sd };
+                0.9 * spread * n.powf(-0.2)
+            }
+            Bandwidth::Manual(h) => *h,
+        };
+        if h > 0.0 && h.is_finite() { h } else { 1.0 }
+    }
+}
+
+/// Gaussian kernel density estimate of `values` at `points` evenly spaced
+/// positions spanning the sample, three bandwidths past either end.
+pub fn density(values: &[f64], points: usize, bandwidth: Bandwidth) -> Vec<(f64, f64)> {
+    let summary = match Summary::of(values) {
+        Some(summary) => summary,
+        None => return vec![],
+    };
+    let n = values.len() as f64;
+    let bandwidth = bandwidth.of(values, &summary);
+    let (from, to) = (summary.min - 3.0 * bandwidth, summary.max + 3.0 * bandwidth);
+    let step = (to - from) / (points.max(2) - 1) as f64;
+    let norm = n * bandwidth * (2.0 * std::f64::consts::PI).sqrt();
+    (0..points.max(2))
+        .map(|i| {
+            let x = from + i as f64 * step;
+            let y = values
+                .iter()
+                .map(|v| (-0.5 * ((x - v) / bandwidth).powi(2)).exp())
+                .sum::<f64>() / norm;
+            (x, y)
+        })
+        .collect()
+}
+
+/// How values are told apart as outliers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutlierMethod {
+    /// More than 1.5 interquartile ranges past the quartiles, as box plots
+    /// draw them.
+    Iqr,
+    /// More than 3 standard deviations from the mean.
+    ZScore,
+}
+
+impl OutlierMethod {
+    /// The method picked with the `outlier_method` select, `None` for
+    /// `none`.
+    pub fn from_input(name: &str) -> Option<Self> {
+        match name {
+            "iqr" => Some(OutlierMethod::Iqr),
+            "zscore" => Some(OutlierMethod::ZScore),
+            _ => None,
+        }
+    }
+}
+
+/// The outliers of `values` by `method`, in increasing order.
+pub fn outliers(values: &[f64], method: OutlierMethod) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let (low, high) = match method {
+        OutlierMethod::Iqr => match (quantile(&sorted, 0.25), quantile(&sorted, 0.75)) {
+            (Some(q1), Some(q3)) => (q1 - 1.5 * (q3 - q1), q3 + 1.5 * (q3 - q1)),
+            _ => return vec![],
+        },
+        OutlierMethod::ZScore => match Summary::of(values) {
+            Some(s) => (s.mean - 3.0 * s.sd, s.mean + 3.0 * s.sd),
+            None => return vec![],
+        },
+    };
+    sorted.retain(|x| *x < low || *x > high);
+    sorted
+}
+
+/// The number of outliers of the labelled series, opening to the list of
+/// each one's, written with `format` in `language`. Long lists are cut
+/// short.
+pub fn outliers_panel(
+    series: &[(&[f64], &str)],
+    method: OutlierMethod,
+    format: NumberFormat,
+    language: Language
+) -> String {
+    let found: Vec<(&str, Vec<f64>)> = series
+        .iter()
+        .map(|(values, label)| (*label, outliers(values, method)))
+        .collect();
+    let count: usize = found.iter().map(|(_, outliers)| outliers.len()).sum();
+    let items: String = found
+        .iter()
+        .map(|(label, outliers)| {
+            let mut listed: Vec<String> = outliers
+                .iter()
+                .take(OUTLIERS_LISTED)
+                .map(|x| format.format(*x, language))
+                .collect();
+            if outliers.len() > OUTLIERS_LISTED {
+                let more = (outliers.len() - OUTLIERS_LISTED).to_string();
+                listed.push(translate(language, "outliers.more", &[("count", more)]));
+            }
+            if listed.is_empty() {
+                listed.push(String::from("-"));
+            }
+            format!("<li><strong>{}</strong>: {}</li>", escape_html(label), escape_html(&listed.join("; ")))
+        })
+        .collect();
+    format!(
+        "<details class=\"outliers\"><summary>{}</summary><ul>{}</ul></details>",
+        translate(language, "outliers.count", &[("count", count.to_string())]),
+        items
+    )
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Builds a bootstrap table. Cells are escaped.
+pub fn html_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let row = |cells: &mut dyn Iterator<Item = &str>, tag: &str| {
+        let cells: String = cells
+            .map(|cell| format!("<{tag}>{}</{tag}>", escape_html(cell), tag = tag))
+            .collect();
+        format!("<tr>{}</tr>", cells)
+    };
+    let body: String = rows
+        .iter()
+        .map(|cells| row(&mut cells.iter().map(String::as_str), "td"))
+        .collect();
+    format!(
+        "<table class=\"table table-sm\"><thead>{}</thead><tbody>{}</tbody></table>",
+        row(&mut headers.iter().copied(), "th"),
+        body
+    )
+}
+
+/// One row per labelled series, with the statistics written with `format`
+/// in `language`.
+pub fn summary_table(series: &[(&[f64], &str)], format: NumberFormat, language: Language) -> String {
+    let rows: Vec<Vec<String>> = series
+        .iter()
+        .map(|(values, label)| {
+            let mut cells = vec![label.to_string()];
+            match Summary::of(values) {
+                Some(s) => cells.extend(
+                    [s.mean, s.sd, s.median, s.min, s.max, s.skewness]
+                        .iter()
+                        .map(|x| format.format(*x, language))
+                ),
+                None => cells.extend((0..6).map(|_| String::from("-"))),
+            }
+            cells
+        })
+        .collect();
+    html_table(&["", "Mean", "SD", "Median", "Min", "Max", "Skewness"], &rows)
+}
@@ -0,0 +1,220 @@
+//! Past simulation runs and named presets, kept in SQLite so they survive a
+//! restart. A run is recorded every time a panel draws a new sample because
+//! its inputs changed, with the inputs it was drawn with so it can be
+//! restored.
+
+use std::path::Path;
+use std::sync::{ Arc, Mutex };
+use chrono::{ DateTime, NaiveDate, Utc };
+use rusqlite::{ params, Connection, OptionalExtension };
+use serde_json::Value;
+
+use super::stats::{ escape_html, Summary };
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    user TEXT,
+    panel TEXT NOT NULL,
+    description TEXT NOT NULL,
+    params TEXT NOT NULL,
+    seed INTEGER,
+    n INTEGER NOT NULL,
+    mean REAL NOT NULL,
+    sd REAL NOT NULL,
+    median REAL NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS presets (
+    user TEXT,
+    name TEXT NOT NULL,
+    params TEXT NOT NULL
+);";
+
+pub struct Run {
+    pub id: i64,
+    pub time: DateTime<Utc>,
+    pub panel: String,
+    /// The panel's settings, as `DistPanel::describe` puts them.
+    pub description: String,
+    pub seed: Option<u64>,
+    pub n: u64,
+    pub mean: f64,
+    pub sd: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// A connection shared by every session. Sessions built without one keep
+/// their runs in a private in-memory database.
+#[derive(Clone)]
+pub struct Storage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::from_connection(Connection::open_in_memory().expect("in-memory database"))
+            .expect("runs table")
+    }
+}
+
+impl Storage {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Storage::from_connection(Connection::open(path)?)
+    }
+
+    fn from_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Storage { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    /// Records a run of `user`'s, where `params` are the inputs of the
+    /// panel it was drawn with.
+    pub fn record(
+        &self,
+        user: Option<&str>,
+        panel: &str,
+        description: &str,
+        params: &Value,
+        seed: Option<u64>,
+        values: &[f64]
+    ) -> rusqlite::Result<()> {
+        let summary = match Summary::of(values) {
+            Some(summary) => summary,
+            None => return Ok(()),
+        };
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO runs (time, user, panel, description, params, seed, n, mean, sd, median, min, max)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                Utc::now().to_rfc3339(),
+                user,
+                panel,
+                description,
+                params.to_string(),
+                // SQLite integers are signed, seeds round trip through the cast.
+                seed.map(|seed| seed as i64),
+                values.len() as i64,
+                summary.mean,
+                summary.sd,
+                summary.median,
+                summary.min,
+                summary.max
+            ]
+        )?;
+        Ok(())
+    }
+
+    /// The latest `limit` runs of `user`, newest first, from the start of
+    /// day `since` (UTC) on.
+    pub fn runs(&self, user: Option<&str>, since: Option<NaiveDate>, limit: usize) -> rusqlite::Result<Vec<Run>> {
+        let connection = self.connection.lock().unwrap();
+        // Times are RFC 3339 in UTC, so they sort after the date they start
+        // with.
+        let mut statement = connection.prepare(
+            "SELECT id, time, panel, description, seed, n, mean, sd, median, min, max
+             FROM runs WHERE user IS ?1 AND (?2 IS NULL OR time >= ?2) ORDER BY id DESC LIMIT ?3"
+        )?;
+        let since = since.map(|since| since.format("%Y-%m-%d").to_string());
+        let runs = statement.query_map(params![user, since, limit as i64], |row| {
+            let time: String = row.get(1)?;
+            Ok(Run {
+                id: row.get(0)?,
+                time: DateTime::parse_from_rfc3339(&time)
+                    .map(|time| time.with_timezone(&Utc))
+                    .unwrap_or_default(),
+                panel: row.get(2)?,
+                description: row.get(3)?,
+                seed: row.get::<_, Option<i64>>(4)?.map(|seed| seed as u64),
+                n: row.get::<_, i64>(5)? as u64,
+                mean: row.get(6)?,
+                sd: row.get(7)?,
+                median: row.get(8)?,
+                min: row.get(9)?,
+                max: row.get(10)?,
+            })
+        })?;
+        runs.collect()
+    }
+
+    /// The inputs run `id` of `user`'s was drawn with.
+    pub fn params(&self, user: Option<&str>, id: i64) -> rusqlite::Result<Option<Value>> {
+        let params: Option<String> = self.connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT params FROM runs WHERE id = ?1 AND user IS ?2",
+                params![id, user],
+                |row| row.get(0)
+            )
+            .optional()?;
+        Ok(params.and_then(|params| serde_json::from_str(&params).ok()))
+    }
+
+    /// Saves `params` as `user`'s preset `name`, replacing any preset of
+    /// theirs with that name.
+    pub fn save_preset(&self, user: Option<&str>, name: &str, params: &Value) -> rusqlite::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM presets WHERE user IS ?1 AND name = ?2", params![user, name])?;
+        transaction.execute(
+            "INSERT INTO presets (user, name, params) VALUES (?1, ?2, ?3)",
+            params![user, name, params.to_string()]
+        )?;
+        transaction.commit()
+    }
+
+    /// Names of `user`'s presets, in alphabetical order.
+    pub fn presets(&self, user: Option<&str>) -> rusqlite::Result<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT name FROM presets WHERE user IS ?1 ORDER BY name")?;
+        let names = statement.query_map(params![user], |row| row.get(0))?;
+        names.collect()
+    }
+
+    /// The inputs saved as `user`'s preset `name`.
+    pub fn preset(&self, user: Option<&str>, name: &str) -> rusqlite::Result<Option<Value>> {
+        let params: Option<String> = self.connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT params FROM presets WHERE user IS ?1 AND name = ?2",
+                params![user, name],
+                |row| row.get(0)
+            )
+            .optional()?;
+        Ok(params.and_then(|params| serde_json::from_str(&params).ok()))
+    }
+}
+
+/// A row per run, with a button restoring its inputs. www/app.js reports
+/// clicks on the buttons as the `restore_run` input.
+pub fn runs_table(runs: &[Run]) -> String {
+    let rows: String = runs
+        .iter()
+        .map(|run| {
+            format!(
+                r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.4}</td><td>{:.4}</td><td>{:.4}</td><td>{:.4}</td><td>{:.4}</td>
+<td><button type="button" class="btn btn-default btn-sm" data-restore-run="{}">Restore</button></td></tr>"#,
+                run.time.format("%Y-%m-%d %H:%M:%S"),
+                escape_html(&run.description),
+                run.seed.map_or(String::from("random"), |seed| seed.to_string()),
+                run.n,
+                run.mean,
+                run.sd,
+                run.median,
+                run.min,
+                run.max,
+                run.id
+            )
+        })
+        .collect();
+    format!(
+        "<table class=\"table table-sm\"><thead><tr><th>Time (UTC)</th><th>Panel</th><th>Seed</th><th>n</th>\
+         <th>Mean</th><th>SD</th><th>Median</th><th>Min</th><th>Max</th><th></th></tr></thead><tbody>{}</tbody></table>",
+        rows
+    )
+}
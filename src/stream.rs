@@ -0,0 +1,90 @@
+//! Live streaming demo: while streaming, `tick` appends a random point to
+//! a rolling window and re-renders `stream_plot`. Ticks come from a timer
+//! running at the rate picked in the UI, besides the heartbeat's.
+
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+use actix::{ AsyncContext, SpawnHandle };
+
+use super::server::{ CustomServer, CustomSession };
+
+pub const STREAM_WINDOW: usize = 100;
+const MIN_RATE_MS: u64 = 100;
+
+pub struct LiveStream {
+    window: VecDeque<(u64, f64)>,
+    next_x: u64,
+    rate: Duration,
+    last: Option<Instant>,
+    timer: Option<SpawnHandle>,
+}
+
+impl LiveStream {
+    pub fn new(rate: Duration) -> Self {
+        LiveStream {
+            window: VecDeque::with_capacity(STREAM_WINDOW),
+            next_x: 0,
+            rate,
+            last: None,
+            timer: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    /// Sets the interval between points, in milliseconds.
+    pub fn set_rate(&mut self, millis: u64) {
+        self.rate = Duration::from_millis(millis.max(MIN_RATE_MS));
+    }
+
+    /// (Re)starts the timer calling `tick` at the current rate.
+    pub fn start(&mut self, session: &mut CustomSession, tick: fn(&mut CustomServer, &mut CustomSession)) {
+        self.stop(session);
+        self.timer = Some(session.run_interval(self.rate, tick));
+    }
+
+    pub fn stop(&mut self, session: &mut CustomSession) {
+        if let Some(timer) = self.timer.take() {
+            session.cancel_future(timer);
+        }
+    }
+
+    /// Whether a point is due. The heartbeat also calls `tick`, so ticks
+    /// arriving well before the next point are skipped. Timer ticks can
+    /// come slightly early, hence the slack.
+    pub fn is_due(&self) -> bool {
+        self.is_running() && self.last.is_none_or(|last| last.elapsed() >= self.rate / 2)
+    }
+
+    pub fn push(&mut self, y: f64) {
+        if self.window.len() == STREAM_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back((self.next_x, y));
+        self.next_x += 1;
+        self.last = Some(Instant::now());
+    }
+
+    pub fn points(&mut self) -> &[(u64, f64)] {
+        self.window.make_contiguous()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_come_out_in_order_within_the_window() {
+        let mut stream = LiveStream::new(Duration::from_millis(MIN_RATE_MS));
+        for n in 0..STREAM_WINDOW + 5 {
+            stream.push(n as f64);
+        }
+        let points = stream.points();
+        assert_eq!(points.len(), STREAM_WINDOW);
+        assert_eq!(points[0], (5, 5.0));
+        assert!(points.windows(2).all(|pair| pair[0].0 + 1 == pair[1].0));
+    }
+}
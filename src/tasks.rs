@@ -0,0 +1,48 @@
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+/// Background tasks spawned on behalf of a session. Tasks still running
+/// when the session ends are aborted so they don't outlive it.
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TaskRegistry {
+    pub fn spawn<F>(&mut self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.retain(|handle| !handle.is_finished());
+        self.tasks.push(tokio::spawn(task));
+    }
+
+    pub fn cancel_all(&mut self) {
+        for handle in self.tasks.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    #[actix_web::test]
+    async fn cancelled_tasks_stop_running() {
+        let mut tasks = TaskRegistry::default();
+        let (running, stopped) = oneshot::channel::<()>();
+        tasks.spawn(async move {
+            // Dropped along with the task once it is aborted.
+            let _running = running;
+            std::future::pending::<()>().await;
+        });
+        tokio::task::yield_now().await;
+        // What the session does in `stopped`.
+        tasks.cancel_all();
+        let stopped = tokio::time::timeout(std::time::Duration::from_secs(1), stopped).await;
+        assert!(matches!(stopped, Ok(Err(_))), "the task was dropped without finishing");
+        assert!(tasks.tasks.is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+//! Drives the handlers synchronously, without actix or a websocket. The
+//! session's clock stands still unless moved with `Driver::advance`.
+//!
+//! ```
+//! # use rusty_shiny::server::CustomServer;
+//! # use rusty_shiny::testing::Driver;
+//! # use serde_json::json;
+//! let mut driver = Driver::new(CustomServer::builder().build());
+//! driver.set("markdown", json!("# Title"));
+//! assert_eq!(driver.session.output("markdown_count"), Some("7 / 5000 characters"));
+//! // The preview is debounced, so it only renders once the input settled.
+//! driver.advance(std::time::Duration::from_millis(300));
+//! assert_eq!(driver.session.output("rendered_md"), Some("<h1>Title</h1>\n"));
+//!
+//! // Invalid values get an error message below the input.
+//! driver.set_number("dist1-n", 0.0);
+//! assert_eq!(driver.session.inserted("#dist1-n").len(), 1);
+//! ```
+
+use std::time::Duration;
+use serde_json::{ json, Value };
+
+use super::messages::Session;
+use super::server::CustomServer;
+
+/// Records every message the handlers send instead of writing it to a
+/// websocket.
+#[derive(Default)]
+pub struct MockSession {
+    pub messages: Vec<Value>,
+}
+
+impl Session for MockSession {
+    fn send(&mut self, message: Value) {
+        self.messages.push(message);
+    }
+}
+
+impl MockSession {
+    /// Runs the session logic's `initialize`, as the first message from the
+    /// client does.
+    pub fn initialize(&mut self, shiny: &mut CustomServer) {
+        shiny.logic().initialize(shiny, self);
+    }
+
+    /// Sets input `key` to `value` and runs `update` as if the client had
+    /// just reported the change.
+    pub fn update(&mut self, shiny: &mut CustomServer, key: &str, value: Value) {
+        shiny.input.insert(key, value);
+        shiny.event = key.to_string();
+        shiny.logic().update(shiny, self);
+    }
+
+    /// Runs the session logic's `tick`, as the heartbeat does.
+    pub fn tick(&mut self, shiny: &mut CustomServer) {
+        shiny.logic().tick(shiny, self);
+    }
+
+    /// The most recent value rendered into output `id`: the HTML of a
+    /// `uiOutput` or the text of a `textOutput`.
+    pub fn output(&self, id: &str) -> Option<&str> {
+        self.messages.iter().rev().find_map(|message| {
+            let value = message.get("values")?.get(id)?;
+            value.get("html").unwrap_or(value).as_str()
+        })
+    }
+
+    /// Error rendered into output `id` by its latest message, if the latest
+    /// was an error.
+    pub fn output_error(&self, id: &str) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|message| {
+                message.get("values").and_then(|values| values.get(id)).is_some()
+                    || message.get("errors").and_then(|errors| errors.get(id)).is_some()
+            })?
+            .get("errors")?
+            .get(id)?
+            .get("message")?
+            .as_str()
+    }
+
+    /// Notifications shown, in the order they were sent.
+    pub fn notifications(&self) -> Vec<&Value> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.get("notification"))
+            .filter(|notification| notification["type"] == "show")
+            .map(|notification| &notification["message"])
+            .collect()
+    }
+
+    /// HTML inserted next to `selector` with `insert_ui`, in the order it
+    /// was sent.
+    pub fn inserted(&self, selector: &str) -> Vec<&str> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.get("shiny-insert-ui"))
+            .filter(|insert| insert["selector"] == selector)
+            .filter_map(|insert| insert["content"]["html"].as_str())
+            .collect()
+    }
+
+    /// Selectors of the elements removed with `remove_ui`.
+    pub fn removed(&self) -> Vec<&str> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.get("shiny-remove-ui")?["selector"].as_str())
+            .collect()
+    }
+
+    /// Messages sent to input `id` with `update_text_input` and the like.
+    pub fn input_messages(&self, id: &str) -> Vec<&Value> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.get("inputMessages")?.as_array())
+            .flatten()
+            .filter(|input| input["id"] == id)
+            .map(|input| &input["message"])
+            .collect()
+    }
+
+    /// Custom messages of the given type, in the order they were sent.
+    pub fn custom_messages(&self, kind: &str) -> Vec<&Value> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.get("custom")?.get(kind))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+/// A session and its server, started as a browser would start them, with
+/// helpers reporting inputs the way Shiny's input bindings do.
+pub struct Driver {
+    pub shiny: CustomServer,
+    pub session: MockSession,
+}
+
+impl Driver {
+    /// Initializes `shiny` with the values the client would report first.
+    pub fn new(shiny: CustomServer) -> Self {
+        Driver::with_inputs(shiny, &[])
+    }
+
+    /// Like `new`, with `inputs` among the initial values, e.g.
+    /// `("dist1-n:shiny.number", json!(50))`.
+    pub fn with_inputs(mut shiny: CustomServer, inputs: &[(&str, Value)]) -> Self {
+        for (key, value) in inputs {
+            shiny.input.insert(key, value.clone());
+        }
+        shiny.clock.freeze();
+        let mut session = MockSession::default();
+        session.initialize(&mut shiny);
+        Driver { shiny, session }
+    }
+
+    /// Reports a new value of input `key`, as `Shiny.setInputValue` would.
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.session.update(&mut self.shiny, key, value);
+    }
+
+    /// Reports a new value of numeric input `id`. Whole numbers are sent
+    /// without a fraction, as the browser's `JSON.stringify` sends them.
+    pub fn set_number(&mut self, id: &str, value: f64) {
+        let value = match value.fract() == 0.0 && value.abs() < 1e15 {
+            true => json!(value as i64),
+            false => json!(value),
+        };
+        self.set(&format!("{}:shiny.number", id), value);
+    }
+
+    /// Reports a click on action button `id`.
+    pub fn click(&mut self, id: &str) {
+        let key = format!("{}:shiny.action", id);
+        let clicks = self.shiny.input.get_u64(&key).unwrap_or(0);
+        self.set(&key, json!(clicks + 1));
+    }
+
+    pub fn tick(&mut self) {
+        self.session.tick(&mut self.shiny);
+    }
+
+    /// Moves the session's clock `by` forward and runs a heartbeat, which
+    /// fires what came due in the meantime.
+    pub fn advance(&mut self, by: Duration) {
+        self.shiny.clock.advance(by);
+        self.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_is_counted_and_rendered() {
+        let mut driver = Driver::new(CustomServer::builder().build());
+        driver.set("markdown", json!("# Title\n\n*emphasis*"));
+        assert_eq!(driver.session.output("markdown_count"), Some("19 / 5000 characters"));
+        assert_eq!(driver.session.output("rendered_md"), None, "the preview waits for the input to settle");
+        driver.advance(Duration::from_millis(299));
+        assert_eq!(driver.session.output("rendered_md"), None);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(driver.session.output("rendered_md"), Some("<h1>Title</h1>\n<p><em>emphasis</em></p>\n"));
+    }
+
+    fn driver() -> Driver {
+        Driver::with_inputs(CustomServer::builder().build(), &[
+            ("dist1-dist_type", json!("normal")),
+            ("dist1-n:shiny.number", json!(100)),
+            ("dist1-mean:shiny.number", json!(0)),
+            ("dist1-sd:shiny.number", json!(1)),
+        ])
+    }
+
+    #[test]
+    fn changing_the_distribution_resamples_and_relabels() {
+        let mut driver = driver();
+        driver.set("dist1-dist_type", json!("uniform"));
+        let sample = driver.shiny.panels[0].sample.value().cloned().unwrap_or_default();
+        assert_eq!(sample.len(), 100);
+        assert!(sample.iter().all(|x| (0.0..=1.0).contains(x)), "drawn from Uniform(0, 1)");
+        let labels: Vec<&Value> = driver.session.input_messages("dist1-mean").into_iter().map(|message| &message["label"]).collect();
+        assert_eq!(labels.last(), Some(&&json!("Minimum")));
+    }
+
+    #[test]
+    fn rejected_values_leave_the_sample_alone() {
+        let mut driver = driver();
+        let sample = driver.shiny.panels[0].sample.value().cloned();
+        driver.set_number("dist1-n", 2.5);
+        let errors = driver.session.inserted("#dist1-n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("dist1-n-error"), "{}", errors[0]);
+        assert_eq!(driver.shiny.panels[0].sample.value().cloned(), sample);
+        assert!(driver.session.output_error("plot1").is_some());
+    }
+}
@@ -0,0 +1,172 @@
+//! Two-sample tests comparing the samples of the distribution panels.
+
+use statrs::distribution::{ ContinuousCDF, StudentsT };
+
+use super::stats::html_table;
+
+/// Significance level the interpretations are given at.
+pub const ALPHA: f64 = 0.05;
+
+/// A test of two samples, or `None` if it can't be run on them.
+pub type Test = fn(&[f64], &[f64]) -> Option<TestResult>;
+
+pub struct TestResult {
+    pub name: &'static str,
+    pub statistic: f64,
+    pub p_value: f64,
+    /// What the result says at `ALPHA`.
+    pub interpretation: &'static str,
+}
+
+fn mean_and_variance(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance)
+}
+
+/// Two-sided p-value of a t statistic.
+fn t_p_value(t: f64, freedom: f64) -> Option<f64> {
+    let dist = StudentsT::new(0.0, 1.0, freedom).ok()?;
+    Some(2.0 * (1.0 - dist.cdf(t.abs())))
+}
+
+fn means_interpretation(p_value: f64) -> &'static str {
+    if p_value < ALPHA {
+        "The means differ"
+    } else {
+        "No evidence that the means differ"
+    }
+}
+
+/// Student's t-test, assuming both samples have the same variance. `None`
+/// if either sample has fewer than 2 values or both are constant.
+pub fn t_test(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let ((m1, v1), (m2, v2)) = (mean_and_variance(a), mean_and_variance(b));
+    let freedom = n1 + n2 - 2.0;
+    let pooled = ((n1 - 1.0) * v1 + (n2 - 1.0) * v2) / freedom;
+    let se = (pooled * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    let statistic = (m1 - m2) / se;
+    let p_value = t_p_value(statistic, freedom)?;
+    Some(TestResult {
+        name: "Student's t-test",
+        statistic,
+        p_value,
+        interpretation: means_interpretation(p_value),
+    })
+}
+
+/// Welch's t-test, which doesn't assume equal variances. `None` in the
+/// same cases as `t_test`.
+pub fn welch_test(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let ((m1, v1), (m2, v2)) = (mean_and_variance(a), mean_and_variance(b));
+    let (s1, s2) = (v1 / n1, v2 / n2);
+    if s1 + s2 == 0.0 {
+        return None;
+    }
+    let statistic = (m1 - m2) / (s1 + s2).sqrt();
+    // Welch–Satterthwaite degrees of freedom.
+    let freedom = (s1 + s2).powi(2) / (s1.powi(2) / (n1 - 1.0) + s2.powi(2) / (n2 - 1.0));
+    let p_value = t_p_value(statistic, freedom)?;
+    Some(TestResult {
+        name: "Welch's t-test",
+        statistic,
+        p_value,
+        interpretation: means_interpretation(p_value),
+    })
+}
+
+/// Survival function of the Kolmogorov distribution.
+fn kolmogorov_q(lambda: f64) -> f64 {
+    if lambda < 1e-3 {
+        return 1.0;
+    }
+    let sum: f64 = (1..=100)
+        .map(|j| {
+            let j = j as f64;
+            let sign = if j % 2.0 == 1.0 { 1.0 } else { -1.0 };
+            sign * (-2.0 * j * j * lambda * lambda).exp()
+        })
+        .sum();
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Two-sample Kolmogorov–Smirnov test, with the asymptotic p-value. `None`
+/// if either sample is empty.
+pub fn ks_test(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(|x, y| x.total_cmp(y));
+    b.sort_by(|x, y| x.total_cmp(y));
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    // Walks both samples in order, tracking the widest gap between their
+    // empirical distribution functions.
+    let (mut i, mut j, mut statistic) = (0, 0, 0.0_f64);
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        statistic = statistic.max((i as f64 / n1 - j as f64 / n2).abs());
+    }
+    let effective = (n1 * n2 / (n1 + n2)).sqrt();
+    let p_value = kolmogorov_q((effective + 0.12 + 0.11 / effective) * statistic);
+    Some(TestResult {
+        name: "Kolmogorov–Smirnov",
+        statistic,
+        p_value,
+        interpretation: if p_value < ALPHA {
+            "The distributions differ"
+        } else {
+            "No evidence that the distributions differ"
+        },
+    })
+}
+
+/// Runs every test on `a` and `b`, one row per test. Tests that can't be run
+/// on the samples say so.
+pub fn results_table(a: &[f64], b: &[f64]) -> String {
+    let tests: [(&str, Test); 3] = [
+        ("Student's t-test", t_test),
+        ("Welch's t-test", welch_test),
+        ("Kolmogorov–Smirnov", ks_test),
+    ];
+    let rows: Vec<Vec<String>> = tests
+        .iter()
+        .map(|(name, test)| match test(a, b) {
+            Some(result) => vec![
+                result.name.to_string(),
+                format!("{:.4}", result.statistic),
+                format!("{:.4}", result.p_value),
+                result.interpretation.to_string(),
+            ],
+            None => vec![
+                name.to_string(),
+                String::from("-"),
+                String::from("-"),
+                String::from("Not enough varying values to run the test"),
+            ],
+        })
+        .collect();
+    html_table(
+        &["Test", "Statistic", "p-value", &format!("At α = {}", ALPHA)],
+        &rows
+    )
+}
@@ -0,0 +1,49 @@
+//! Serving HTTPS and WSS directly, for deployments without a reverse proxy
+//! terminating TLS. Given a certificate chain and a private key in PEM
+//! files, the server only accepts TLS connections, and can answer plain
+//! HTTP on another port with redirects to HTTPS.
+
+use std::fs::File;
+use std::io::{ self, BufReader };
+use actix_web::{ http::header, web, HttpRequest, HttpResponse };
+use rustls::ServerConfig;
+
+/// Reads the certificate chain at `cert` and the private key at `key`.
+pub fn server_config(cert: &str, key: &str) -> io::Result<ServerConfig> {
+    let invalid = |path: &str, e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e))
+    };
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(cert, &e))?;
+    if certs.is_empty() {
+        return Err(invalid(cert, &"no certificate found"));
+    }
+    let key_der = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))
+        .map_err(|e| invalid(key, &e))?
+        .ok_or_else(|| invalid(key, &"no private key found"))?;
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key_der)
+        .map_err(|e| invalid(key, &e))
+}
+
+/// Port the HTTPS server listens on, for `redirect_to_https`.
+#[derive(Clone, Copy)]
+pub struct HttpsPort(pub u16);
+
+/// Sends the request to the same host and path over HTTPS.
+pub async fn redirect_to_https(req: HttpRequest, port: web::Data<HttpsPort>) -> HttpResponse {
+    let info = req.connection_info();
+    // The host header may carry the HTTP port, which HTTPS doesn't use.
+    let host = match info.host().rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()) => host,
+        _ => info.host(),
+    };
+    let authority = match port.0 {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+    let location = format!("https://{}{}", authority, req.uri().path_and_query().map_or("/", |path| path.as_str()));
+    HttpResponse::PermanentRedirect().insert_header((header::LOCATION, location)).finish()
+}
@@ -0,0 +1,103 @@
+//! Typed access to the input pool. Unlike the pool's own getters, which
+//! return `None` both for missing inputs and for values of the wrong type,
+//! these say what went wrong.
+
+use std::fmt;
+use chrono::NaiveDate;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use shiny_rs::session::input_pool::InputPool;
+
+use super::i18n::{ translate, Language };
+use super::messages::{ Session, show_notification };
+
+#[derive(Debug)]
+pub enum InputError {
+    /// The client hasn't sent the input, or sent it empty.
+    Missing(String),
+    /// The value doesn't have the expected type.
+    Invalid { key: String, message: String },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Missing(key) => write!(f, "input {} has no value", key),
+            InputError::Invalid { key, message } => write!(f, "input {} is invalid: {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+impl InputError {
+    /// What went wrong, in `language`, for the user.
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            InputError::Missing(key) => translate(language, "input.missing", &[("key", key.clone())]),
+            InputError::Invalid { key, message } => {
+                translate(language, "input.invalid", &[("key", key.clone()), ("message", message.clone())])
+            }
+        }
+    }
+}
+
+pub struct TypedInputs<'a> {
+    input: &'a InputPool,
+}
+
+impl<'a> TypedInputs<'a> {
+    pub fn new(input: &'a InputPool) -> Self {
+        TypedInputs { input }
+    }
+
+    /// Deserializes the value of `key` into any type.
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<T, InputError> {
+        let value = self.input.get(key).ok_or_else(|| InputError::Missing(key.to_string()))?;
+        if value.is_null() {
+            return Err(InputError::Missing(key.to_string()));
+        }
+        T::deserialize(value).map_err(|e| InputError::Invalid {
+            key: key.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool, InputError> {
+        self.get_json(key)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Result<f64, InputError> {
+        self.get_json(key)
+    }
+
+    pub fn get_u64(&self, key: &str) -> Result<u64, InputError> {
+        self.get_json(key)
+    }
+
+    /// Values of inputs sending arrays, like `sliderInput` ranges.
+    pub fn get_vec_f64(&self, key: &str) -> Result<Vec<f64>, InputError> {
+        self.get_json(key)
+    }
+
+    /// Dates, sent by `dateInput` as `YYYY-MM-DD`.
+    pub fn get_date(&self, key: &str) -> Result<NaiveDate, InputError> {
+        let text: String = self.get_json(key)?;
+        NaiveDate::parse_from_str(&text, "%Y-%m-%d").map_err(|e| InputError::Invalid {
+            key: key.to_string(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Logs an input error and tells the user about it in `language`.
+pub fn report_input_error(session: &mut dyn Session, language: Language, error: &InputError) {
+    tracing::warn!(%error, "bad input");
+    show_notification(session, json!({
+        "html": error.message(language),
+        "action": "",
+        "deps": [],
+        "closeButton": true,
+        "type": "warning"
+    }));
+}
@@ -0,0 +1,127 @@
+//! Updates of inputs, the counterparts of Shiny's `updateNumericInput` and
+//! the like. Each takes the changes as a struct whose fields left `None`
+//! keep what the input has, and sends them as the message the input's
+//! binding reads.
+//!
+//! ```ignore
+//! update_numeric_input(session, "seed", NumericUpdate { value: Some(None), ..Default::default() });
+//! ```
+
+use chrono::NaiveDate;
+use serde_json::{ Map, Value };
+
+use super::messages::{ Session, update_text_input };
+use super::stats::escape_html;
+
+#[derive(Default)]
+pub struct NumericUpdate {
+    pub label: Option<String>,
+    /// `Some(None)` empties the input.
+    pub value: Option<Option<f64>>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+}
+
+/// Changes of a slider: a range input of the page, not an ionRangeSlider.
+#[derive(Default)]
+pub struct SliderUpdate {
+    pub label: Option<String>,
+    pub value: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct SelectUpdate {
+    pub label: Option<String>,
+    /// Replaces the options with these `(value, label)` pairs.
+    pub choices: Option<Vec<(String, String)>>,
+    pub selected: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CheckboxUpdate {
+    pub label: Option<String>,
+    pub value: Option<bool>,
+}
+
+#[derive(Default)]
+pub struct DateUpdate {
+    pub label: Option<String>,
+    /// `Some(None)` empties the input.
+    pub value: Option<Option<NaiveDate>>,
+    pub min: Option<NaiveDate>,
+    pub max: Option<NaiveDate>,
+}
+
+/// Sends the fields that are set to input `id`.
+fn send(session: &mut dyn Session, id: &str, fields: Vec<(&str, Option<Value>)>) {
+    let message: Map<String, Value> = fields
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value?)))
+        .collect();
+    update_text_input(session, id, Value::Object(message));
+}
+
+fn date(date: NaiveDate) -> Value {
+    Value::from(date.format("%Y-%m-%d").to_string())
+}
+
+pub fn update_numeric_input(session: &mut dyn Session, id: &str, update: NumericUpdate) {
+    send(session, id, vec![
+        ("label", update.label.map(Value::from)),
+        ("value", update.value.map(|value| value.map_or(Value::Null, Value::from))),
+        ("min", update.min.map(Value::from)),
+        ("max", update.max.map(Value::from)),
+        ("step", update.step.map(Value::from)),
+    ]);
+}
+
+pub fn update_slider(session: &mut dyn Session, id: &str, update: SliderUpdate) {
+    send(session, id, vec![
+        ("label", update.label.map(Value::from)),
+        ("value", update.value.map(Value::from)),
+        ("min", update.min.map(Value::from)),
+        ("max", update.max.map(Value::from)),
+        ("step", update.step.map(Value::from)),
+    ]);
+}
+
+/// Updates a select, not a selectize input. Labels of the choices are
+/// escaped.
+pub fn update_select(session: &mut dyn Session, id: &str, update: SelectUpdate) {
+    let options = update.choices.map(|choices| {
+        let options: String = choices
+            .iter()
+            .map(|(value, label)| {
+                format!(r#"<option value="{}">{}</option>"#, escape_html(value), escape_html(label))
+            })
+            .collect();
+        Value::from(options)
+    });
+    send(session, id, vec![
+        ("label", update.label.map(Value::from)),
+        ("options", options),
+        ("value", update.selected.map(Value::from)),
+    ]);
+}
+
+pub fn update_checkbox(session: &mut dyn Session, id: &str, update: CheckboxUpdate) {
+    send(session, id, vec![
+        ("label", update.label.map(Value::from)),
+        ("value", update.value.map(Value::from)),
+    ]);
+}
+
+/// Updates a date input: an `<input type="date">` of the page, which takes
+/// dates as `YYYY-MM-DD`.
+pub fn update_date(session: &mut dyn Session, id: &str, update: DateUpdate) {
+    send(session, id, vec![
+        ("label", update.label.map(Value::from)),
+        ("value", update.value.map(|value| value.map_or(Value::from(""), date))),
+        ("min", update.min.map(date)),
+        ("max", update.max.map(date)),
+    ]);
+}
@@ -0,0 +1,129 @@
+use actix::Message;
+use actix_multipart::Multipart;
+use actix_web::{ error, web, Error, HttpResponse };
+use futures_util::TryStreamExt;
+use serde::Deserialize;
+
+use super::auth::User;
+use super::i18n::{ translate, Language };
+use super::registry::SessionRegistry;
+use super::server::{ GetFiles, GetLanguage };
+
+const MAX_UPLOAD_BYTES: usize = 1024 * 1024;
+
+/// Name of the last upload in the session's directory.
+pub const UPLOAD_FILE: &str = "upload.txt";
+
+/// Numbers uploaded by the user, plotted next to the simulated samples.
+pub struct Upload {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetUpload(pub Upload);
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    session: String,
+}
+
+/// Parses every comma, semicolon or whitespace separated number in `text`.
+/// Cells that are not numbers are only allowed in the first line, which is
+/// taken to be a header. Errors are in `language`.
+pub fn parse_numbers(text: &str, language: Language) -> Result<Vec<f64>, String> {
+    let mut values = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let cells = line
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .map(|cell| cell.trim_matches('"'))
+            .filter(|cell| !cell.is_empty());
+        for cell in cells {
+            match cell.parse::<f64>() {
+                Ok(value) => values.push(value),
+                Err(_) if line_number == 0 => {}
+                Err(_) => {
+                    let args = [("line", (line_number + 1).to_string()), ("cell", cell.to_string())];
+                    return Err(translate(language, "upload.not_number", &args));
+                }
+            }
+        }
+    }
+    if values.is_empty() {
+        return Err(translate(language, "upload.no_numbers", &[]));
+    }
+    Ok(values)
+}
+
+/// `name` with only letters, digits, spaces and `.-_()` left, at most 64
+/// characters, as it's shown in the plot's legend and the data table.
+pub fn safe_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || " .-_()".contains(*c))
+        .take(64)
+        .collect();
+    match name.trim() {
+        "" => String::from("upload"),
+        name => name.to_string(),
+    }
+}
+
+pub async fn upload(
+    registry: web::Data<SessionRegistry>,
+    query: web::Query<UploadQuery>,
+    user: Option<web::ReqData<User>>,
+    mut payload: Multipart
+) -> Result<HttpResponse, Error> {
+    let addr = registry
+        .get_owned(&query.session, user.as_ref().map(|user| user.name.as_str()))
+        .ok_or_else(|| error::ErrorNotFound("Unknown session"))?;
+    let language = addr.send(GetLanguage).await.map_err(error::ErrorInternalServerError)?;
+    let t = |key| translate(language, key, &[]);
+    let mut field = payload
+        .try_next()
+        .await?
+        .ok_or_else(|| error::ErrorBadRequest(t("upload.missing")))?;
+    let name = safe_name(field.content_disposition().get_filename().unwrap_or_default());
+    let mut bytes = Vec::new();
+    while let Some(chunk) = field.try_next().await? {
+        if bytes.len() + chunk.len() > MAX_UPLOAD_BYTES {
+            return Err(error::ErrorPayloadTooLarge(t("upload.too_large")));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    // The file as uploaded is kept in the session's directory, within the
+    // quota of all sessions.
+    if let Some(files) = addr.send(GetFiles).await.map_err(error::ErrorInternalServerError)? {
+        files.write(UPLOAD_FILE, &bytes).map_err(|e| match e.kind() {
+            std::io::ErrorKind::StorageFull => error::ErrorInsufficientStorage(t("upload.no_room")),
+            _ => error::ErrorInternalServerError(e),
+        })?;
+    }
+    let text = String::from_utf8(bytes).map_err(|_| error::ErrorBadRequest(t("upload.not_text")))?;
+    let values = parse_numbers(&text, language).map_err(error::ErrorBadRequest)?;
+    addr.do_send(SetUpload(Upload { name, values }));
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keep_a_safe_charset() {
+        assert_eq!(safe_name("data (2).csv"), "data (2).csv");
+        assert_eq!(safe_name("<img src=x onerror=alert(1)>.csv"), "img srcx onerroralert(1).csv");
+        assert_eq!(safe_name("</script>"), "script");
+        assert_eq!(safe_name("\"'<>"), "upload");
+        assert_eq!(safe_name(&"a".repeat(100)).len(), 64);
+    }
+
+    #[test]
+    fn parse_errors_are_translated() {
+        assert_eq!(parse_numbers("x\n1\ntwo", Language::Es), Err(String::from("Línea 3: \"two\" no es un número")));
+        assert_eq!(parse_numbers("x, y", Language::En), Err(String::from("The file contains no numbers")));
+        assert_eq!(parse_numbers("x\n1;2", Language::En), Ok(vec![1.0, 2.0]));
+    }
+}
@@ -0,0 +1,202 @@
+//! Input validation, in the spirit of `shiny::validate(need(...))`: each
+//! input key has a list of validators, the first failing one is shown
+//! below the input and the caller skips the computations that depend on
+//! it.
+
+use std::collections::HashMap;
+use regex::Regex;
+use serde_json::Value;
+
+use super::i18n::{ translate, Language };
+use super::messages::{ Session, insert_ui, remove_ui };
+
+pub enum Validator {
+    /// The input must have a non-blank value.
+    Required,
+    /// The value must lie within `min..=max`.
+    Range { min: f64, max: f64 },
+    /// The value must be a multiple of the step.
+    Step(f64),
+    /// The text must have at most this many characters.
+    MaxLength(usize),
+    /// The text must match the pattern, otherwise the message with this
+    /// catalog key is shown.
+    Pattern(Regex, &'static str),
+    /// The value must satisfy the predicate, otherwise the message with
+    /// this catalog key is shown.
+    Predicate(fn(f64) -> bool, &'static str),
+    /// The text must parse, otherwise the parser's error, in the language
+    /// given, is shown.
+    Parse(fn(&str, Language) -> Result<(), String>),
+}
+
+fn check(condition: bool, message: impl FnOnce() -> String) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message())
+    }
+}
+
+impl Validator {
+    /// Missing values only fail `Required`, so optional inputs can have
+    /// other validators.
+    fn check(&self, value: &Value, language: Language) -> Result<(), String> {
+        let t = |key, args: &[(&str, String)]| translate(language, key, args);
+        if let Validator::Required = self {
+            let blank = value.is_null() || value.as_str().is_some_and(|text| text.trim().is_empty());
+            return check(!blank, || t("validation.required", &[]));
+        }
+        if value.is_null() {
+            return Ok(());
+        }
+        match self {
+            Validator::Required => Ok(()),
+            Validator::Range { min, max } => {
+                let value = number(value, language)?;
+                check(
+                    (*min..=*max).contains(&value),
+                    || t("validation.range", &[("min", min.to_string()), ("max", max.to_string())])
+                )
+            }
+            Validator::Step(step) => {
+                let value = number(value, language)?;
+                check(
+                    ((value / step).round() * step - value).abs() < 1e-9,
+                    || t("validation.step", &[("step", step.to_string())])
+                )
+            }
+            Validator::MaxLength(max) => {
+                let length = text(value, language)?.chars().count();
+                check(length <= *max, || t("validation.max_length", &[("max", max.to_string())]))
+            }
+            Validator::Pattern(pattern, message) => {
+                check(pattern.is_match(text(value, language)?), || t(message, &[]))
+            }
+            Validator::Predicate(predicate, message) => {
+                check(predicate(number(value, language)?), || t(message, &[]))
+            }
+            Validator::Parse(parse) => parse(text(value, language)?, language),
+        }
+    }
+}
+
+fn number(value: &Value, language: Language) -> Result<f64, String> {
+    value.as_f64().ok_or_else(|| translate(language, "validation.number", &[]))
+}
+
+fn text(value: &Value, language: Language) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| translate(language, "validation.text", &[]))
+}
+
+/// Validators for the app's inputs, keyed by input key (e.g.
+/// `dist1-n:shiny.number`), and the errors currently shown.
+#[derive(Default)]
+pub struct Validators {
+    rules: HashMap<String, Vec<Validator>>,
+    shown: HashMap<String, String>,
+    /// Language of the errors.
+    language: Language,
+}
+
+impl Validators {
+    pub fn add(mut self, key: &str, validator: Validator) -> Self {
+        self.rules.entry(key.to_string()).or_default().push(validator);
+        self
+    }
+
+    /// Replaces the validators of `key`. The error shown, if any, stays
+    /// until `key` is validated again.
+    pub fn set(&mut self, key: &str, validators: Vec<Validator>) {
+        self.rules.insert(key.to_string(), validators);
+    }
+
+    /// Shows later errors in `language`. Errors shown already keep theirs
+    /// until their input changes.
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    /// Drops the validators of `key`, for inputs removed from the page.
+    pub fn remove(&mut self, key: &str) {
+        self.rules.remove(key);
+        self.shown.remove(input_id(key));
+    }
+
+    /// Checks `value` against the validators of `key`, showing the first
+    /// failure below the input, or removing a previous error if it passes.
+    pub fn validate(&mut self, session: &mut dyn Session, key: &str, value: &Value) -> Result<(), String> {
+        let result = self.rules
+            .get(key)
+            .map_or(Ok(()), |validators| validators.iter().try_for_each(|v| v.check(value, self.language)));
+        let id = input_id(key);
+        match &result {
+            Ok(()) => {
+                if self.shown.remove(id).is_some() {
+                    remove_ui(session, &format!("#{}-error", id));
+                }
+            }
+            Err(message) => {
+                if self.shown.get(id) != Some(message) {
+                    if self.shown.insert(id.to_string(), message.clone()).is_some() {
+                        remove_ui(session, &format!("#{}-error", id));
+                    }
+                    insert_ui(
+                        session,
+                        &format!("#{}", id),
+                        "afterEnd",
+                        &format!(r#"<div id="{}-error" class="text-danger small">{}</div>"#, id, message)
+                    );
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Strips the type suffix shiny-rs adds to input keys.
+pub(crate) fn input_id(key: &str) -> &str {
+    key.split(':').next().unwrap_or(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::server::CustomServer;
+    use crate::testing::{ Driver, MockSession };
+
+    #[test]
+    fn first_failure_is_shown_once_and_removed_when_fixed() {
+        let mut validators = Validators::default()
+            .add("n:shiny.number", Validator::Required)
+            .add("n:shiny.number", Validator::Range { min: 1.0, max: 10.0 });
+        let mut session = MockSession::default();
+        assert!(validators.validate(&mut session, "n:shiny.number", &json!(null)).is_err());
+        assert!(validators.validate(&mut session, "n:shiny.number", &json!(20)).is_err());
+        assert!(validators.validate(&mut session, "n:shiny.number", &json!(20)).is_err());
+        let errors = session.inserted("#n");
+        assert_eq!(errors.len(), 2, "a repeated error isn't inserted again");
+        assert!(errors[1].contains("Must be between 1 and 10"));
+        assert!(validators.validate(&mut session, "n:shiny.number", &json!(5)).is_ok());
+        assert_eq!(session.removed(), ["#n-error", "#n-error"]);
+    }
+
+    #[test]
+    fn out_of_range_values_skip_the_render() {
+        let mut driver = Driver::with_inputs(CustomServer::builder().build(), &[
+            ("dist1-n:shiny.number", json!(50)),
+            ("dist1-mean:shiny.number", json!(0)),
+            ("dist1-sd:shiny.number", json!(1)),
+        ]);
+        let sample = driver.shiny.panels[0].sample.value().cloned();
+        assert_eq!(sample.as_ref().map(Vec::len), Some(50));
+        driver.set_number("dist1-n", 0.0);
+        assert_eq!(driver.session.inserted("#dist1-n").len(), 1);
+        assert_eq!(driver.session.output_error("plot1"), Some("Fix the invalid inputs to update the plot"));
+        assert_eq!(driver.shiny.panels[0].sample.value().cloned(), sample, "not resampled");
+        driver.set_number("dist1-n", 20.0);
+        assert_eq!(driver.session.removed().last(), Some(&"#dist1-n-error"));
+        assert_eq!(driver.shiny.panels[0].sample.value().map(Vec::len), Some(20));
+    }
+}
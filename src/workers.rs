@@ -0,0 +1,128 @@
+//! Worker threads shared by every session for long simulations. A fixed
+//! number of `Worker` actors, each on a thread of its own, run the jobs
+//! `WorkerPool` hands them, so a session asking for a large simulation
+//! waits its turn rather than taking a thread from the others. Jobs
+//! beyond the running ones wait in a queue of bounded length; a job
+//! submitted to a full queue is refused. Each waiting job's session hears
+//! where it stands whenever the queue moves.
+
+use std::collections::VecDeque;
+use actix::{
+    fut, Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, Recipient, SyncArbiter,
+    SyncContext
+};
+
+/// Work run on a worker's thread.
+pub type Work = Box<dyn FnOnce() + Send>;
+
+/// Runs jobs on a thread of its own.
+pub struct Worker;
+
+impl Actor for Worker {
+    type Context = SyncContext<Self>;
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Run(Work);
+
+impl Handler<Run> for Worker {
+    type Result = ();
+    fn handle(&mut self, msg: Run, _ctx: &mut Self::Context) {
+        (msg.0)();
+    }
+}
+
+/// Sent to the submitter of a waiting job with its place in the queue, 1
+/// being next, and 0 once it started.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueuePosition {
+    pub job: String,
+    pub position: usize,
+}
+
+/// Runs `work` once a worker is free. Answers with the job's position in
+/// the queue, 0 when it started right away.
+#[derive(Message)]
+#[rtype(result = "Result<usize, QueueFull>")]
+pub struct Submit {
+    /// Told to the submitter along with the positions.
+    pub job: String,
+    pub work: Work,
+    pub positions: Recipient<QueuePosition>,
+}
+
+/// The queue was full, the job was dropped.
+#[derive(Debug)]
+pub struct QueueFull;
+
+struct Waiting {
+    job: String,
+    work: Work,
+    positions: Recipient<QueuePosition>,
+}
+
+pub struct WorkerPool {
+    workers: Addr<Worker>,
+    /// Workers without a job.
+    idle: usize,
+    queue: VecDeque<Waiting>,
+    max_queued: usize,
+}
+
+impl WorkerPool {
+    /// Starts `workers` threads, with room for `max_queued` jobs waiting
+    /// for one of them.
+    pub fn start(workers: usize, max_queued: usize) -> Addr<WorkerPool> {
+        let workers = workers.max(1);
+        WorkerPool {
+            workers: SyncArbiter::start(workers, || Worker),
+            idle: workers,
+            queue: VecDeque::new(),
+            max_queued,
+        }
+        .start()
+    }
+
+    fn run(&mut self, work: Work, ctx: &mut Context<Self>) {
+        self.idle -= 1;
+        ctx.spawn(fut::wrap_future::<_, Self>(self.workers.send(Run(work))).map(|_, pool, ctx| {
+            pool.idle += 1;
+            pool.next(ctx);
+        }));
+    }
+
+    /// Hands the first waiting job to a free worker, and tells the others
+    /// they moved up.
+    fn next(&mut self, ctx: &mut Context<Self>) {
+        let waiting = match self.queue.pop_front() {
+            Some(waiting) => waiting,
+            None => return,
+        };
+        waiting.positions.do_send(QueuePosition { job: waiting.job, position: 0 });
+        self.run(waiting.work, ctx);
+        for (index, waiting) in self.queue.iter().enumerate() {
+            waiting.positions.do_send(QueuePosition { job: waiting.job.clone(), position: index + 1 });
+        }
+    }
+}
+
+impl Actor for WorkerPool {
+    type Context = Context<Self>;
+}
+
+impl Handler<Submit> for WorkerPool {
+    type Result = Result<usize, QueueFull>;
+    fn handle(&mut self, msg: Submit, ctx: &mut Self::Context) -> Self::Result {
+        if self.idle > 0 {
+            self.run(msg.work, ctx);
+            return Ok(0);
+        }
+        if self.queue.len() >= self.max_queued {
+            return Err(QueueFull);
+        }
+        self.queue.push_back(Waiting { job: msg.job, work: msg.work, positions: msg.positions });
+        Ok(self.queue.len())
+    }
+}
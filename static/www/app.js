@@ -0,0 +1,286 @@
+// Two range inputs acting as the handles of one slider. The value is
+// `{ lower, upper }`, whichever way round the handles are.
+var percentileRangeBinding = new Shiny.InputBinding();
+$.extend(percentileRangeBinding, {
+  find: function(scope) {
+    return $(scope).find(".percentile-range");
+  },
+  getValue: function(el) {
+    var handles = $(el).find("input[type=range]");
+    var a = Number(handles[0].value);
+    var b = Number(handles[1].value);
+    return { lower: Math.min(a, b), upper: Math.max(a, b) };
+  },
+  subscribe: function(el, callback) {
+    var binding = this;
+    $(el).on("input.percentileRange", "input[type=range]", function() {
+      var value = binding.getValue(el);
+      $(el).find(".percentile-range-label").text(value.lower + " to " + value.upper);
+      callback(true);
+    });
+  },
+  unsubscribe: function(el) {
+    $(el).off(".percentileRange");
+  },
+  getRatePolicy: function() {
+    return { policy: "debounce", delay: 250 };
+  }
+});
+Shiny.inputBindings.register(percentileRangeBinding, "shiny-rs.percentileRange");
+
+// Inputs Shiny has no binding for, like color pickers, range and date
+// inputs. Range inputs send numbers, the others their value as is. The
+// server can change their label, value and limits; a value it sets is sent
+// back like one the user picked.
+var nativeInputBinding = new Shiny.InputBinding();
+$.extend(nativeInputBinding, {
+  find: function(scope) {
+    return $(scope).find("input.native-input");
+  },
+  getValue: function(el) {
+    return el.type === "range" ? Number(el.value) : el.value;
+  },
+  subscribe: function(el, callback) {
+    $(el).on("input.nativeInput", function() {
+      callback(true);
+    });
+  },
+  unsubscribe: function(el) {
+    $(el).off(".nativeInput");
+  },
+  receiveMessage: function(el, data) {
+    if (data.hasOwnProperty("label")) {
+      $("#" + el.id + "-label").text(data.label);
+    }
+    ["min", "max", "step"].forEach(function(name) {
+      if (data.hasOwnProperty(name)) {
+        el[name] = data[name];
+      }
+    });
+    if (data.hasOwnProperty("value")) {
+      el.value = data.value;
+      $(el).trigger("input");
+    }
+  },
+  getRatePolicy: function() {
+    return { policy: "debounce", delay: 250 };
+  }
+});
+Shiny.inputBindings.register(nativeInputBinding, "shiny-rs.nativeInput");
+
+// Identifies the page across reconnections, so the server can hand a new
+// websocket the state of the one that dropped. Sent as an event so it
+// reaches the server again on every connection.
+// Pages reloaded by the server in development mode keep theirs.
+var reconnectToken = sessionStorage.getItem("shiny-rs-reload-token")
+  || Array.from(crypto.getRandomValues(new Uint8Array(16)), function(byte) {
+    return byte.toString(16).padStart(2, "0");
+  }).join("");
+sessionStorage.removeItem("shiny-rs-reload-token");
+
+// Large messages come as binary frames typed `<encoding>[+<compression>]`
+// once the server knows the client can read them. Shiny hands those to the
+// custom message handler of that type, so they are unpacked before Shiny
+// dispatches them.
+$(document).on("shiny:connected", function() {
+  Shiny.setInputValue("reconnect_token", reconnectToken, { priority: "event" });
+  if (window.MessagePack) {
+    Shiny.setInputValue("binary_encoding", "msgpack");
+  }
+  if (window.pako) {
+    Shiny.setInputValue("compression", ["gzip", "deflate"]);
+  }
+});
+
+$(document).on("shiny:message", function(event) {
+  var custom = event.message.custom;
+  var kind = custom && Object.keys(custom)[0];
+  if (!kind || !(custom[kind] instanceof ArrayBuffer)) {
+    return;
+  }
+  var parts = kind.split("+");
+  var bytes = new Uint8Array(custom[kind]);
+  if (parts[1]) {
+    // Detects gzip and zlib from the header.
+    bytes = pako.inflate(bytes);
+  }
+  event.message = parts[0] === "msgpack"
+    ? MessagePack.decode(bytes)
+    : JSON.parse(new TextDecoder().decode(bytes));
+});
+
+$(function() {
+  Shiny.addCustomMessageHandler("scroll-into-view", function(message) {
+    var el = document.querySelector(message.selector);
+    if (el) {
+      el.scrollIntoView({ behavior: "smooth", block: "start" });
+    }
+  });
+
+  // Sent in development mode once the page's files changed.
+  Shiny.addCustomMessageHandler("reload", function(message) {
+    sessionStorage.setItem("shiny-rs-reload-token", reconnectToken);
+    location.reload();
+  });
+
+  Shiny.addCustomMessageHandler("remove-ui-done", function(message) {
+    Shiny.setInputValue("remove_ui_done", message.id, { priority: "event" });
+  });
+
+  // Echoes the server's latency pings, which time the round trip.
+  Shiny.addCustomMessageHandler("latency-ping", function(message) {
+    Shiny.setInputValue("latency_pong", message.id, { priority: "event" });
+  });
+
+  // Reports clicks and selections on a plot as the `<id>_click` and
+  // `<id>_brush` inputs.
+  function bindPlotEvents(id, gd) {
+    if (!gd.on || gd.shinyRsBound) {
+      return;
+    }
+    gd.shinyRsBound = true;
+    gd.on("plotly_click", function(event) {
+      var point = event.points[0];
+      Shiny.setInputValue(id + "_click", [point.x, point.y], { priority: "event" });
+    });
+    gd.on("plotly_selected", function(event) {
+      // Plotly also fires this with no event when the selection is cleared.
+      if (event && event.range) {
+        Shiny.setInputValue(id + "_brush", event.range, { priority: "event" });
+      }
+    });
+  }
+
+  // Plots rendered as HTML draw themselves from an inline script.
+  $(document).on("shiny:value", function(event) {
+    if (event.name === "plot1") {
+      setTimeout(function() {
+        $("#plot1 .js-plotly-plot").each(function() {
+          bindPlotEvents("plot1", this);
+        });
+      }, 0);
+    }
+  });
+
+  Shiny.addCustomMessageHandler("render-plotly", function(message) {
+    var el = document.getElementById(message.id);
+    if (el) {
+      // Clear the error Shiny may have rendered into the output.
+      el.className = el.className.replace(/\bshiny-output-error\S*/g, "");
+      Plotly.purge(el);
+      el.innerHTML = "";
+      el.shinyRsBound = false;
+      Plotly.newPlot(el, message.figure.data, message.figure.layout).then(function() {
+        bindPlotEvents(message.id, el);
+      });
+    }
+  });
+
+  // Alternates the page title with `message.text`, `message.times` times.
+  Shiny.addCustomMessageHandler("flash-title", function(message) {
+    var original = document.title;
+    var flashes = message.times * 2;
+    var timer = setInterval(function() {
+      flashes -= 1;
+      document.title = flashes % 2 === 1 ? message.text : original;
+      if (flashes === 0) {
+        clearInterval(timer);
+      }
+    }, 500);
+  });
+
+  // Counts down the seconds left in the idle warning until the modal is
+  // closed or the time is up.
+  Shiny.addCustomMessageHandler("idle-countdown", function(message) {
+    var seconds = message.seconds;
+    var countdown = $(".idle-countdown").text(seconds);
+    var timer = setInterval(function() {
+      seconds = Math.max(seconds - 1, 0);
+      countdown.text(seconds);
+      if (seconds === 0 || !document.body.contains(countdown[0])) {
+        clearInterval(timer);
+      }
+    }, 1000);
+  });
+
+  // Moves the bar `with_progress` rendered into an output.
+  Shiny.addCustomMessageHandler("progress-bar", function(message) {
+    var output = $(document.getElementById(message.id));
+    output.find(".progress-bar").css("width", (message.value * 100) + "%");
+    output.find(".progress-detail").text(message.detail);
+  });
+
+  // Shows or hides elements with Bootstrap's `d-none`, flipping each when
+  // `visible` is null. Shiny is told so it updates the outputs' visibility.
+  Shiny.addCustomMessageHandler("toggle-element", function(message) {
+    $(message.selector).each(function() {
+      var visible = message.visible === null ? $(this).hasClass("d-none") : message.visible;
+      $(this).toggleClass("d-none", !visible).trigger(visible ? "shown" : "hidden");
+    });
+  });
+
+  Shiny.addCustomMessageHandler("set-theme", function(message) {
+    document.getElementById("dark-theme").disabled = !message.dark;
+  });
+
+  Shiny.addCustomMessageHandler("update-query-string", function(message) {
+    window.history.replaceState(null, "", message.search);
+  });
+
+  // Uploads go straight to the upload route instead of through Shiny's
+  // fileInput protocol. The server sends the session's upload URL on start.
+  var uploadUrl = null;
+  Shiny.addCustomMessageHandler("upload-url", function(message) {
+    uploadUrl = message.url;
+  });
+
+  $(document).on("click", "#upload_button", function() {
+    var picker = document.createElement("input");
+    picker.type = "file";
+    picker.accept = ".csv,.txt,text/csv,text/plain";
+    picker.addEventListener("change", function() {
+      if (picker.files.length === 0 || uploadUrl === null) {
+        return;
+      }
+      var data = new FormData();
+      data.append("file", picker.files[0]);
+      $("#upload_status").text("Uploading " + picker.files[0].name + "...");
+      fetch(uploadUrl, { method: "POST", body: data }).then(function(response) {
+        return response.text().then(function(text) {
+          $("#upload_status").text(response.ok ? "" : text);
+        });
+      });
+    });
+    picker.click();
+  });
+
+  // The sample table is paged and sorted by the server.
+  $(document).on("click", "[data-table-sort]", function(event) {
+    event.preventDefault();
+    Shiny.setInputValue("datatable_sort", $(this).data("table-sort"), { priority: "event" });
+  });
+
+  $(document).on("click", "[data-table-page]", function() {
+    Shiny.setInputValue("datatable_page", $(this).data("table-page"), { priority: "event" });
+  });
+
+  $(document).on("click", "[data-restore-run]", function() {
+    Shiny.setInputValue("restore_run", $(this).data("restore-run"), { priority: "event" });
+  });
+
+  // Report dismissed notifications so queued ones can be shown next.
+  new MutationObserver(function(mutations) {
+    mutations.forEach(function(mutation) {
+      mutation.removedNodes.forEach(function(node) {
+        var prefix = "shiny-notification-";
+        if (node.id && node.id.indexOf(prefix) === 0 && node.id !== "shiny-notification-panel") {
+          Shiny.setInputValue(
+            "notification_dismissed",
+            node.id.substring(prefix.length),
+            { priority: "event" }
+          );
+        }
+      });
+    });
+  }).observe(document.body, { childList: true, subtree: true });
+});